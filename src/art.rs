@@ -1,11 +1,15 @@
 use std::collections::BTreeMap;
 
-use raqote::{DrawOptions, DrawTarget, PathBuilder, SolidSource, Source, StrokeStyle};
+use raqote::{DrawOptions, DrawTarget, SolidSource, Source};
 
+use super::canvas::Canvas;
+use super::canvas16::Canvas16;
 use super::color::{ColorDb, ColorKey, ColorSpec};
-use super::config::{Animation, Config, FractionalViewport};
+use super::config::{Animation, FractionalViewport, LayoutOptions, RenderConfig, RenderOptions};
+use super::error::Error;
 use super::layouts::StartPointGroups;
 use super::math::{angle, cos, dist, modulo, pi, rescale, sin};
+use super::progress::{Event, Observer, Silent, Stage, StageTimer};
 use super::rand::Rng;
 use super::sectors::{Collider, Sectors};
 use super::traits::*;
@@ -458,10 +462,10 @@ pub struct ColorScheme {
 }
 
 impl ColorScheme {
-    pub fn from_traits(traits: &Traits, color_db: &ColorDb, rng: &mut Rng) -> Self {
+    pub fn from_traits(traits: &Traits, color_db: &ColorDb, rng: &mut Rng) -> Result<Self, Error> {
         let palette = color_db
             .palette(traits.color_palette)
-            .unwrap_or_else(|| panic!("missing color data for palette {:?}", traits.color_palette));
+            .ok_or(Error::MissingPalette(traits.color_palette))?;
         let bg = rng.wc(&palette.background_colors);
         let substitute = |c: ColorKey| -> Option<ColorKey> {
             bg.substitutions.get(&c).copied().unwrap_or(Some(c))
@@ -524,14 +528,29 @@ impl ColorScheme {
         let splatter_center = (rng.uniform(w(-0.1), w(1.1)), rng.uniform(h(-0.1), h(1.1)));
         let splatter_odds = *rng.wc(splatter_odds_choices);
 
-        ColorScheme {
+        Ok(ColorScheme {
             background: bg.color,
             primary_seq,
             secondary_seq,
             splatter_odds,
             splatter_center,
             splatter_choices,
+        })
+    }
+
+    /// Checks that every color of this scheme is in `color_db`, so that painting with it can't
+    /// fail.
+    fn check_colors(&self, color_db: &ColorDb) -> Result<(), Error> {
+        let keys = std::iter::once(&self.background)
+            .chain(&self.primary_seq)
+            .chain(&self.secondary_seq)
+            .chain(&self.splatter_choices);
+        for &key in keys {
+            if color_db.color(key).is_none() {
+                return Err(Error::InvalidColor(key));
+            }
         }
+        Ok(())
     }
 }
 
@@ -774,13 +793,13 @@ impl GroupedFlowLines {
     }
 }
 
-fn build_sectors(config: &Config) -> Sectors {
+fn build_sectors(options: &LayoutOptions) -> Sectors {
     const CHECK_MARGIN: f64 = 0.05;
     const CHECK_LEFT: f64 = -VIRTUAL_W * CHECK_MARGIN;
     const CHECK_RIGHT: f64 = VIRTUAL_W + VIRTUAL_W * CHECK_MARGIN;
     const CHECK_TOP: f64 = -VIRTUAL_H * CHECK_MARGIN;
     const CHECK_BOTTOM: f64 = VIRTUAL_H + VIRTUAL_H * CHECK_MARGIN;
-    Sectors::new(config, CHECK_LEFT, CHECK_RIGHT, CHECK_TOP, CHECK_BOTTOM)
+    Sectors::new(options, CHECK_LEFT, CHECK_RIGHT, CHECK_TOP, CHECK_BOTTOM)
 }
 
 #[derive(Debug)]
@@ -824,7 +843,12 @@ pub struct Point {
     pub scale: f64,
     pub primary_color: Hsb,
     pub secondary_color: Hsb,
+    /// Palette colors that `primary_color` and `secondary_color` were perturbed from.
+    pub primary_key: ColorKey,
+    pub secondary_key: ColorKey,
     pub bullseye: Bullseye,
+    /// Index of the flow line group that this point was laid out in.
+    pub group: usize,
 }
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Hsb(pub f64, pub f64, pub f64);
@@ -857,7 +881,7 @@ impl Hsb {
 pub struct Rgb(pub f64, pub f64, pub f64);
 
 impl Rgb {
-    pub fn to_solid_source(self) -> SolidSource {
+    pub(crate) fn to_solid_source(self) -> SolidSource {
         SolidSource {
             r: self.0 as u8,
             g: self.1 as u8,
@@ -865,9 +889,13 @@ impl Rgb {
             a: 255,
         }
     }
-    pub fn to_source(self) -> Source<'static> {
+    pub(crate) fn to_source(self) -> Source<'static> {
         Source::Solid(self.to_solid_source())
     }
+    /// Converts to 16 bits per channel, rounding instead of truncating.
+    pub fn to_rgb16(self) -> crate::canvas16::Rgb16 {
+        [self.0, self.1, self.2].map(|c| (c * 257.0).round().clamp(0.0, 65535.0) as u16)
+    }
 }
 
 impl Point {
@@ -922,7 +950,7 @@ impl Points {
 
         let mut all_points = Vec::new();
         let mut group_sizes = Vec::with_capacity(grouped_flow_lines.0.len());
-        for group in grouped_flow_lines.0 {
+        for (group_idx, group) in grouped_flow_lines.0.into_iter().enumerate() {
             if rng.odds(color_change_odds.group) {
                 primary_color_idx =
                     pick_next_color(&color_scheme.primary_seq, primary_color_idx, rng);
@@ -934,6 +962,7 @@ impl Points {
             Self::build_group(
                 &mut all_points,
                 color_db,
+                group_idx,
                 group,
                 color_scheme,
                 primary_color_idx,
@@ -958,6 +987,7 @@ impl Points {
     fn build_group(
         dest: &mut Vec<Point>,
         color_db: &ColorDb,
+        group_idx: usize,
         group: FlowLineGroup,
         color_scheme: &ColorScheme,
         mut primary_color_idx: usize,
@@ -1021,7 +1051,10 @@ impl Points {
                     scale,
                     primary_color,
                     secondary_color,
+                    primary_key: color_scheme.primary_seq[primary_color_idx],
+                    secondary_key: color_scheme.secondary_seq[secondary_color_idx],
                     bullseye,
+                    group: group_idx,
                 })
             }
 
@@ -1083,8 +1116,8 @@ fn perturb_color(color: Hsb, spec: &ColorSpec, rng: &mut Rng) -> Hsb {
     Hsb(hue, sat, bright)
 }
 
-fn adjust_draw_radius(config: &Config, points: &mut [Point]) {
-    if config.inflate_draw_radius {
+fn adjust_draw_radius(options: &LayoutOptions, points: &mut [Point]) {
+    if options.inflate_draw_radius {
         for p in points {
             p.scale = p.scale.max(w(0.00041));
         }
@@ -1114,6 +1147,11 @@ struct PaintCtx<PM: PaintMode> {
     /// Ratio mapping from "virtual space" (used for layout) to "raster space" (actual pixels on
     /// the output `DrawTarget`).
     scale_ratio: f32,
+    /// Layer, flow line group, and palette color of the ring dot band currently being drawn,
+    /// for tagging strokes.
+    layer: Layer,
+    group: usize,
+    color_key: ColorKey,
 }
 
 /// A viewport/crop specification in virtual canvas space, where the horizontal axis ranges from
@@ -1126,6 +1164,12 @@ struct VirtualViewport {
     bottom: f64,
 }
 
+impl VirtualViewport {
+    fn origin(&self) -> (f64, f64) {
+        (self.left, self.top)
+    }
+}
+
 impl<'a> From<&'a FractionalViewport> for VirtualViewport {
     fn from(vp: &'a FractionalViewport) -> Self {
         Self {
@@ -1140,7 +1184,9 @@ impl<'a> From<&'a FractionalViewport> for VirtualViewport {
 /// Computes the actual output canvas dimensions given a viewport and the width of the full virtual
 /// canvas (as passed to `--width`). E.g., if the viewport specifies a width of 25% and the full
 /// width is 1000px, the output width will be 250px.
-fn canvas_dimensions(fvp: &FractionalViewport, full_width: i32) -> (i32, i32) {
+/// Computes the output dimensions in pixels of a render with the given viewport and virtual
+/// canvas width.
+pub fn canvas_dimensions(fvp: &FractionalViewport, full_width: i32) -> (i32, i32) {
     let full_height = ((full_width as i64) * 5 / 4) as i32;
     let w = (f64::from(full_width) * fvp.width()).round() as i32;
     let h = (f64::from(full_height) * fvp.height()).round() as i32;
@@ -1150,8 +1196,8 @@ fn canvas_dimensions(fvp: &FractionalViewport, full_width: i32) -> (i32, i32) {
 impl<PM: PaintMode> PaintCtx<PM> {
     /// Assembles a new paint context.
     ///
-    /// The given `FractionalViewport` overrides any viewport set in the `Config`.
-    fn new(config: &Config, fvp: &FractionalViewport, canvas_width: i32) -> Self {
+    /// The given `FractionalViewport` overrides any viewport set in the `RenderConfig`.
+    fn new(config: &RenderConfig, fvp: &FractionalViewport, canvas_width: i32) -> Self {
         let virtual_vp = VirtualViewport::from(fvp);
 
         let (w, h) = canvas_dimensions(fvp, canvas_width);
@@ -1165,12 +1211,11 @@ impl<PM: PaintMode> PaintCtx<PM> {
             min_circle_steps,
             viewport: virtual_vp,
             scale_ratio,
+            layer: Layer::Normal,
+            group: 0,
+            color_key: 0,
         }
     }
-
-    fn draw_target(&mut self) -> Option<&mut DrawTarget> {
-        PM::as_draw_target(&mut self.maybe_draw_target)
-    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -1180,14 +1225,23 @@ enum Background {
 }
 
 mod paint_mode {
-    use raqote::DrawTarget;
+    use raqote::{DrawOptions, DrawTarget, Path, PathBuilder, SolidSource, StrokeStyle, Transform};
+
+    use super::{Layer, Rgb, Stroke};
+    use crate::canvas16::Canvas16;
 
     pub trait PaintMode {
         type DrawTarget;
         type Components: Send;
 
         fn new_draw_target(width: i32, height: i32) -> Self::DrawTarget;
-        fn as_draw_target(data: &mut Self::DrawTarget) -> Option<&mut DrawTarget>;
+
+        /// Whether this mode does anything with strokes. If not, callers may skip computing them.
+        fn paints() -> bool;
+        fn clear(dt: &mut Self::DrawTarget, color: Rgb);
+        /// Paints a stroke, given the top-left corner of the target in virtual space and the ratio
+        /// from virtual space to raster space.
+        fn stroke(dt: &mut Self::DrawTarget, stroke: &Stroke, origin: (f64, f64), scale_ratio: f32);
 
         fn decompose(dt: Self::DrawTarget) -> Self::Components;
         fn compose(dt: Self::Components) -> Self::DrawTarget;
@@ -1204,6 +1258,99 @@ mod paint_mode {
     #[derive(Debug, Copy, Clone)]
     pub struct Skip;
 
+    /// Do not rasterize, but collect all strokes in paint order, for vector output.
+    #[derive(Debug, Copy, Clone)]
+    pub struct Record;
+
+    /// Paint objects with 16 bits per channel. Raqote rasterizes each stroke to a coverage mask,
+    /// and colors are blended at full precision.
+    #[derive(Debug, Copy, Clone)]
+    pub struct Paint16;
+
+    /// Paint objects exactly as [`Paint`] does, while tracking which layer last changed each
+    /// pixel, for layered output.
+    #[derive(Debug, Copy, Clone)]
+    pub struct PaintLayered;
+
+    pub struct LayeredTarget {
+        pub dt: DrawTarget,
+        /// For each pixel, the layer of the stroke that last changed it, or `None` for the
+        /// background.
+        pub owners: Vec<Option<Layer>>,
+        /// Scratch buffer holding the pixels under a stroke from before it was painted.
+        before: Vec<u32>,
+    }
+
+    pub struct LayeredComponents {
+        components: Components,
+        owners: Vec<Option<Layer>>,
+    }
+
+    pub struct DeepTarget {
+        pub canvas: Canvas16,
+        /// Scratch buffer for coverage masks, reused across strokes to avoid reallocating.
+        scratch: Vec<u32>,
+        mask: Vec<u8>,
+    }
+
+    impl From<Canvas16> for DeepTarget {
+        fn from(canvas: Canvas16) -> Self {
+            DeepTarget {
+                canvas,
+                scratch: Vec::new(),
+                mask: Vec::new(),
+            }
+        }
+    }
+
+    fn stroke_path(stroke: &Stroke, origin: (f64, f64), scale_ratio: f32) -> Path {
+        let mut pb = PathBuilder::new();
+        for (i, (x, y)) in stroke.vertices(origin).enumerate() {
+            let (x, y) = (x as f32 * scale_ratio, y as f32 * scale_ratio);
+            if i == 0 {
+                pb.move_to(x, y);
+            } else {
+                pb.line_to(x, y);
+            }
+        }
+        pb.close();
+        pb.finish()
+    }
+
+    /// Computes a raster-space box `(left, top, width, height)` containing every pixel that a
+    /// stroke may touch, clipped to a canvas of the given size, or `None` if the stroke is
+    /// entirely off the canvas.
+    fn stroke_bounds(
+        stroke: &Stroke,
+        origin: (f64, f64),
+        scale_ratio: f32,
+        (canvas_w, canvas_h): (i32, i32),
+    ) -> Option<(i32, i32, i32, i32)> {
+        let scale = f64::from(scale_ratio);
+        let (cx, cy) = (stroke.center.0 - origin.0, stroke.center.1 - origin.1);
+        // Miter joins can reach past half the stroke width, but not past the full width. Add a
+        // pixel of slack for antialiasing.
+        let (rx, ry) = (
+            stroke.rx.abs() + stroke.width,
+            stroke.ry.abs() + stroke.width,
+        );
+        let left = (((cx - rx) * scale).floor() as i32 - 1).max(0);
+        let top = (((cy - ry) * scale).floor() as i32 - 1).max(0);
+        let right = (((cx + rx) * scale).ceil() as i32 + 1).min(canvas_w);
+        let bottom = (((cy + ry) * scale).ceil() as i32 + 1).min(canvas_h);
+        if left >= right || top >= bottom {
+            return None;
+        }
+        Some((left, top, right - left, bottom - top))
+    }
+
+    fn stroke_style(stroke: &Stroke, scale_ratio: f32) -> StrokeStyle {
+        StrokeStyle {
+            width: stroke.width as f32 * scale_ratio,
+            ..StrokeStyle::default()
+        }
+    }
+
     pub struct Components {
         width: i32,
         height: i32,
@@ -1217,8 +1364,25 @@ mod paint_mode {
         fn new_draw_target(width: i32, height: i32) -> Self::DrawTarget {
             DrawTarget::new(width, height)
         }
-        fn as_draw_target(data: &mut DrawTarget) -> Option<&mut DrawTarget> {
-            Some(data)
+
+        fn paints() -> bool {
+            true
+        }
+        fn clear(dt: &mut Self::DrawTarget, color: Rgb) {
+            dt.clear(color.to_solid_source());
+        }
+        fn stroke(
+            dt: &mut Self::DrawTarget,
+            stroke: &Stroke,
+            origin: (f64, f64),
+            scale_ratio: f32,
+        ) {
+            dt.stroke(
+                &stroke_path(stroke, origin, scale_ratio),
+                &stroke.color.to_rgb().to_source(),
+                &stroke_style(stroke, scale_ratio),
+                &DrawOptions::new(),
+            );
         }
 
         fn decompose(dt: Self::DrawTarget) -> Self::Components {
@@ -1250,9 +1414,12 @@ mod paint_mode {
         type Components = ();
 
         fn new_draw_target(_width: i32, _height: i32) -> Self::DrawTarget {}
-        fn as_draw_target(_data: &mut ()) -> Option<&mut DrawTarget> {
-            None
+
+        fn paints() -> bool {
+            false
         }
+        fn clear(_dt: &mut Self::DrawTarget, _color: Rgb) {}
+        fn stroke(_dt: &mut Self::DrawTarget, _: &Stroke, _: (f64, f64), _: f32) {}
 
         fn decompose(_dt: Self::DrawTarget) -> Self::Components {}
         fn compose(_components: Self::Components) -> Self::DrawTarget {}
@@ -1263,6 +1430,203 @@ mod paint_mode {
             false
         }
     }
+
+    impl PaintMode for Paint16 {
+        type DrawTarget = DeepTarget;
+        type Components = Canvas16;
+
+        fn new_draw_target(width: i32, height: i32) -> Self::DrawTarget {
+            Canvas16::new(width, height).into()
+        }
+
+        fn paints() -> bool {
+            true
+        }
+        fn clear(dt: &mut Self::DrawTarget, color: Rgb) {
+            dt.canvas.clear(color.to_rgb16());
+        }
+        fn stroke(
+            dt: &mut Self::DrawTarget,
+            stroke: &Stroke,
+            origin: (f64, f64),
+            scale_ratio: f32,
+        ) {
+            // Rasterize only the stroke's bounding box.
+            let canvas_dims = (dt.canvas.width(), dt.canvas.height());
+            let Some((left, top, width, height)) =
+                stroke_bounds(stroke, origin, scale_ratio, canvas_dims)
+            else {
+                return;
+            };
+
+            let mut scratch = std::mem::take(&mut dt.scratch);
+            scratch.clear();
+            scratch.resize((width * height) as usize, 0);
+            let mut mask_dt = DrawTarget::from_backing(width, height, scratch);
+            mask_dt.set_transform(&Transform::translation(-left as f32, -top as f32));
+            mask_dt.stroke(
+                &stroke_path(stroke, origin, scale_ratio),
+                &raqote::Source::Solid(SolidSource::from_unpremultiplied_argb(
+                    0xff, 0xff, 0xff, 0xff,
+                )),
+                &stroke_style(stroke, scale_ratio),
+                &DrawOptions::new(),
+            );
+            let scratch = mask_dt.into_inner();
+            dt.mask.clear();
+            dt.mask.extend(scratch.iter().map(|px| (px >> 24) as u8));
+            dt.scratch = scratch;
+
+            dt.canvas.fill_mask(
+                &dt.mask,
+                width,
+                (left, top),
+                stroke.color.to_rgb().to_rgb16(),
+            );
+        }
+
+        fn decompose(dt: Self::DrawTarget) -> Self::Components {
+            dt.canvas
+        }
+        fn compose(components: Self::Components) -> Self::DrawTarget {
+            components.into()
+        }
+        fn superimpose(dt: &mut Self::DrawTarget, components: &Self::Components, x: i32, y: i32) {
+            dt.canvas.draw_canvas_at(components, (x, y))
+        }
+
+        fn respect_chunks() -> bool {
+            true
+        }
+    }
+
+    impl PaintMode for PaintLayered {
+        type DrawTarget = LayeredTarget;
+        type Components = LayeredComponents;
+
+        fn new_draw_target(width: i32, height: i32) -> Self::DrawTarget {
+            LayeredTarget {
+                dt: Paint::new_draw_target(width, height),
+                owners: vec![None; (width * height) as usize],
+                before: Vec::new(),
+            }
+        }
+
+        fn paints() -> bool {
+            true
+        }
+        fn clear(dt: &mut Self::DrawTarget, color: Rgb) {
+            Paint::clear(&mut dt.dt, color);
+            dt.owners.fill(None);
+        }
+        fn stroke(
+            dt: &mut Self::DrawTarget,
+            stroke: &Stroke,
+            origin: (f64, f64),
+            scale_ratio: f32,
+        ) {
+            let canvas_width = dt.dt.width();
+            let canvas_dims = (canvas_width, dt.dt.height());
+            let Some((left, top, width, height)) =
+                stroke_bounds(stroke, origin, scale_ratio, canvas_dims)
+            else {
+                return;
+            };
+            let rows = move || {
+                (top..top + height).map(move |y| {
+                    let start = (y * canvas_width + left) as usize;
+                    start..start + width as usize
+                })
+            };
+
+            dt.before.clear();
+            for row in rows() {
+                dt.before.extend_from_slice(&dt.dt.get_data()[row]);
+            }
+            Paint::stroke(&mut dt.dt, stroke, origin, scale_ratio);
+            let data = dt.dt.get_data();
+            for (row, before) in rows().zip(dt.before.chunks_exact(width as usize)) {
+                for ((owner, after), before) in dt.owners[row.clone()]
+                    .iter_mut()
+                    .zip(&data[row])
+                    .zip(before)
+                {
+                    if after != before {
+                        *owner = Some(stroke.layer);
+                    }
+                }
+            }
+        }
+
+        fn decompose(dt: Self::DrawTarget) -> Self::Components {
+            LayeredComponents {
+                components: Paint::decompose(dt.dt),
+                owners: dt.owners,
+            }
+        }
+        fn compose(components: Self::Components) -> Self::DrawTarget {
+            LayeredTarget {
+                dt: Paint::compose(components.components),
+                owners: components.owners,
+                before: Vec::new(),
+            }
+        }
+        fn superimpose(dt: &mut Self::DrawTarget, components: &Self::Components, x: i32, y: i32) {
+            Paint::superimpose(&mut dt.dt, &components.components, x, y);
+            // Chunks are opaque, so their owners replace ours wherever they overlap.
+            let (src_w, dst_w, dst_h) =
+                (components.components.width, dt.dt.width(), dt.dt.height());
+            let (x0, x1) = (x.max(0), (x + src_w).min(dst_w));
+            if x0 >= x1 {
+                return;
+            }
+            for src_y in 0..components.components.height {
+                let dst_y = y + src_y;
+                if !(0..dst_h).contains(&dst_y) {
+                    continue;
+                }
+                let src = (src_y * src_w + (x0 - x)) as usize;
+                let dst = (dst_y * dst_w + x0) as usize;
+                let n = (x1 - x0) as usize;
+                dt.owners[dst..dst + n].copy_from_slice(&components.owners[src..src + n]);
+            }
+        }
+
+        fn respect_chunks() -> bool {
+            true
+        }
+    }
+
+    impl PaintMode for Record {
+        type DrawTarget = Vec<Stroke>;
+        type Components = Vec<Stroke>;
+
+        fn new_draw_target(_width: i32, _height: i32) -> Self::DrawTarget {
+            Vec::new()
+        }
+
+        fn paints() -> bool {
+            true
+        }
+        fn clear(_dt: &mut Self::DrawTarget, _color: Rgb) {}
+        fn stroke(dt: &mut Self::DrawTarget, stroke: &Stroke, _: (f64, f64), _: f32) {
+            dt.push(*stroke);
+        }
+
+        fn decompose(dt: Self::DrawTarget) -> Self::Components {
+            dt
+        }
+        fn compose(components: Self::Components) -> Self::DrawTarget {
+            components
+        }
+        fn superimpose(dt: &mut Self::DrawTarget, components: &Self::Components, _: i32, _: i32) {
+            dt.extend_from_slice(components);
+        }
+
+        fn respect_chunks() -> bool {
+            false
+        }
+    }
 }
 use paint_mode::PaintMode;
 
@@ -1290,45 +1654,136 @@ enum SplatterSink<'a> {
     Ignored,
 }
 
+fn background_color(color_db: &ColorDb, color_scheme: &ColorScheme) -> Hsb {
+    let spec = color_db
+        .color(color_scheme.background)
+        .expect("invalid background");
+    Hsb(spec.hue, spec.sat, spec.bright)
+}
+
 #[allow(clippy::too_many_arguments)]
 fn render<PM: PaintMode>(
     canvas_width: i32,
     background: Background,
     traits: &Traits,
     color_db: &ColorDb,
-    config: &Config,
+    config: &RenderConfig,
+    stack_offset: &StackOffset,
+    normal_points: NormalPoints<'_>,
+    extra_splatter_points: &[Point],
+    color_scheme: &ColorScheme,
+    colors_used: &mut ColorsUsed,
+    rng: &mut Rng,
+    observer: &dyn Observer,
+) -> Result<PM::DrawTarget, Error> {
+    let full_fvp = config.viewport.as_ref().cloned().unwrap_or_default();
+    let canvas_dims = canvas_dimensions(&full_fvp, canvas_width);
+    let mut result: Option<PM::DrawTarget> = None;
+    render_chunks::<PM, _>(
+        canvas_width,
+        1,
+        background,
+        traits,
+        color_db,
+        config,
+        stack_offset,
+        normal_points,
+        extra_splatter_points,
+        color_scheme,
+        colors_used,
+        rng,
+        observer,
+        |chunk: ChunkOutput<PM>| {
+            // Skip compositing if there's only one chunk.
+            if (chunk.left_px, chunk.top_px) == (0, 0) && chunk.dims == canvas_dims {
+                result = Some(PM::compose(chunk.components));
+                return;
+            }
+            let dt =
+                result.get_or_insert_with(|| PM::new_draw_target(canvas_dims.0, canvas_dims.1));
+            PM::superimpose(dt, &chunk.components, chunk.left_px, chunk.top_px);
+        },
+    )?;
+    Ok(result.unwrap_or_else(|| PM::new_draw_target(canvas_dims.0, canvas_dims.1)))
+}
+
+/// A painted chunk of the output, as produced by [`render_chunks`].
+struct ChunkOutput<PM: PaintMode> {
+    left_px: i32,
+    top_px: i32,
+    dims: (i32, i32),
+    components: PM::Components,
+}
+
+/// Paints the canvas in chunks according to `config.chunks`, handing each chunk to
+/// `consume_chunk` on the calling thread as it finishes. Chunks are disjoint and arrive in no
+/// particular order. Chunk boundaries are rounded to multiples of `align` pixels (except at the
+/// right and bottom edges of the canvas), and chunks that round to nothing are skipped.
+///
+/// At most one chunk per available core is painted at a time, so peak memory use scales with the
+/// chunk size rather than the canvas size.
+///
+/// Chunks and painted points are reported to `observer` as they go.
+///
+/// Fails before painting anything if `color_scheme` has colors that aren't in `color_db`.
+#[allow(clippy::too_many_arguments)]
+fn render_chunks<PM: PaintMode, F: FnMut(ChunkOutput<PM>)>(
+    canvas_width: i32,
+    align: i32,
+    background: Background,
+    traits: &Traits,
+    color_db: &ColorDb,
+    config: &RenderConfig,
     stack_offset: &StackOffset,
     mut normal_points: NormalPoints<'_>,
     extra_splatter_points: &[Point],
     color_scheme: &ColorScheme,
     colors_used: &mut ColorsUsed,
     rng: &mut Rng,
-) -> PM::DrawTarget {
+    observer: &dyn Observer,
+    mut consume_chunk: F,
+) -> Result<(), Error> {
     let full_fvp = &config.viewport.as_ref().cloned().unwrap_or_default();
 
-    let background_color = {
-        let spec = color_db
-            .color(color_scheme.background)
-            .expect("invalid background");
-        Hsb(spec.hue, spec.sat, spec.bright)
-            .to_rgb()
-            .to_solid_source()
-    };
+    color_scheme.check_colors(color_db)?;
+    let background_color = background_color(color_db, color_scheme).to_rgb();
 
     let (hsteps, vsteps): (u32, u32) = if PM::respect_chunks() {
         (config.chunks.w.into(), config.chunks.h.into())
     } else {
         (1, 1)
     };
-    let num_chunks: usize = (hsteps * vsteps).try_into().expect("too many chunks!");
+    let num_chunks = hsteps.checked_mul(vsteps).map(usize::try_from);
+    if !matches!(num_chunks, Some(Ok(_))) {
+        return Err(Error::TooManyChunks {
+            w: hsteps,
+            h: vsteps,
+        });
+    }
 
     let canvas_dims = canvas_dimensions(full_fvp, canvas_width);
     let chunk_origin = |chunk_x: u32, chunk_y: u32| -> (i32, i32) {
         let (w, h) = canvas_dims;
-        let x = f64::from(w) * (f64::from(chunk_x) / f64::from(hsteps));
-        let y = f64::from(h) * (f64::from(chunk_y) / f64::from(vsteps));
-        (x.round() as i32, y.round() as i32)
+        let x = f64::from(w) * (f64::from(chunk_x) / f64::from(hsteps)) / f64::from(align);
+        let y = f64::from(h) * (f64::from(chunk_y) / f64::from(vsteps)) / f64::from(align);
+        let snap = |v: f64, step: u32, steps: u32, max: i32| {
+            if step == steps {
+                max
+            } else {
+                (v.round() as i32 * align).min(max)
+            }
+        };
+        (snap(x, chunk_x, hsteps, w), snap(y, chunk_y, vsteps, h))
     };
+    let chunks: Vec<(u32, u32)> = (0..hsteps)
+        .flat_map(|x| (0..vsteps).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            let (left, top) = chunk_origin(x, y);
+            let (right, bottom) = chunk_origin(x + 1, y + 1);
+            // Always keep the first chunk, so that even an empty canvas advances the RNG.
+            (left < right && top < bottom) || (x, y) == (0, 0)
+        })
+        .collect();
 
     // Pull some `Sync` values off `normal_points`, just the bits that worker threads need.
     let (normal_points_slice, splatter_sink_immediate): (&[Point], bool) = match normal_points {
@@ -1338,12 +1793,17 @@ fn render<PM: PaintMode>(
         } => (points, matches!(splatter_sink, SplatterSink::Immediate)),
         NormalPoints::None => (&[], false),
     };
+    let points_painted = std::sync::atomic::AtomicUsize::new(0);
+    let report_points = |n: usize| {
+        let painted = points_painted.fetch_add(n, std::sync::atomic::Ordering::Relaxed) + n;
+        observer.on_event(&Event::PointsPainted {
+            painted,
+            total: normal_points_slice.len() * chunks.len(),
+        });
+    };
 
     struct Output<PM: PaintMode> {
-        left_px: i32,
-        top_px: i32,
-        // `DrawTarget` is `!Send`, so we break it down into its components.
-        dt_components: PM::Components,
+        chunk: ChunkOutput<PM>,
         colors_used: ColorsUsed,
         splatter_points: Vec<Point>,
         rng: Rng,
@@ -1352,10 +1812,15 @@ fn render<PM: PaintMode>(
         let (left_px, top_px) = chunk_origin(x, y);
         let (right_px, bottom_px) = chunk_origin(x + 1, y + 1);
         let (width_px, height_px) = (right_px - left_px, bottom_px - top_px);
-        eprintln!(
-            "painting chunk ({}, {}): {}x{}+{}+{}px",
-            x, y, width_px, height_px, left_px, top_px
-        );
+        observer.on_event(&Event::ChunkStarted {
+            column: x,
+            row: y,
+            left: left_px,
+            top: top_px,
+            width: width_px,
+            height: height_px,
+        });
+        let start = std::time::Instant::now();
         let (width_ratio, height_ratio) = (
             full_fvp.width() / f64::from(canvas_dims.0),
             full_fvp.height() / f64::from(canvas_dims.1),
@@ -1367,10 +1832,9 @@ fn render<PM: PaintMode>(
             f64::from(top_px) * height_ratio + full_fvp.top(),
         );
         let mut pctx = PaintCtx::<PM>::new(config, &fvp, canvas_width);
-        match (background, pctx.draw_target()) {
-            (Background::Transparent, _) => (),
-            (Background::Opaque, None) => (),
-            (Background::Opaque, Some(dt)) => dt.clear(background_color),
+        match background {
+            Background::Transparent => (),
+            Background::Opaque => PM::clear(&mut pctx.maybe_draw_target, background_color),
         };
         let mut colors_used = ColorsUsed::new();
         let mut new_splatter_points = Vec::new();
@@ -1382,6 +1846,7 @@ fn render<PM: PaintMode>(
             color_scheme,
             &mut new_splatter_points,
             &mut rng,
+            &report_points,
         );
         if splatter_sink_immediate {
             paint_splatter_points(
@@ -1402,10 +1867,19 @@ fn render<PM: PaintMode>(
             &mut colors_used,
             &mut rng,
         );
+        observer.on_event(&Event::ChunkFinished {
+            column: x,
+            row: y,
+            elapsed: start.elapsed(),
+        });
         Output {
-            left_px,
-            top_px,
-            dt_components: PM::decompose(pctx.maybe_draw_target),
+            chunk: ChunkOutput {
+                left_px,
+                top_px,
+                dims: (width_px, height_px),
+                // `DrawTarget` is `!Send`, so we break it down into its components.
+                components: PM::decompose(pctx.maybe_draw_target),
+            },
             colors_used,
             splatter_points: new_splatter_points,
             rng,
@@ -1422,62 +1896,69 @@ fn render<PM: PaintMode>(
         },
     };
 
-    // Skip compositing if there's only one chunk.
-    if num_chunks == 1 {
-        let output = process_chunk(0, 0, rng.clone());
+    // Skip threading if there's only one chunk.
+    if let [(x, y)] = chunks[..] {
+        let output = process_chunk(x, y, rng.clone());
         *rng = output.rng;
         process_splatters(&output.splatter_points);
         colors_used.extend(&output.colors_used);
-        return PM::compose(output.dt_components);
+        consume_chunk(output.chunk);
+        return Ok(());
     }
 
-    // Otherwise, render each chunk in its own thread, compositing as we go on the main thread.
-    let (tx_output, rx_output) = std::sync::mpsc::sync_channel::<Output<PM>>(num_chunks);
+    // Otherwise, render chunks on a pool of worker threads, consuming them as we go on the
+    // calling thread.
+    let num_workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(chunks.len());
+    let next_chunk = std::sync::atomic::AtomicUsize::new(0);
+    let start_rng = rng.clone();
+    let (tx_output, rx_output) = std::sync::mpsc::sync_channel::<Output<PM>>(num_workers);
     std::thread::scope(|s| {
-        for x in 0..hsteps {
-            for y in 0..vsteps {
-                let rng = rng.clone();
-                let tx_output = tx_output.clone();
-                s.spawn(move || {
-                    let output = process_chunk(x, y, rng);
-                    tx_output.send(output).unwrap();
-                });
-            }
+        for _ in 0..num_workers {
+            let tx_output = tx_output.clone();
+            let (chunks, next_chunk, process_chunk, rng) =
+                (&chunks, &next_chunk, &process_chunk, &start_rng);
+            s.spawn(move || loop {
+                let i = next_chunk.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let Some(&(x, y)) = chunks.get(i) else {
+                    break;
+                };
+                let output = process_chunk(x, y, rng.clone());
+                if tx_output.send(output).is_err() {
+                    break;
+                }
+            });
         }
         drop(tx_output);
+        // Own the receiver, so that returning early drops it and unblocks the workers.
+        let rx_output = rx_output;
 
-        let mut pctx_final = PaintCtx::<PM>::new(config, full_fvp, canvas_width);
-        let mut chunks_composited = 0;
-        let mut expected_splatter_points = None;
+        let mut chunks_consumed = 0;
+        let mut final_rng = None;
         while let Ok(output) = rx_output.recv() {
-            if chunks_composited == 0 {
-                *rng = output.rng;
+            if chunks_consumed == 0 {
+                final_rng = Some(output.rng);
                 process_splatters(&output.splatter_points);
-                expected_splatter_points = Some(output.splatter_points);
-            } else {
-                if output.rng != *rng {
-                    panic!("rng state mismatch");
-                }
-                if cfg!(debug)
-                    && output.splatter_points != *expected_splatter_points.as_ref().unwrap()
-                {
-                    panic!("new splatter points mismatch");
-                }
+            } else if Some(&output.rng) != final_rng.as_ref() {
+                return Err(Error::ChunkMismatch);
             }
-            PM::superimpose(
-                &mut pctx_final.maybe_draw_target,
-                &output.dt_components,
-                output.left_px,
-                output.top_px,
-            );
             colors_used.extend(&output.colors_used);
-            chunks_composited += 1;
+            consume_chunk(output.chunk);
+            chunks_consumed += 1;
         }
-        assert_eq!(chunks_composited, num_chunks, "missing some chunks");
-        pctx_final.maybe_draw_target
+        assert_eq!(chunks_consumed, chunks.len(), "missing some chunks");
+        *rng = final_rng.expect("no chunks");
+        Ok(())
     })
 }
 
+/// How many points [`paint_normal_points`] paints between progress reports.
+const POINTS_PER_REPORT: usize = 100;
+
+/// Paints `points` and collects their splatter points, calling `report_points` with the number
+/// of points painted since the last call every so often and once more at the end.
+#[allow(clippy::too_many_arguments)]
 fn paint_normal_points<PM: PaintMode>(
     pctx: &mut PaintCtx<PM>,
     traits: &Traits,
@@ -1486,8 +1967,10 @@ fn paint_normal_points<PM: PaintMode>(
     color_scheme: &ColorScheme,
     splatter_points: &mut Vec<Point>,
     rng: &mut Rng,
+    report_points: &dyn Fn(usize),
 ) {
     let is_zebra = matches!(traits.color_mode, ColorMode::Zebra);
+    let mut unreported = 0;
     for p in points {
         let (x, y) = p.position;
 
@@ -1508,24 +1991,34 @@ fn paint_normal_points<PM: PaintMode>(
                 &Point {
                     position: (x + xoff, y + yoff),
                     primary_color: p.secondary_color,
+                    primary_key: p.secondary_key,
                     bullseye: Bullseye {
                         density: p.bullseye.density * rng.gauss(0.99, 0.03),
                         rings: p.bullseye.rings,
                     },
                     ..p.clone()
                 },
+                Layer::Shadow,
                 pctx,
                 rng,
             );
         }
         if is_zebra {
-            draw_ring_dot(p, pctx, rng);
+            draw_ring_dot(p, Layer::Normal, pctx, rng);
         } else {
             let mut p = p.clone();
             p.secondary_color = p.primary_color;
-            draw_ring_dot(&p, pctx, rng);
+            draw_ring_dot(&p, Layer::Normal, pctx, rng);
+        }
+        unreported += 1;
+        if unreported == POINTS_PER_REPORT {
+            report_points(unreported);
+            unreported = 0;
         }
     }
+    if unreported > 0 {
+        report_points(unreported);
+    }
 }
 
 fn paint_splatter_points<PM: PaintMode>(
@@ -1547,12 +2040,17 @@ fn paint_splatter_points<PM: PaintMode>(
         let mut p = p.clone();
         p.primary_color = final_color;
         p.secondary_color = final_color;
+        p.primary_key = splatter_color;
+        p.secondary_key = splatter_color;
         p.bullseye.density = f64::max(0.17, p.bullseye.density * 0.7);
-        draw_ring_dot(&p, pctx, rng);
+        draw_ring_dot(&p, Layer::Splatter, pctx, rng);
     }
 }
 
-fn draw_ring_dot<PM: PaintMode>(pt: &Point, pctx: &mut PaintCtx<PM>, rng: &mut Rng) {
+fn draw_ring_dot<PM: PaintMode>(pt: &Point, layer: Layer, pctx: &mut PaintCtx<PM>, rng: &mut Rng) {
+    pctx.layer = layer;
+    pctx.group = pt.group;
+
     let num_rings = pt.num_drawn_rings();
     let band_step = pt.scale / num_rings as f64;
 
@@ -1570,11 +2068,12 @@ fn draw_ring_dot<PM: PaintMode>(pt: &Point, pctx: &mut PaintCtx<PM>, rng: &mut R
     let mut band_num = 0;
     let mut r = pt.scale;
     while r > w(0.0004) {
-        let color = if band_num % 2 == 0 {
-            pt.primary_color
+        let (color, color_key) = if band_num % 2 == 0 {
+            (pt.primary_color, pt.primary_key)
         } else {
-            pt.secondary_color
+            (pt.secondary_color, pt.secondary_key)
         };
+        pctx.color_key = color_key;
         band_num += 1;
 
         let band_center_x = rng.gauss(pt.position.0, w(0.0005).min(r * position_variance));
@@ -1617,8 +2116,6 @@ fn draw_messy_circle<PM: PaintMode>(
     pctx: &mut PaintCtx<PM>,
     rng: &mut Rng,
 ) {
-    let source = color.to_rgb().to_source();
-
     let num_rounds_divisor = if thickness > w(0.02) {
         rescale(thickness, (w(0.02), w(0.04)), (w(0.00021), w(0.00022)))
     } else if thickness > w(0.006) {
@@ -1661,7 +2158,7 @@ fn draw_messy_circle<PM: PaintMode>(
         let thickness = rng
             .gauss(mean_thickness, single_line_variance)
             .max(w(0.0002));
-        draw_clean_circle((x, y), r, thickness, 0.007, &source, pctx, rng);
+        draw_clean_circle((x, y), r, thickness, 0.007, color, pctx, rng);
     }
 }
 
@@ -1670,7 +2167,7 @@ fn draw_clean_circle<PM: PaintMode>(
     r: f64,
     thickness: f64,
     eccentricity: f64,
-    source: &Source,
+    color: Hsb,
     pctx: &mut PaintCtx<PM>,
     rng: &mut Rng,
 ) {
@@ -1685,50 +2182,105 @@ fn draw_clean_circle<PM: PaintMode>(
     // We don't need to compute that, but we need to burn a uniform deviate to keep RNG synced.
     rng.rnd();
 
-    if let Some(dt) = PM::as_draw_target(&mut pctx.maybe_draw_target) {
-        if x + (rx + stroke_weight / 2.0) < pctx.viewport.left
-            || x - (rx + stroke_weight / 2.0) > pctx.viewport.right
-            || y + (ry + stroke_weight / 2.0) < pctx.viewport.top
-            || y - (ry + stroke_weight / 2.0) > pctx.viewport.bottom
-        {
-            // Circle is entirely outside viewport; skip painting it. There are no more stateful RNG
-            // calls past this point, so we can bail entirely, skipping `dt.stroke` (vast majority of
-            // time spent) and also the trigonometric functions (not nearly as expensive but do show up
-            // on the profile).
-            return;
-        }
+    if !PM::paints() {
+        return;
+    }
+    if x + (rx + stroke_weight / 2.0) < pctx.viewport.left
+        || x - (rx + stroke_weight / 2.0) > pctx.viewport.right
+        || y + (ry + stroke_weight / 2.0) < pctx.viewport.top
+        || y - (ry + stroke_weight / 2.0) > pctx.viewport.bottom
+    {
+        // Circle is entirely outside viewport; skip painting it. There are no more stateful RNG
+        // calls past this point, so we can bail entirely, skipping `dt.stroke` (vast majority of
+        // time spent) and also the trigonometric functions (not nearly as expensive but do show up
+        // on the profile).
+        return;
+    }
 
-        let num_steps = (r * pi(2.0) / w(0.0005)).max(pctx.min_circle_steps);
-        let step = pi(2.0) / num_steps;
+    let stroke = Stroke {
+        center: (x, y),
+        rx,
+        ry,
+        width: stroke_weight,
+        steps: (r * pi(2.0) / w(0.0005)).max(pctx.min_circle_steps),
+        color,
+        color_key: pctx.color_key,
+        layer: pctx.layer,
+        group: pctx.group,
+    };
+    let origin = pctx.viewport.origin();
+    PM::stroke(
+        &mut pctx.maybe_draw_target,
+        &stroke,
+        origin,
+        pctx.scale_ratio,
+    );
+}
 
-        let mut pb = PathBuilder::new();
-        let mut theta = 0.0;
-        while theta < pi(2.0) {
-            let x = (x - pctx.viewport.left + rx * theta.cos()) as f32 * pctx.scale_ratio;
-            let y = (y - pctx.viewport.top + ry * theta.sin()) as f32 * pctx.scale_ratio;
-            if theta == 0.0 {
-                pb.move_to(x, y);
-            } else {
-                pb.line_to(x, y);
-            }
-            theta += step;
+/// Which painting phase a stroke belongs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Layer {
+    /// The offset copy of a point drawn under it in [`ColorMode::Stacked`] pieces.
+    Shadow,
+    /// A point laid out along a flow line.
+    Normal,
+    /// A splatter copy of a normal point, painted in a splatter color.
+    Splatter,
+}
+
+impl Layer {
+    /// All layers, in the order that they're first painted.
+    pub const ALL: [Layer; 3] = [Layer::Shadow, Layer::Normal, Layer::Splatter];
+
+    /// A short lowercase name, like `"shadow"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Shadow => "shadow",
+            Layer::Normal => "normal",
+            Layer::Splatter => "splatter",
         }
-        pb.close();
-        let path = pb.finish();
-
-        dt.stroke(
-            &path,
-            source,
-            &StrokeStyle {
-                width: stroke_weight as f32 * pctx.scale_ratio,
-                ..StrokeStyle::default()
-            },
-            &DrawOptions::new(),
-        );
     }
 }
 
-fn as_image(dt: &DrawTarget) -> raqote::Image {
+/// A single stroked ellipse, as painted by the renderer. All coordinates are in virtual canvas
+/// space.
+///
+/// The raster painter approximates each ellipse with a closed polygon of [`Stroke::vertices`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stroke {
+    pub center: (f64, f64),
+    pub rx: f64,
+    pub ry: f64,
+    /// Width of the stroke, centered on the ellipse outline.
+    pub width: f64,
+    /// Number of polygon segments used to approximate the ellipse. Not necessarily an integer.
+    pub steps: f64,
+    pub color: Hsb,
+    /// The palette color that `color` was perturbed from.
+    pub color_key: ColorKey,
+    pub layer: Layer,
+    /// Index of the flow line group of the point that generated this stroke.
+    pub group: usize,
+}
+
+impl Stroke {
+    /// Iterates over the vertices of the polygon approximating this ellipse, relative to the given
+    /// origin in virtual canvas space.
+    pub fn vertices(&self, (ox, oy): (f64, f64)) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let (x, y) = self.center;
+        let step = pi(2.0) / self.steps;
+        std::iter::successors(Some(0.0), move |theta| Some(theta + step))
+            .take_while(|&theta| theta < pi(2.0))
+            .map(move |theta: f64| {
+                (
+                    x - ox + self.rx * theta.cos(),
+                    y - oy + self.ry * theta.sin(),
+                )
+            })
+    }
+}
+
+fn as_image(dt: &DrawTarget) -> raqote::Image<'_> {
     raqote::Image {
         width: dt.width(),
         height: dt.height(),
@@ -1739,302 +2291,933 @@ fn superimpose(onto: &mut DrawTarget, layer: raqote::Image, (x, y): (i32, i32))
     onto.draw_image_at(x as f32, y as f32, &layer, &DrawOptions::new());
 }
 
+/// A finished chunk of a render, as passed to the callback of [`draw_chunks`].
+pub struct Chunk<'a> {
+    /// Position of the chunk's top-left corner in the output, in pixels.
+    pub left: i32,
+    pub top: i32,
+    pub canvas: &'a Canvas,
+}
+
 pub struct Frame<'a> {
-    pub dt: &'a DrawTarget,
+    pub canvas: &'a Canvas,
     pub number: Option<u32>,
 }
-pub struct RenderData {
-    pub canvas: DrawTarget,
+pub struct RenderData<C = Canvas> {
+    pub canvas: C,
     pub num_points: usize,
     pub colors_used: ColorsUsed,
     pub ring_counts_used: BTreeMap<RingCount, usize>,
 }
 
-pub fn draw<F: FnMut(Frame)>(
-    seed: &[u8; 32],
-    color_db: &ColorDb,
-    config: &Config,
-    canvas_width: i32,
-    mut consume_frame: F,
-) -> RenderData {
-    let traits = Traits::from_seed(seed);
-    let mut rng = Rng::from_seed(&seed[..]);
-    eprintln!("initialized traits");
-
-    let flow_field_spec = FlowFieldSpec::from_traits(&traits, &mut rng);
-    let spacing_spec = SpacingSpec::from_traits(&traits, &mut rng);
-    let color_change_odds = ColorChangeOdds::from_traits(&traits, &mut rng);
-    let mut scale_generator = ScaleGenerator::from_traits(&traits, &mut rng);
-    let mut bullseye_generator = BullseyeGenerator::from_traits(&traits, &mut rng);
-    let color_scheme = ColorScheme::from_traits(&traits, color_db, &mut rng);
-
-    let flow_field = FlowField::build(&flow_field_spec, &traits, &mut rng);
-    eprintln!("built flow field");
-    let ignore_flow_field = IgnoreFlowField::build(&flow_field_spec, &mut rng);
-    let start_points = StartPointGroups::build(traits.structure, &mut rng);
-
-    let grouped_flow_lines =
-        GroupedFlowLines::build(flow_field, ignore_flow_field, start_points, &mut rng);
-    let mut sectors: Sectors = build_sectors(config);
-    let mut colors_used = ColorsUsed::new();
-    let (mut points, group_sizes) = Points::build(
-        &traits,
-        color_db,
-        grouped_flow_lines,
-        &color_scheme,
-        &color_change_odds,
-        &spacing_spec,
-        &mut bullseye_generator,
-        &mut scale_generator,
-        &mut sectors,
-        &mut colors_used,
-        &mut rng,
-    );
-    eprintln!("laid out points");
-    let num_points = points.0.len();
-    let mut ring_counts_used = BTreeMap::new();
-    for pt in &points.0 {
-        *ring_counts_used.entry(pt.num_drawn_rings()).or_default() += 1;
-    }
-
-    let () = adjust_draw_radius(config, points.0.as_mut_slice());
-    let stack_offset = StackOffset::build(&traits, &mut rng);
-
-    let batch_sizes = match config.animate {
-        Animation::None => None,
-        Animation::Groups => Some(group_sizes.0),
-        Animation::Points { step } => {
-            let step = step as usize;
-            let (n_groups, remainder) = (num_points / step, num_points % step);
-            let mut result = vec![step; n_groups];
-            if remainder > 0 {
-                result.push(remainder);
+/// The strokes of a render, in paint order, for vector output. See [`record`].
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub background: Hsb,
+    /// The viewport that strokes were culled to. Strokes entirely outside of it are omitted.
+    pub viewport: FractionalViewport,
+    pub strokes: Vec<Stroke>,
+}
+
+impl Recording {
+    /// Computes the output dimensions in pixels for the given virtual canvas width, as with
+    /// [`draw`].
+    pub fn dimensions(&self, canvas_width: i32) -> (i32, i32) {
+        canvas_dimensions(&self.viewport, canvas_width)
+    }
+
+    /// The top-left corner of the viewport, in virtual canvas space. Pass this to
+    /// [`Stroke::vertices`] to get coordinates relative to the output.
+    pub fn origin(&self) -> (f64, f64) {
+        VirtualViewport::from(&self.viewport).origin()
+    }
+
+    /// The ratio mapping virtual canvas space to pixels for the given virtual canvas width.
+    pub fn scale_ratio(&self, canvas_width: i32) -> f64 {
+        f64::from(canvas_width) / VIRTUAL_W
+    }
+}
+
+/// A render that remembers which layer produced each pixel. See [`draw_layered`].
+pub struct LayeredImage {
+    /// The canonical image, identical to what [`draw`] produces.
+    pub image: Canvas,
+    /// For each pixel in row-major order, the layer of the stroke that last changed it, or `None`
+    /// where the background shows through.
+    pub owners: Vec<Option<Layer>>,
+}
+
+impl LayeredImage {
+    /// Extracts the pixels owned by one layer (or by the background, for `None`), leaving the
+    /// rest transparent.
+    ///
+    /// Every pixel is owned by exactly one layer and holds its final, opaque value there. So
+    /// compositing the background and then each of [`Layer::ALL`] with plain source-over
+    /// reproduces [`LayeredImage::image`] exactly. At antialiased edges, an owned pixel is a blend
+    /// of the colors of several layers.
+    pub fn layer(&self, layer: Option<Layer>) -> Canvas {
+        let mut dt = DrawTarget::new(self.image.width(), self.image.height());
+        for ((out, &px), &owner) in dt
+            .get_data_mut()
+            .iter_mut()
+            .zip(self.image.data())
+            .zip(&self.owners)
+        {
+            if owner == layer {
+                *out = px;
             }
-            Some(result)
         }
-    };
+        Canvas::from_draw_target(dt)
+    }
+}
 
-    let dt = match batch_sizes {
-        None => {
-            let dt = render::<paint_mode::Paint>(
-                canvas_width,
-                Background::Opaque,
-                &traits,
-                color_db,
-                config,
-                &stack_offset,
-                NormalPoints::Some {
-                    points: points.0.as_slice(),
-                    splatter_sink: SplatterSink::Immediate,
-                },
-                &[], // no extra splatter points
-                &color_scheme,
-                &mut colors_used,
-                &mut rng,
-            );
-            consume_frame(Frame {
-                dt: &dt,
-                number: None,
-            });
-            eprintln!("drew points");
-            dt
+/// The parameters of a piece that are picked from its traits before the flow field is built.
+struct Parameters {
+    traits: Traits,
+    rng: Rng,
+    flow_field_spec: FlowFieldSpec,
+    spacing_spec: SpacingSpec,
+    color_change_odds: ColorChangeOdds,
+    scale_generator: ScaleGenerator,
+    bullseye_generator: BullseyeGenerator,
+    color_scheme: ColorScheme,
+}
+
+impl Parameters {
+    fn from_seed(
+        seed: &[u8; 32],
+        color_db: &ColorDb,
+        force_version: Option<Version>,
+    ) -> Result<Self, Error> {
+        let mut traits = Traits::from_seed(seed);
+        if let Some(version) = force_version {
+            traits.version = version;
         }
+        let mut rng = Rng::from_seed(&seed[..]);
+
+        let flow_field_spec = FlowFieldSpec::from_traits(&traits, &mut rng);
+        let spacing_spec = SpacingSpec::from_traits(&traits, &mut rng);
+        let color_change_odds = ColorChangeOdds::from_traits(&traits, &mut rng);
+        let scale_generator = ScaleGenerator::from_traits(&traits, &mut rng);
+        let bullseye_generator = BullseyeGenerator::from_traits(&traits, &mut rng);
+        let color_scheme = ColorScheme::from_traits(&traits, color_db, &mut rng)?;
+        color_scheme.check_colors(color_db)?;
+        Ok(Parameters {
+            traits,
+            rng,
+            flow_field_spec,
+            spacing_spec,
+            color_change_odds,
+            scale_generator,
+            bullseye_generator,
+            color_scheme,
+        })
+    }
 
-        Some(batch_sizes) => {
-            let old_rng = rng.clone();
-            // For the first frame, render just the background.
-            let mut fb = render::<paint_mode::Paint>(
-                canvas_width,
-                Background::Opaque,
-                &traits,
-                color_db,
-                config,
-                &stack_offset,
-                NormalPoints::None,
-                &[], // no extra splatter points
-                &color_scheme,
-                &mut colors_used,
-                &mut rng,
-            );
-            if old_rng != rng {
-                panic!("painting background changed rng");
-            }
-            // https://github.com/rust-lang/rust-clippy/issues/11650
-            #[allow(clippy::drop_non_drop)]
-            drop(old_rng);
-
-            struct EagerSplatters {
-                /// A transparent-background frame buffer containing all splatter points that have
-                /// been painted so far.
-                layer: DrawTarget,
-                /// A spare canvas that can be used for compositing at each frame emission.
-                output_buf: DrawTarget,
-                /// The RNG state after all normal points and after any splatter points that have
-                /// been painted so far.
-                rng: Rng,
-                /// Colors used by splatters only. Saved separately to preserve iteration order.
-                colors_used: ColorsUsed,
-            }
-            enum Splatters {
-                Eager(EagerSplatters),
-                Deferred(Vec<Point>),
-            }
-            let mut splatters = if config.splatter_immediately {
-                let layer = DrawTarget::new(fb.width(), fb.height());
-                let output_buf = DrawTarget::new(fb.width(), fb.height());
-                // Compute output state by pre-rendering all the normal points.
-                eprintln!("pre-rendering normal points to seek for splatter state");
-                let mut rng = rng.clone();
-                render::<paint_mode::Skip>(
-                    canvas_width,
-                    Background::Transparent,
-                    &traits,
-                    color_db,
-                    config,
-                    &stack_offset,
-                    NormalPoints::Some {
+    /// Builds the flow field, returning it with the RNG state that the rest of the layout
+    /// starts from.
+    fn into_flow_field(mut self) -> (FlowField, Rng) {
+        let flow_field = FlowField::build(&self.flow_field_spec, &self.traits, &mut self.rng);
+        (flow_field, self.rng)
+    }
+}
+
+/// Everything computed before painting starts. A layout can be built once and then painted any
+/// number of times, e.g., at different sizes or with different viewports; see [`Layout::render`].
+/// Layouts are immutable and `Sync`, so renders can also share one across threads.
+pub struct Layout {
+    options: LayoutOptions,
+    traits: Traits,
+    color_scheme: ColorScheme,
+    points: Points,
+    group_sizes: GroupSizes,
+    colors_used: ColorsUsed,
+    ring_counts_used: BTreeMap<RingCount, usize>,
+    stack_offset: StackOffset,
+    /// RNG state as of the start of painting.
+    rng: Rng,
+}
+
+impl Layout {
+    /// Lays out a piece: traits, flow field, flow lines, and then points.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `color_db` has no palette or colors for the piece; see [`Layout::try_from_seed`].
+    pub fn from_seed(seed: &[u8; 32], color_db: &ColorDb, options: &LayoutOptions) -> Self {
+        or_panic(Self::try_from_seed(seed, color_db, options, &Silent))
+    }
+
+    /// Like [`Layout::from_seed`], but fails if `color_db` has no palette or colors for the
+    /// piece, and reports the layout stages to `observer`.
+    pub fn try_from_seed(
+        seed: &[u8; 32],
+        color_db: &ColorDb,
+        options: &LayoutOptions,
+        observer: &dyn Observer,
+    ) -> Result<Self, Error> {
+        let timer = StageTimer::start(observer, Stage::Traits);
+        let Parameters {
+            traits,
+            mut rng,
+            flow_field_spec,
+            spacing_spec,
+            color_change_odds,
+            mut scale_generator,
+            mut bullseye_generator,
+            color_scheme,
+        } = Parameters::from_seed(seed, color_db, options.force_version)?;
+        timer.finish();
+
+        let timer = StageTimer::start(observer, Stage::FlowField);
+        let flow_field = FlowField::build(&flow_field_spec, &traits, &mut rng);
+        let ignore_flow_field = IgnoreFlowField::build(&flow_field_spec, &mut rng);
+        let start_points = StartPointGroups::build(traits.structure, &mut rng);
+
+        let grouped_flow_lines =
+            GroupedFlowLines::build(flow_field, ignore_flow_field, start_points, &mut rng);
+        timer.finish();
+
+        let timer = StageTimer::start(observer, Stage::Points);
+        let mut sectors: Sectors = build_sectors(options);
+        let mut colors_used = ColorsUsed::new();
+        let (mut points, group_sizes) = Points::build(
+            &traits,
+            color_db,
+            grouped_flow_lines,
+            &color_scheme,
+            &color_change_odds,
+            &spacing_spec,
+            &mut bullseye_generator,
+            &mut scale_generator,
+            &mut sectors,
+            &mut colors_used,
+            &mut rng,
+        );
+        let mut ring_counts_used = BTreeMap::new();
+        for pt in &points.0 {
+            *ring_counts_used.entry(pt.num_drawn_rings()).or_default() += 1;
+        }
+
+        let () = adjust_draw_radius(options, points.0.as_mut_slice());
+        let stack_offset = StackOffset::build(&traits, &mut rng);
+        timer.finish();
+
+        Ok(Layout {
+            options: options.clone(),
+            traits,
+            color_scheme,
+            points,
+            group_sizes,
+            colors_used,
+            ring_counts_used,
+            stack_offset,
+            rng,
+        })
+    }
+}
+
+impl Layout {
+    /// The options that this layout was built with.
+    pub fn options(&self) -> &LayoutOptions {
+        &self.options
+    }
+
+    /// The traits that this layout was built from, with any forced version applied.
+    pub fn traits(&self) -> &Traits {
+        &self.traits
+    }
+
+    pub fn color_scheme(&self) -> &ColorScheme {
+        &self.color_scheme
+    }
+
+    /// The laid-out points, in painting order.
+    pub fn points(&self) -> &[Point] {
+        &self.points.0
+    }
+
+    /// The number of points in each flow line group, in painting order.
+    pub fn group_sizes(&self) -> &[usize] {
+        &self.group_sizes.0
+    }
+
+    /// How many points have each number of drawn rings.
+    pub fn ring_counts_used(&self) -> &BTreeMap<RingCount, usize> {
+        &self.ring_counts_used
+    }
+
+    /// Colors used by the laid-out points, in order of first use. Painting may add more colors
+    /// for splatters; see [`RenderData::colors_used`].
+    pub fn colors_used(&self) -> &ColorsUsed {
+        &self.colors_used
+    }
+
+    /// Paints this layout as [`draw`] does, handing each frame to `consume_frame`: a single frame
+    /// without animation, or else one frame per batch of points, as configured by
+    /// `options.animate`.
+    pub fn render<F: FnMut(Frame)>(
+        &self,
+        color_db: &ColorDb,
+        options: &RenderOptions,
+        canvas_width: i32,
+        consume_frame: F,
+    ) -> RenderData {
+        or_panic(self.try_render(color_db, options, canvas_width, &Silent, consume_frame))
+    }
+
+    /// Like [`Layout::render`], but fails if `options` are invalid, if `color_db` doesn't have this
+    /// layout's colors, or if chunks can't be painted consistently. Reports progress to
+    /// `observer`; when animating,
+    /// points painted are reported once per frame.
+    pub fn try_render<F: FnMut(Frame)>(
+        &self,
+        color_db: &ColorDb,
+        options: &RenderOptions,
+        canvas_width: i32,
+        observer: &dyn Observer,
+        consume_frame: F,
+    ) -> Result<RenderData, Error> {
+        let config = RenderConfig::from_options(&self.options, options);
+        config.validate()?;
+        self.try_animate(color_db, &config, canvas_width, observer, consume_frame)
+    }
+
+    /// Paints this layout without animation, with the paint mode `PM`. `config` must have been
+    /// built from this layout's options, as [`Layout::try_render`] does.
+    fn paint_with<PM: PaintMode>(
+        &self,
+        color_db: &ColorDb,
+        config: &RenderConfig,
+        canvas_width: i32,
+        observer: &dyn Observer,
+    ) -> Result<RenderData<PM::DrawTarget>, Error> {
+        let timer = StageTimer::start(observer, Stage::Paint);
+        let mut colors_used = self.colors_used.clone();
+        let mut rng = self.rng.clone();
+        let dt = render::<PM>(
+            canvas_width,
+            Background::Opaque,
+            &self.traits,
+            color_db,
+            config,
+            &self.stack_offset,
+            NormalPoints::Some {
+                points: self.points.0.as_slice(),
+                splatter_sink: SplatterSink::Immediate,
+            },
+            &[], // no extra splatter points
+            &self.color_scheme,
+            &mut colors_used,
+            &mut rng,
+            observer,
+        )?;
+        timer.finish();
+        Ok(RenderData {
+            canvas: dt,
+            num_points: self.points.0.len(),
+            colors_used,
+            ring_counts_used: self.ring_counts_used.clone(),
+        })
+    }
+}
+
+/// Unwraps the result of a `try_` function, for the functions that panic instead.
+fn or_panic<T>(result: Result<T, Error>) -> T {
+    result.unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Whether [`RenderConfig::force_version`] actually changes the layout of `seed`: that is, whether the
+/// forced version builds a different flow field than the seed's own version does. `None` if no
+/// version is forced.
+///
+/// Versions only differ in how radial flow fields are placed, so this is often `Some(false)`.
+/// Everything after the flow field depends only on it and on the RNG state, so only the flow
+/// field is built, not the whole layout: this is cheap next to the render itself.
+pub fn forced_version_changes_layout(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+) -> Result<Option<bool>, Error> {
+    let Some(forced) = config.force_version else {
+        return Ok(None);
+    };
+    if forced == Traits::from_seed(seed).version {
+        return Ok(Some(false));
+    }
+    let (own_field, own_rng) = Parameters::from_seed(seed, color_db, None)?.into_flow_field();
+    let (forced_field, forced_rng) =
+        Parameters::from_seed(seed, color_db, Some(forced))?.into_flow_field();
+    Ok(Some(own_field.0 != forced_field.0 || own_rng != forced_rng))
+}
+
+/// Lays out a piece and runs the paint pass without painting anything, to get the statistics of
+/// [`RenderData`] cheaply. Splatters are still placed, so `colors_used` matches [`draw`]. Nothing
+/// is logged, so this is suitable for analyzing many seeds.
+pub fn analyze(seed: &[u8; 32], color_db: &ColorDb, config: &RenderConfig) -> RenderData<()> {
+    or_panic(try_analyze(seed, color_db, config))
+}
+
+/// Like [`analyze`], but returns errors as [`try_draw`] does.
+pub fn try_analyze(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+) -> Result<RenderData<()>, Error> {
+    config.validate()?;
+    Layout::try_from_seed(seed, color_db, &config.layout_options(), &Silent)?
+        .paint_with::<paint_mode::Skip>(color_db, config, VIRTUAL_W as i32, &Silent)
+}
+
+/// Lays out a piece and records all the strokes that [`draw`] would paint, without rasterizing
+/// them. The RNG stream is the same as for [`draw`], so the strokes match the raster output.
+///
+/// The `animate` setting of the config is ignored.
+pub fn record(seed: &[u8; 32], color_db: &ColorDb, config: &RenderConfig) -> RenderData<Recording> {
+    or_panic(try_record(seed, color_db, config, &Silent))
+}
+
+/// Like [`record`], but returns errors and reports progress as [`try_draw`] does.
+pub fn try_record(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    observer: &dyn Observer,
+) -> Result<RenderData<Recording>, Error> {
+    config.validate()?;
+    let layout = Layout::try_from_seed(seed, color_db, &config.layout_options(), observer)?;
+    // The canvas width only affects raster-space scaling, which recording doesn't use.
+    let RenderData {
+        canvas: strokes,
+        num_points,
+        colors_used,
+        ring_counts_used,
+    } = layout.paint_with::<paint_mode::Record>(color_db, config, VIRTUAL_W as i32, observer)?;
+
+    Ok(RenderData {
+        canvas: Recording {
+            background: background_color(color_db, &layout.color_scheme),
+            viewport: config.viewport.clone().unwrap_or_default(),
+            strokes,
+        },
+        num_points,
+        colors_used,
+        ring_counts_used,
+    })
+}
+
+/// Like [`draw`], but hands each chunk of the `config.chunks` grid to `consume_chunk` as soon as
+/// it's painted, instead of compositing them onto one canvas. Chunks arrive in no particular
+/// order. With many chunks, this can produce outputs far larger than would fit in memory.
+///
+/// Chunk boundaries are rounded to multiples of `align` pixels (except at the right and bottom
+/// edges), so that they can line up with output tiles. Grid cells that round to nothing are
+/// skipped.
+///
+/// The `animate` setting of the config is ignored.
+pub fn draw_chunks<F: FnMut(Chunk)>(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+    align: i32,
+    consume_chunk: F,
+) -> RenderData<()> {
+    or_panic(try_draw_chunks(
+        seed,
+        color_db,
+        config,
+        canvas_width,
+        align,
+        &Silent,
+        consume_chunk,
+    ))
+}
+
+/// Like [`draw_chunks`], but returns errors and reports progress as [`try_draw`] does. Chunks
+/// painted before an error may already have been handed to `consume_chunk`.
+pub fn try_draw_chunks<F: FnMut(Chunk)>(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+    align: i32,
+    observer: &dyn Observer,
+    mut consume_chunk: F,
+) -> Result<RenderData<()>, Error> {
+    config.validate()?;
+    let Layout {
+        options: _,
+        traits,
+        color_scheme,
+        points,
+        group_sizes: _,
+        mut colors_used,
+        ring_counts_used,
+        stack_offset,
+        mut rng,
+    } = Layout::try_from_seed(seed, color_db, &config.layout_options(), observer)?;
+
+    let timer = StageTimer::start(observer, Stage::Paint);
+    render_chunks::<paint_mode::Paint, _>(
+        canvas_width,
+        align.max(1),
+        Background::Opaque,
+        &traits,
+        color_db,
+        config,
+        &stack_offset,
+        NormalPoints::Some {
+            points: points.0.as_slice(),
+            splatter_sink: SplatterSink::Immediate,
+        },
+        &[], // no extra splatter points
+        &color_scheme,
+        &mut colors_used,
+        &mut rng,
+        observer,
+        |chunk| {
+            let canvas = Canvas::from_draw_target(paint_mode::Paint::compose(chunk.components));
+            consume_chunk(Chunk {
+                left: chunk.left_px,
+                top: chunk.top_px,
+                canvas: &canvas,
+            });
+        },
+    )?;
+    timer.finish();
+
+    Ok(RenderData {
+        canvas: (),
+        num_points: points.0.len(),
+        colors_used,
+        ring_counts_used,
+    })
+}
+
+/// Like [`draw`], but also records which layer (shadow, normal, or splatter) last changed each
+/// pixel, so that the image can be split into layers. See [`LayeredImage::layer`].
+///
+/// The `animate` setting of the config is ignored.
+pub fn draw_layered(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+) -> RenderData<LayeredImage> {
+    or_panic(try_draw_layered(
+        seed,
+        color_db,
+        config,
+        canvas_width,
+        &Silent,
+    ))
+}
+
+/// Like [`draw_layered`], but returns errors and reports progress as [`try_draw`] does.
+pub fn try_draw_layered(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+    observer: &dyn Observer,
+) -> Result<RenderData<LayeredImage>, Error> {
+    config.validate()?;
+    let RenderData {
+        canvas,
+        num_points,
+        colors_used,
+        ring_counts_used,
+    } = Layout::try_from_seed(seed, color_db, &config.layout_options(), observer)?
+        .paint_with::<paint_mode::PaintLayered>(color_db, config, canvas_width, observer)?;
+    Ok(RenderData {
+        canvas: LayeredImage {
+            image: Canvas::from_draw_target(canvas.dt),
+            owners: canvas.owners,
+        },
+        num_points,
+        colors_used,
+        ring_counts_used,
+    })
+}
+
+/// Like [`draw`], but paints and composites with 16 bits per channel, avoiding the banding that
+/// 8-bit output can show in subtle color variations on large prints. Colors are converted from
+/// [`Hsb`] without truncation; raqote is only used to rasterize stroke coverage.
+///
+/// The `animate` setting of the config is ignored.
+pub fn draw16(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+) -> RenderData<Canvas16> {
+    or_panic(try_draw16(seed, color_db, config, canvas_width, &Silent))
+}
+
+/// Like [`draw16`], but returns errors and reports progress as [`try_draw`] does.
+pub fn try_draw16(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+    observer: &dyn Observer,
+) -> Result<RenderData<Canvas16>, Error> {
+    config.validate()?;
+    let RenderData {
+        canvas,
+        num_points,
+        colors_used,
+        ring_counts_used,
+    } = Layout::try_from_seed(seed, color_db, &config.layout_options(), observer)?
+        .paint_with::<paint_mode::Paint16>(color_db, config, canvas_width, observer)?;
+    Ok(RenderData {
+        canvas: canvas.canvas,
+        num_points,
+        colors_used,
+        ring_counts_used,
+    })
+}
+
+/// Lays out and paints a piece, handing each frame to `consume_frame`; see [`Layout::render`].
+///
+/// # Panics
+///
+/// Panics on the errors that [`try_draw`] returns.
+pub fn draw<F: FnMut(Frame)>(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+    consume_frame: F,
+) -> RenderData {
+    or_panic(try_draw(
+        seed,
+        color_db,
+        config,
+        canvas_width,
+        &Silent,
+        consume_frame,
+    ))
+}
+
+/// Like [`draw`], but fails instead of panicking if `config` is [invalid](RenderConfig::validate),
+/// if `color_db` has no palette or colors for the piece, if `config.chunks` has too many chunks,
+/// or if chunks can't be painted consistently.
+/// Frames painted before an error may already have been handed to `consume_frame`.
+///
+/// Reports each stage of layout and painting to `observer`, along with chunks, points painted,
+/// and frames emitted; see [`crate::progress`].
+pub fn try_draw<F: FnMut(Frame)>(
+    seed: &[u8; 32],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+    observer: &dyn Observer,
+    consume_frame: F,
+) -> Result<RenderData, Error> {
+    config.validate()?;
+    Layout::try_from_seed(seed, color_db, &config.layout_options(), observer)?.try_render(
+        color_db,
+        &config.render_options(),
+        canvas_width,
+        observer,
+        consume_frame,
+    )
+}
+
+impl Layout {
+    /// Paints this layout for [`Layout::try_render`], from a `config` built from this layout's
+    /// options. When animating, points painted are reported once per frame.
+    fn try_animate<F: FnMut(Frame)>(
+        &self,
+        color_db: &ColorDb,
+        config: &RenderConfig,
+        canvas_width: i32,
+        observer: &dyn Observer,
+        mut consume_frame: F,
+    ) -> Result<RenderData, Error> {
+        let Layout {
+            options: _,
+            traits,
+            color_scheme,
+            points,
+            group_sizes,
+            colors_used,
+            ring_counts_used,
+            stack_offset,
+            rng,
+        } = self;
+        let mut colors_used = colors_used.clone();
+        let mut rng = rng.clone();
+        let num_points = points.0.len();
+
+        let batch_sizes = match config.animate {
+            Animation::None => None,
+            Animation::Groups => Some(group_sizes.0.clone()),
+            Animation::Points { step } => {
+                let step = step as usize;
+                let (n_groups, remainder) = (num_points / step, num_points % step);
+                let mut result = vec![step; n_groups];
+                if remainder > 0 {
+                    result.push(remainder);
+                }
+                Some(result)
+            }
+        };
+
+        let timer = StageTimer::start(observer, Stage::Paint);
+        let emitted = |number: Option<u32>| {
+            observer.on_event(&Event::FrameEmitted {
+                number,
+                elapsed: timer.elapsed(),
+            })
+        };
+        let canvas = match batch_sizes {
+            None => {
+                let dt = render::<paint_mode::Paint>(
+                    canvas_width,
+                    Background::Opaque,
+                    traits,
+                    color_db,
+                    config,
+                    stack_offset,
+                    NormalPoints::Some {
                         points: points.0.as_slice(),
-                        splatter_sink: SplatterSink::Ignored,
+                        splatter_sink: SplatterSink::Immediate,
                     },
                     &[], // no extra splatter points
-                    &color_scheme,
-                    &mut ColorsUsed::new(),
+                    color_scheme,
+                    &mut colors_used,
                     &mut rng,
-                );
-                Splatters::Eager(EagerSplatters {
-                    layer,
-                    output_buf,
-                    rng,
-                    colors_used: ColorsUsed::new(),
-                })
-            } else {
-                Splatters::Deferred(Vec::new())
-            };
+                    observer,
+                )?;
+                let canvas = Canvas::from_draw_target(dt);
+                consume_frame(Frame {
+                    canvas: &canvas,
+                    number: None,
+                });
+                emitted(None);
+                canvas
+            }
 
-            let mut frame_number = 0;
-            consume_frame(Frame {
-                dt: &fb,
-                number: Some(frame_number),
-            });
-            frame_number += 1;
-
-            let mut emit_incremental_frame =
-                |layer: &DrawTarget, splatters: Option<&mut EagerSplatters>| {
-                    assert_eq!((layer.width(), layer.height()), (fb.width(), fb.height()));
-                    superimpose(&mut fb, as_image(layer), (0, 0));
-                    let buf = match splatters {
-                        None => &mut fb,
-                        Some(splatters) => {
-                            let buf = &mut splatters.output_buf;
-                            buf.get_data_mut().copy_from_slice(fb.get_data());
-                            superimpose(buf, as_image(&splatters.layer), (0, 0));
-                            buf
-                        }
+            Some(batch_sizes) => {
+                let old_rng = rng.clone();
+                // For the first frame, render just the background.
+                let fb = render::<paint_mode::Paint>(
+                    canvas_width,
+                    Background::Opaque,
+                    traits,
+                    color_db,
+                    config,
+                    stack_offset,
+                    NormalPoints::None,
+                    &[], // no extra splatter points
+                    color_scheme,
+                    &mut colors_used,
+                    &mut rng,
+                    &Silent,
+                )?;
+                if old_rng != rng {
+                    panic!("painting background changed rng");
+                }
+                let mut fb = Canvas::from_draw_target(fb);
+                // https://github.com/rust-lang/rust-clippy/issues/11650
+                #[allow(clippy::drop_non_drop)]
+                drop(old_rng);
+
+                struct EagerSplatters {
+                    /// A transparent-background frame buffer containing all splatter points that have
+                    /// been painted so far.
+                    layer: DrawTarget,
+                    /// A spare canvas that can be used for compositing at each frame emission.
+                    output_buf: Canvas,
+                    /// The RNG state after all normal points and after any splatter points that have
+                    /// been painted so far.
+                    rng: Rng,
+                    /// Colors used by splatters only. Saved separately to preserve iteration order.
+                    colors_used: ColorsUsed,
+                }
+                enum Splatters {
+                    Eager(Box<EagerSplatters>),
+                    Deferred(Vec<Point>),
+                }
+                let mut splatters = if config.splatter_immediately {
+                    let layer = DrawTarget::new(fb.width(), fb.height());
+                    let output_buf = Canvas::new(fb.width(), fb.height());
+                    // Compute output state by pre-rendering all the normal points.
+                    observer.on_event(&Event::Note(
+                        "pre-rendering normal points to seek for splatter state".into(),
+                    ));
+                    let mut rng = rng.clone();
+                    render::<paint_mode::Skip>(
+                        canvas_width,
+                        Background::Transparent,
+                        traits,
+                        color_db,
+                        config,
+                        stack_offset,
+                        NormalPoints::Some {
+                            points: points.0.as_slice(),
+                            splatter_sink: SplatterSink::Ignored,
+                        },
+                        &[], // no extra splatter points
+                        color_scheme,
+                        &mut ColorsUsed::new(),
+                        &mut rng,
+                        &Silent,
+                    )?;
+                    Splatters::Eager(Box::new(EagerSplatters {
+                        layer,
+                        output_buf,
+                        rng,
+                        colors_used: ColorsUsed::new(),
+                    }))
+                } else {
+                    Splatters::Deferred(Vec::new())
+                };
+
+                let mut frame_number = 0;
+                consume_frame(Frame {
+                    canvas: &fb,
+                    number: Some(frame_number),
+                });
+                emitted(Some(frame_number));
+                frame_number += 1;
+
+                let mut emit_incremental_frame =
+                    |layer: &DrawTarget, splatters: Option<&mut EagerSplatters>| {
+                        assert_eq!((layer.width(), layer.height()), (fb.width(), fb.height()));
+                        superimpose(fb.draw_target_mut(), as_image(layer), (0, 0));
+                        let buf = match splatters {
+                            None => &mut fb,
+                            Some(splatters) => {
+                                let buf = &mut splatters.output_buf;
+                                let dt = buf.draw_target_mut();
+                                dt.get_data_mut().copy_from_slice(fb.data());
+                                superimpose(dt, as_image(&splatters.layer), (0, 0));
+                                buf
+                            }
+                        };
+                        consume_frame(Frame {
+                            canvas: buf,
+                            number: Some(frame_number),
+                        });
+                        emitted(Some(frame_number));
+                        frame_number += 1;
                     };
-                    consume_frame(Frame {
-                        dt: buf,
-                        number: Some(frame_number),
+
+                let mut points = points.0.as_slice();
+                // Report points painted per batch, rather than per chunk of each batch.
+                let mut points_painted = 0;
+                for size in batch_sizes {
+                    let (batch, rest) = points.split_at(size);
+                    match &mut splatters {
+                        Splatters::Deferred(splatter_points) => {
+                            let dt = render::<paint_mode::Paint>(
+                                canvas_width,
+                                Background::Transparent,
+                                traits,
+                                color_db,
+                                config,
+                                stack_offset,
+                                NormalPoints::Some {
+                                    points: batch,
+                                    splatter_sink: SplatterSink::Deferred(splatter_points),
+                                },
+                                &[], // no extra splatter points
+                                color_scheme,
+                                &mut colors_used,
+                                &mut rng,
+                                &Silent,
+                            )?;
+                            emit_incremental_frame(&dt, None);
+                        }
+                        Splatters::Eager(splatters) => {
+                            let mut these_splatters = Vec::new();
+                            let normal_layer = render::<paint_mode::Paint>(
+                                canvas_width,
+                                Background::Transparent,
+                                traits,
+                                color_db,
+                                config,
+                                stack_offset,
+                                NormalPoints::Some {
+                                    points: batch,
+                                    splatter_sink: SplatterSink::Deferred(&mut these_splatters),
+                                },
+                                &[], // no extra splatter points
+                                color_scheme,
+                                &mut colors_used,
+                                &mut rng,
+                                &Silent,
+                            )?;
+                            let splatter_layer = render::<paint_mode::Paint>(
+                                canvas_width,
+                                Background::Transparent,
+                                traits,
+                                color_db,
+                                config,
+                                stack_offset,
+                                NormalPoints::None,
+                                &these_splatters,
+                                color_scheme,
+                                &mut splatters.colors_used,
+                                &mut splatters.rng,
+                                &Silent,
+                            )?;
+                            superimpose(&mut splatters.layer, as_image(&splatter_layer), (0, 0));
+                            emit_incremental_frame(&normal_layer, Some(splatters));
+                        }
+                    }
+                    points = rest;
+                    points_painted += size;
+                    observer.on_event(&Event::PointsPainted {
+                        painted: points_painted,
+                        total: num_points,
                     });
-                    frame_number += 1;
-                };
+                }
 
-            let mut points = points.0.as_slice();
-            for size in batch_sizes {
-                let (batch, rest) = points.split_at(size);
-                match &mut splatters {
+                // Finish processing splatters: either render them all if they were deferred, or
+                // register their colors used now that we've gotten all the normal points' colors.
+                match splatters {
+                    Splatters::Eager(splatters) => colors_used.extend(&splatters.colors_used),
                     Splatters::Deferred(splatter_points) => {
                         let dt = render::<paint_mode::Paint>(
                             canvas_width,
                             Background::Transparent,
-                            &traits,
+                            traits,
                             color_db,
                             config,
-                            &stack_offset,
-                            NormalPoints::Some {
-                                points: batch,
-                                splatter_sink: SplatterSink::Deferred(splatter_points),
-                            },
-                            &[], // no extra splatter points
-                            &color_scheme,
+                            stack_offset,
+                            NormalPoints::None,
+                            splatter_points.as_slice(),
+                            color_scheme,
                             &mut colors_used,
                             &mut rng,
-                        );
+                            &Silent,
+                        )?;
                         emit_incremental_frame(&dt, None);
                     }
-                    Splatters::Eager(splatters) => {
-                        let mut these_splatters = Vec::new();
-                        let normal_layer = render::<paint_mode::Paint>(
-                            canvas_width,
-                            Background::Transparent,
-                            &traits,
-                            color_db,
-                            config,
-                            &stack_offset,
-                            NormalPoints::Some {
-                                points: batch,
-                                splatter_sink: SplatterSink::Deferred(&mut these_splatters),
-                            },
-                            &[], // no extra splatter points
-                            &color_scheme,
-                            &mut colors_used,
-                            &mut rng,
-                        );
-                        let splatter_layer = render::<paint_mode::Paint>(
-                            canvas_width,
-                            Background::Transparent,
-                            &traits,
-                            color_db,
-                            config,
-                            &stack_offset,
-                            NormalPoints::None,
-                            &these_splatters,
-                            &color_scheme,
-                            &mut splatters.colors_used,
-                            &mut splatters.rng,
-                        );
-                        superimpose(&mut splatters.layer, as_image(&splatter_layer), (0, 0));
-                        emit_incremental_frame(&normal_layer, Some(splatters));
-                    }
-                }
-                points = rest;
-            }
-
-            // Finish processing splatters: either render them all if they were deferred, or
-            // register their colors used now that we've gotten all the normal points' colors.
-            match splatters {
-                Splatters::Eager(splatters) => colors_used.extend(&splatters.colors_used),
-                Splatters::Deferred(splatter_points) => {
-                    let dt = render::<paint_mode::Paint>(
-                        canvas_width,
-                        Background::Transparent,
-                        &traits,
-                        color_db,
-                        config,
-                        &stack_offset,
-                        NormalPoints::None,
-                        splatter_points.as_slice(),
-                        &color_scheme,
-                        &mut colors_used,
-                        &mut rng,
-                    );
-                    emit_incremental_frame(&dt, None);
                 }
+                fb
             }
-            fb
-        }
-    };
+        };
+        timer.finish();
 
-    RenderData {
-        canvas: dt,
-        num_points,
-        colors_used,
-        ring_counts_used,
+        Ok(RenderData {
+            canvas,
+            num_points,
+            colors_used,
+            ring_counts_used: ring_counts_used.clone(),
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::config::ConfigError;
 
     /// Tests that the hard-coded values for [`SPC`], [`FLOW_FIELD_ROWS`], and [`FLOW_FIELD_COLS`]
     /// match the computed values used (either implicitly or explicitly) in the JavaScript algorithm.
@@ -2087,4 +3270,200 @@ mod test {
         assert_eq!(colors_used.as_slice(), &[key]);
         assert_eq!(color, expected);
     }
+
+    #[test]
+    fn test_shared_layout_renders_like_draw() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<Layout>();
+
+        let seed =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let color_db = ColorDb::from_bundle();
+        let layout = Layout::from_seed(&seed, &color_db, &LayoutOptions::default());
+        let configs = [
+            (120, RenderConfig::default()),
+            (
+                240,
+                RenderConfig {
+                    viewport: Some("0.5x0.5+0.25+0.25".parse().unwrap()),
+                    chunks: "2x2".parse().unwrap(),
+                    ..Default::default()
+                },
+            ),
+        ];
+        std::thread::scope(|s| {
+            for (width, config) in &configs {
+                let (layout, color_db) = (&layout, &color_db);
+                s.spawn(move || {
+                    let mut rendered = Vec::new();
+                    layout.render(color_db, &config.render_options(), *width, |frame| {
+                        rendered = frame.canvas.data().to_vec();
+                    });
+                    let drawn = draw(&seed, color_db, config, *width, |_| {}).canvas;
+                    assert_eq!(rendered, drawn.data(), "width {}", width);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_try_draw_errors() {
+        let seed =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let color_db = ColorDb::from_bundle();
+        let empty_db = ColorDb::from_wire(
+            serde_json::from_value(serde_json::json!({"colors": [], "palettes": []})).unwrap(),
+        )
+        .unwrap();
+        let options = LayoutOptions::default();
+        assert!(matches!(
+            Layout::try_from_seed(&seed, &empty_db, &options, &Silent),
+            Err(Error::MissingPalette(_))
+        ));
+
+        let layout = Layout::from_seed(&seed, &color_db, &options);
+        let result = layout.try_render(&empty_db, &RenderOptions::default(), 60, &Silent, |_| {});
+        assert!(matches!(result, Err(Error::InvalidColor(_))));
+
+        let config = RenderConfig {
+            chunks: "65536x65536".parse().unwrap(),
+            ..Default::default()
+        };
+        let result = try_draw(&seed, &color_db, &config, 60, &Silent, |_| {});
+        assert!(matches!(
+            result,
+            Err(Error::TooManyChunks { w: 65536, h: 65536 })
+        ));
+
+        // Configs built by hand are checked as the builder would check them.
+        let config = RenderConfig {
+            animate: Animation::Points { step: 0 },
+            ..Default::default()
+        };
+        let result = try_draw(&seed, &color_db, &config, 60, &Silent, |_| {});
+        assert!(matches!(
+            result,
+            Err(Error::Config(ConfigError::ZeroPointsPerFrame))
+        ));
+        let result = try_record(&seed, &color_db, &config, &Silent);
+        assert!(matches!(result, Err(Error::Config(_))));
+        let options = RenderOptions {
+            viewport: Some(FractionalViewport::from_whlt(0.0, 1.0, 0.0, 0.0)),
+            ..Default::default()
+        };
+        let result = layout.try_render(&color_db, &options, 60, &Silent, |_| {});
+        assert!(matches!(
+            result,
+            Err(Error::Config(ConfigError::EmptyViewport(_)))
+        ));
+    }
+
+    #[test]
+    fn test_progress_events() {
+        let seed =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let color_db = ColorDb::from_bundle();
+        let config = RenderConfig {
+            chunks: "2x1".parse().unwrap(),
+            ..Default::default()
+        };
+        let events = std::sync::Mutex::new(Vec::new());
+        let observer = |event: &Event| events.lock().unwrap().push(event.clone());
+        let data = try_draw(&seed, &color_db, &config, 60, &observer, |_| {}).unwrap();
+        let events = events.into_inner().unwrap();
+
+        let finished: Vec<Stage> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::StageFinished { stage, .. } => Some(*stage),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            finished,
+            [Stage::Traits, Stage::FlowField, Stage::Points, Stage::Paint]
+        );
+        let chunks_finished = events
+            .iter()
+            .filter(|event| matches!(event, Event::ChunkFinished { .. }))
+            .count();
+        assert_eq!(chunks_finished, 2);
+        // Each chunk paints every point.
+        let most_painted = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::PointsPainted { painted, total } => Some((*painted, *total)),
+                _ => None,
+            })
+            .max();
+        let total = 2 * data.num_points;
+        assert_eq!(most_painted, Some((total, total)));
+        let frames: Vec<Option<u32>> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::FrameEmitted { number, .. } => Some(*number),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(frames, [None]);
+    }
+
+    #[test]
+    fn test_forced_version_changes_layout() {
+        use crate::traits::Version;
+
+        let radial =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let color_db = ColorDb::from_bundle();
+        let forcing = |version| RenderConfig {
+            force_version: Some(version),
+            ..Default::default()
+        };
+        let changes = |seed: &[u8; 32], config: &RenderConfig| {
+            forced_version_changes_layout(seed, &color_db, config).unwrap()
+        };
+        assert_eq!(changes(&radial, &RenderConfig::default()), None);
+        assert_eq!(changes(&radial, &forcing(Version::V1)), Some(false));
+        // Older versions put every radial flow field's center at the last candidate height.
+        assert_eq!(changes(&radial, &forcing(Version::V0)), Some(true));
+
+        let mut traits = Traits::from_seed(&radial);
+        traits.set("flow_field", "horizontal").unwrap();
+        let linear = crate::seed::compose(radial[..28].try_into().unwrap(), &traits).unwrap();
+        assert_eq!(changes(&linear, &forcing(Version::V0)), Some(false));
+    }
+
+    #[test]
+    fn test_layers_composite_to_canonical_image() {
+        let seed =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let color_db = ColorDb::from_bundle();
+        let config = RenderConfig {
+            chunks: "2x2".parse().unwrap(),
+            ..Default::default()
+        };
+        let canonical = draw(&seed, &color_db, &config, 240, |_| {}).canvas;
+        let layered = draw_layered(&seed, &color_db, &config, 240).canvas;
+        assert_eq!(layered.image.data(), canonical.data());
+
+        let (w, h) = (canonical.width(), canonical.height());
+        let mut composite = DrawTarget::new(w, h);
+        for layer in std::iter::once(None).chain(Layer::ALL.map(Some)) {
+            let dt = layered.layer(layer);
+            let image = raqote::Image {
+                width: w,
+                height: h,
+                data: dt.data(),
+            };
+            composite.draw_image_at(0.0, 0.0, &image, &DrawOptions::new());
+        }
+        assert_eq!(composite.get_data(), canonical.data());
+        for layer in Layer::ALL {
+            assert!(
+                layered.owners.contains(&Some(layer)),
+                "no {:?} pixels",
+                layer
+            );
+        }
+    }
 }
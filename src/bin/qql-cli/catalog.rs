@@ -0,0 +1,128 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use qql::catalog::{Catalog, Entry, CATALOG_ENV};
+use qql::config::FractionalViewport;
+
+use crate::Seed;
+
+#[derive(clap::Subcommand)]
+pub enum CatalogCommand {
+    /// Add a seed to the catalog under an alias.
+    Add(AddOpts),
+    /// List the seeds in the catalog.
+    List,
+    /// Remove a seed from the catalog, by alias or token number.
+    Remove {
+        #[clap(value_name = "ALIAS")]
+        name: String,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct AddOpts {
+    /// Name for the seed, like `qql085`. Can be used anywhere a seed is expected.
+    alias: String,
+    seed: Seed,
+
+    /// Token number, so that the seed can also be named like `#85`.
+    #[clap(long, value_name = "N")]
+    token: Option<u32>,
+    /// Free-form notes about the piece.
+    #[clap(long, value_name = "TEXT")]
+    notes: Option<String>,
+    /// A named viewport, like `face=0.25x0.25+0.4+0.3`, for `--viewport face` when rendering
+    /// this seed. Repeatable.
+    #[clap(long = "viewport", value_name = "NAME=WxH+X+Y")]
+    viewports: Vec<NamedViewport>,
+    /// Replace any entry with the same alias or token number.
+    #[clap(long)]
+    replace: bool,
+}
+
+#[derive(Clone)]
+struct NamedViewport {
+    name: String,
+    viewport: FractionalViewport,
+}
+
+impl FromStr for NamedViewport {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, viewport) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected NAME=WxH+X+Y, like face=0.25x0.25+0.4+0.3"))?;
+        Ok(NamedViewport {
+            name: name.trim().to_string(),
+            viewport: viewport.trim().parse()?,
+        })
+    }
+}
+
+pub fn main(command: CatalogCommand) {
+    let Some(path) = Catalog::default_path() else {
+        eprintln!(
+            "fatal: no home directory for the catalog; set {}",
+            CATALOG_ENV
+        );
+        std::process::exit(1);
+    };
+    let mut catalog = Catalog::load(&path).unwrap_or_else(|e| {
+        eprintln!("fatal: failed to read {}: {:#}", path.display(), e);
+        std::process::exit(1);
+    });
+    match command {
+        CatalogCommand::Add(opts) => {
+            let entry = Entry {
+                alias: opts.alias,
+                token: opts.token,
+                seed: *opts.seed.as_bytes(),
+                notes: opts.notes,
+                viewports: opts
+                    .viewports
+                    .into_iter()
+                    .map(|v| (v.name, v.viewport))
+                    .collect(),
+            };
+            let alias = entry.alias.clone();
+            if let Err(e) = catalog.add(entry, opts.replace) {
+                eprintln!("fatal: {}", e);
+                std::process::exit(1);
+            }
+            save(&catalog, &path);
+            eprintln!("added {} to {}", alias, path.display());
+        }
+        CatalogCommand::List => {
+            for entry in &catalog.entries {
+                print_entry(entry);
+            }
+        }
+        CatalogCommand::Remove { name } => {
+            let Some(entry) = catalog.remove(&name) else {
+                crate::usage_error(format!("no catalog entry {:?}", name));
+            };
+            save(&catalog, &path);
+            eprintln!("removed {} from {}", entry.alias, path.display());
+        }
+    }
+}
+
+fn save(catalog: &Catalog, path: &Path) {
+    if let Err(e) = catalog.save(path) {
+        eprintln!("fatal: failed to write {}: {:#}", path.display(), e);
+        std::process::exit(1);
+    }
+}
+
+fn print_entry(entry: &Entry) {
+    match entry.token {
+        Some(token) => println!("{} (#{}): {}", entry.alias, token, Seed(entry.seed)),
+        None => println!("{}: {}", entry.alias, Seed(entry.seed)),
+    }
+    if let Some(notes) = &entry.notes {
+        println!("    {}", notes);
+    }
+    for (name, viewport) in &entry.viewports {
+        println!("    viewport {}: {}", name, viewport);
+    }
+}
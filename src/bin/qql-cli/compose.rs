@@ -0,0 +1,54 @@
+use qql::seed::{compose, NONCE_BYTES, SENTINEL_BYTES};
+use qql::traits::Traits;
+
+use crate::{HexBytes, Seed, TraitAssignment};
+
+#[derive(clap::Args)]
+pub struct ComposeOpts {
+    /// Seed to start from. Its address, nonce, sentinel, and any traits that aren't set are kept.
+    seed: Seed,
+
+    /// Traits to set, like `flow_field=spiral spacing=dense color_palette=seoul`. Names are as
+    /// shown by `qql-cli info`; also accepts `version=v0` and `bullseye_rings=1,7`.
+    #[clap(value_name = "TRAIT=VALUE")]
+    traits: Vec<TraitAssignment>,
+
+    /// Replace the minter address, as 20 hex bytes.
+    #[clap(long, value_name = "HEX")]
+    address: Option<HexBytes<20>>,
+    /// Replace the nonce, as 6 hex bytes.
+    #[clap(long, value_name = "HEX")]
+    nonce: Option<HexBytes<6>>,
+    /// Replace the sentinel, as 2 hex bytes. Versioned seeds need `ffff`.
+    #[clap(long, value_name = "HEX")]
+    sentinel: Option<HexBytes<2>>,
+}
+
+pub fn main(opts: ComposeOpts) {
+    let base = opts.seed.as_bytes();
+    let mut traits = Traits::from_seed(base);
+    for assignment in &opts.traits {
+        if let Err(e) = traits.set(&assignment.name, &assignment.value) {
+            crate::usage_error(e);
+        }
+    }
+
+    let mut prefix: [u8; 28] = base[..28].try_into().unwrap();
+    if let Some(HexBytes(address)) = opts.address {
+        prefix[..20].copy_from_slice(&address);
+    }
+    if let Some(HexBytes(nonce)) = opts.nonce {
+        prefix[NONCE_BYTES].copy_from_slice(&nonce);
+    }
+    if let Some(HexBytes(sentinel)) = opts.sentinel {
+        prefix[SENTINEL_BYTES].copy_from_slice(&sentinel);
+    }
+
+    match compose(&prefix, &traits) {
+        Ok(seed) => println!("{}", Seed(seed)),
+        Err(e) => {
+            eprintln!("fatal: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::ops::Deref;
+
+use clap::{ArgMatches, FromArgMatches};
+
+use qql::config::{Animation, Chunks, FractionalViewport, RenderConfig};
+use qql::traits::Version;
+
+/// Command-line flags for a [`RenderConfig`], which is validated as the flags are parsed, so that
+/// invalid combinations are reported as usage errors.
+#[derive(Debug, Clone)]
+pub struct ConfigArgs(RenderConfig);
+
+impl Deref for ConfigArgs {
+    type Target = RenderConfig;
+    fn deref(&self) -> &RenderConfig {
+        &self.0
+    }
+}
+
+#[derive(clap::Args)]
+struct Flags {
+    /// Speed up collision checking by avoiding our slow `sqrt` implementation. May slightly
+    /// affect layout.
+    #[clap(long)]
+    fast_collisions: bool,
+
+    /// At paint time, ensure that all points have at least a small positive radius.
+    #[clap(long)]
+    inflate_draw_radius: bool,
+
+    /// Lay out the piece as if its seed had this version (`v0`, `v1`, or `unversioned`), instead
+    /// of the version in its trait bits.
+    ///
+    /// Seeds from before V1 lay out radial flow fields with a bug that V1 fixed, so `v1` shows how
+    /// such a seed would have looked under the V1 algorithm. The output is not canonical.
+    #[clap(long, value_name = "VERSION")]
+    force_version: Option<Version>,
+
+    /// Use at least this many segments for every circle. Values below `8` have no effect.
+    ///
+    /// At typical resolutions, circles should look smooth without tweaking. But at very large
+    /// resolutions (say, above 10k pixels wide), the segments may start to become visible,
+    /// especially on small circles. Crank this value up linearly to compensate, at the cost of
+    /// render time.
+    #[clap(long, value_name = "STEPS")]
+    min_circle_steps: Option<u32>,
+
+    /// Restrict rendering to a region of the canvas.
+    ///
+    /// Values are specified as floats from 0.0 (top/left) to 1.0 (bottom/right). For instance,
+    /// `0.1x0.1+0.45+0.45` renders the center 1% of the canvas. See `--width` about how this
+    /// affects the output image size.
+    #[clap(long, value_name = "WxH+X+Y")]
+    viewport: Option<FractionalViewport>,
+
+    /// Chunks for parallel rendering.
+    #[clap(long, value_name = "WxH", default_value_t)]
+    chunks: Chunks,
+
+    /// Output multiple frames showing the construction of the piece.
+    ///
+    /// May be `none` for no animation, `groups` to paint one flow line group at a time, or
+    /// `points:N` (where `N` is a positive integer) to show paint `N` points at a time.
+    #[clap(long, default_value_t)]
+    animate: Animation,
+
+    /// Animate in splatter points immediately after their parents.
+    ///
+    /// By default, all splatters are deferred to the end of the animation. With this option,
+    /// each splatter point is instead drawn immediately after the point that spawned it. This
+    /// takes additional processing and may increase render time. Can only be used if `--animate`
+    /// is also set.
+    #[clap(long, default_value_t)]
+    splatter_immediately: bool,
+}
+
+impl Flags {
+    fn build(self) -> Result<RenderConfig, clap::Error> {
+        let config = RenderConfig {
+            fast_collisions: self.fast_collisions,
+            inflate_draw_radius: self.inflate_draw_radius,
+            force_version: self.force_version,
+            min_circle_steps: self.min_circle_steps,
+            viewport: self.viewport,
+            chunks: self.chunks,
+            animate: self.animate,
+            splatter_immediately: self.splatter_immediately,
+        };
+        config.validate().map_err(|e| {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, e.to_string())
+        })?;
+        Ok(config)
+    }
+}
+
+impl clap::Args for ConfigArgs {
+    fn augment_args(cmd: clap::Command) -> clap::Command {
+        Flags::augment_args(cmd)
+    }
+    fn augment_args_for_update(cmd: clap::Command) -> clap::Command {
+        Flags::augment_args_for_update(cmd)
+    }
+}
+
+impl FromArgMatches for ConfigArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(ConfigArgs(Flags::from_arg_matches(matches)?.build()?))
+    }
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
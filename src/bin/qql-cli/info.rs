@@ -0,0 +1,114 @@
+use qql::seed::{bit_range, SeedInfo, VERSION_BITS};
+
+use crate::Seed;
+
+#[derive(clap::Args)]
+pub struct InfoOpts {
+    seed: Seed,
+
+    /// Print JSON instead of text.
+    #[clap(long)]
+    json: bool,
+}
+
+pub fn main(opts: InfoOpts) {
+    let info = SeedInfo::new(opts.seed.as_bytes());
+    if opts.json {
+        println!("{:#}", to_json(&info));
+    } else {
+        print!("{}", to_text(&info));
+    }
+}
+
+fn to_text(info: &SeedInfo) -> String {
+    let mut out = String::new();
+    let mut line = |label: &str, value: String| out.push_str(&format!("{:<11}{}\n", label, value));
+    line("seed:", format!("0x{}", hex::encode(info.seed)));
+    line("address:", format!("0x{}", hex::encode(info.address)));
+    line("nonce:", format!("0x{}", hex::encode(info.nonce)));
+    let sentinel_note = if info.has_sentinel() {
+        ""
+    } else {
+        " (not ffff, so unversioned)"
+    };
+    line(
+        "sentinel:",
+        format!("0x{}{}", hex::encode(info.sentinel), sentinel_note),
+    );
+    line(
+        "version:",
+        format!(
+            "{} (nibble {:#x}, bits {}..{})",
+            info.version, info.version_nibble, VERSION_BITS.start, VERSION_BITS.end
+        ),
+    );
+    line(
+        "traits:",
+        format!("{:#010x} = {:#034b}", info.trait_field, info.trait_field),
+    );
+
+    out.push_str(&format!(
+        "  {:<6} {:>4}  {:<21} {}\n",
+        "bits", "raw", "trait", "value"
+    ));
+    for bits in &info.trait_bits {
+        let range = format!("{}..{}", bits.bits.start, bits.bits.end);
+        let mut value = format!("{} ({} of {})", bits.value, bits.index, bits.num_options);
+        if bits.raw as usize != bits.index {
+            value.push_str(", wrapped");
+        }
+        out.push_str(&format!(
+            "  {:<6} {:>4}  {:<21} {}\n",
+            range, bits.raw, bits.name, value
+        ));
+    }
+    let unused = &info.unused_bits;
+    let mut value = format!(
+        "{:#0width$b}",
+        bit_range(info.trait_field, unused),
+        width = (unused.end - unused.start) as usize + 2
+    );
+    if unused.start <= VERSION_BITS.start {
+        value.push_str(", including the version nibble");
+    }
+    out.push_str(&format!(
+        "  {:<6} {:>4}  {:<21} {}\n",
+        format!("{}..{}", unused.start, unused.end),
+        info.unused(),
+        "(unused)",
+        value
+    ));
+    out
+}
+
+fn to_json(info: &SeedInfo) -> serde_json::Value {
+    let traits: Vec<serde_json::Value> = info
+        .trait_bits
+        .iter()
+        .map(|bits| {
+            serde_json::json!({
+                "name": bits.name,
+                "bits": [bits.bits.start, bits.bits.end],
+                "raw": bits.raw,
+                "index": bits.index,
+                "num_options": bits.num_options,
+                "value": bits.value,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "seed": format!("0x{}", hex::encode(info.seed)),
+        "address": format!("0x{}", hex::encode(info.address)),
+        "nonce": format!("0x{}", hex::encode(info.nonce)),
+        "sentinel": format!("0x{}", hex::encode(info.sentinel)),
+        "has_sentinel": info.has_sentinel(),
+        "version": info.version.to_string(),
+        "version_nibble": info.version_nibble,
+        "trait_field": format!("{:#010x}", info.trait_field),
+        "traits": traits,
+        "unused_bits": {
+            "bits": [info.unused_bits.start, info.unused_bits.end],
+            "raw": info.unused(),
+        },
+    })
+}
@@ -0,0 +1,971 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Debug, Display};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use clap::error::ErrorKind;
+use clap::Parser;
+
+use qql::catalog::Catalog;
+use qql::config::{Animation, FractionalViewport};
+
+mod catalog;
+mod compose;
+mod config_args;
+mod info;
+mod metadata;
+mod mutate;
+mod progress;
+mod pyramid;
+mod random;
+mod rarity;
+mod rerender;
+mod search;
+
+#[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(flatten)]
+    render: Opts,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Write a Deep Zoom (DZI) tile pyramid, rendering each tile at its own zoom level.
+    Pyramid(pyramid::PyramidOpts),
+    /// Show how a seed breaks down into address, nonce, sentinel, version, and trait bits.
+    Info(info::InfoOpts),
+    /// Build a seed with chosen traits, keeping another seed's address and nonce.
+    Compose(compose::ComposeOpts),
+    /// Show the exact probability of a seed's traits, or of a combination of traits.
+    Rarity(rarity::RarityOpts),
+    /// Find seeds that an address can mint with given traits.
+    Search(search::SearchOpts),
+    /// Generate random V1 seeds, optionally with given traits or from a passphrase.
+    Random(random::RandomOpts),
+    /// Name seeds with aliases and token numbers, in a local catalog.
+    #[clap(subcommand)]
+    Catalog(catalog::CatalogCommand),
+    /// Render a contact sheet of variants of a seed that each change one trait.
+    Mutate(mutate::MutateOpts),
+    /// Print ERC-721 token metadata JSON for a seed, with its traits as attributes.
+    Metadata(metadata::MetadataOpts),
+    /// Render a PNG written by this tool again, using the seed and options recorded in its
+    /// metadata.
+    Rerender(rerender::RerenderOpts),
+}
+
+// Options for rendering a single piece, when no subcommand is given.
+#[derive(clap::Args)]
+struct Opts {
+    #[clap(required = true)]
+    seed: Option<Seed>,
+
+    /// Canvas width.
+    ///
+    /// This applies to the virtual canvas, before any viewport is computed. For instance, if
+    /// `--width` is 1000 and `--viewport` is `0.5x0.5+0.25+0.25`, the actual output file will be
+    /// 500px wide.
+    #[clap(short, long, default_value = "2400", value_parser = clap::value_parser!(i32).range(1..))]
+    width: i32,
+    /// Output file. The format is chosen by extension: `.svg` writes vector output with one
+    /// ellipse per stroke, `.pdf` writes a vector PDF page, `.hpgl`/`.plt` and `.gcode`/`.nc`
+    /// write pen plotter toolpaths, `.tif`/`.tiff` writes a tiled (Big)TIFF chunk by chunk, `.ora`
+    /// writes an OpenRaster file with one layer per painting phase, `.gif` and `.apng` write an
+    /// animation (with `--animate`), and anything else writes a PNG.
+    #[clap(short = 'o')]
+    output_filename: Option<PathBuf>,
+    /// Physical page size for PDF, plotter, or TIFF output, like `24x30in`. Units may be `in`,
+    /// `cm`, `mm`, or `pt`.
+    ///
+    /// The artwork is scaled to fit the page and centered. For TIFF output, this sets the
+    /// resolution tags instead. Defaults to the output dimensions in pixels at 72 pixels per inch.
+    #[clap(long, value_name = "WxH<UNIT>")]
+    page: Option<qql::export::PageSize>,
+    /// Bits per channel for PNG output.
+    ///
+    /// With `16`, colors are painted and composited at 16 bits per channel, which avoids banding
+    /// in subtle color variations on large prints. Cannot be used with `--animate`.
+    #[clap(long, value_name = "BITS", default_value = "8")]
+    bit_depth: BitDepth,
+    /// Tile size in pixels for TIFF output. Must be a multiple of 16.
+    ///
+    /// Chunk boundaries from `--chunks` are rounded to multiples of this size.
+    #[clap(long, value_name = "PX", default_value = "512")]
+    tile_size: u32,
+    /// For OpenRaster output, also write each layer as a transparent PNG next to the output file,
+    /// named like `<name>-shadow.png`.
+    #[clap(long)]
+    layer_pngs: bool,
+    /// Stream frames to `-o` in this format as they're rendered, instead of writing image files.
+    /// Without `-o`, or with `-o -`, frames go to standard output.
+    ///
+    /// With `--animate`, this lets you pipe frames straight into a video encoder, or through a
+    /// named pipe. Output paths ending in `.y4m` imply `--stream y4m`.
+    #[clap(long, value_name = "FORMAT")]
+    stream: Option<StreamFormat>,
+    #[clap(flatten)]
+    plot: PlotOpts,
+    #[clap(flatten)]
+    anim: AnimOpts,
+    #[clap(flatten)]
+    progress: progress::ProgressOpts,
+    #[clap(flatten)]
+    config: config_args::ConfigArgs,
+}
+
+/// Options for pen plotter output.
+#[derive(clap::Args)]
+struct PlotOpts {
+    /// For plotter output, drop strokes that are entirely hidden under later strokes.
+    #[clap(long)]
+    drop_hidden: bool,
+    /// For plotter output, also write a PNG simulating the plot to this path.
+    #[clap(long, value_name = "PATH")]
+    plot_preview: Option<PathBuf>,
+    /// Pen width in millimeters, for the plot preview.
+    #[clap(long, value_name = "MM", default_value = "0.3")]
+    pen_width: f64,
+}
+
+/// Options for GIF and APNG output.
+#[derive(clap::Args)]
+struct AnimOpts {
+    /// Frame rate for GIF, APNG, or streamed output.
+    #[clap(long, value_name = "FPS", default_value = "30")]
+    fps: f64,
+    /// Number of times to play a GIF or APNG, or 0 to loop forever.
+    #[clap(long, value_name = "N", default_value = "0")]
+    plays: u16,
+    /// Seconds to show the last frame of a GIF or APNG before it ends or loops.
+    #[clap(long, value_name = "SECONDS", default_value = "2")]
+    hold: f64,
+    /// Target maximum size for GIF or APNG output, like `15MB` or `800KiB`.
+    ///
+    /// If the animation is larger, it's rendered again with every other frame dropped, until it
+    /// fits.
+    #[clap(long, value_name = "SIZE")]
+    max_size: Option<ByteSize>,
+}
+
+/// A number of bytes, with an optional decimal (`kB`, `MB`, `GB`) or binary (`KiB`, `MiB`, `GiB`)
+/// unit.
+#[derive(Debug, Copy, Clone)]
+struct ByteSize(u64);
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const UNITS: &[(&str, u64)] = &[
+            ("KiB", 1 << 10),
+            ("MiB", 1 << 20),
+            ("GiB", 1 << 30),
+            ("kB", 1_000),
+            ("KB", 1_000),
+            ("MB", 1_000_000),
+            ("GB", 1_000_000_000),
+            ("B", 1),
+        ];
+        let (n, multiplier) = UNITS
+            .iter()
+            .find_map(|&(suffix, m)| Some((s.strip_suffix(suffix)?, m)))
+            .unwrap_or((s, 1));
+        let n: f64 = n
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid size {:?}: {}", s, e))?;
+        if !(n >= 0.0 && n.is_finite()) {
+            anyhow::bail!("Size must be non-negative");
+        }
+        Ok(ByteSize((n * multiplier as f64) as u64))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+enum StreamFormat {
+    /// YUV4MPEG2 (4:2:0, BT.709), as read by FFmpeg and most video encoders.
+    Y4m,
+    /// A text header line, `RGBA <width> <height> <fps>`, then raw 8-bit RGBA frames.
+    Rgba,
+}
+
+impl From<StreamFormat> for qql::export::stream::StreamFormat {
+    fn from(format: StreamFormat) -> Self {
+        match format {
+            StreamFormat::Y4m => Self::Y4m,
+            StreamFormat::Rgba => Self::Rgba,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+enum BitDepth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+impl Opts {
+    fn seed(&self) -> Seed {
+        self.seed.expect("seed is required without a subcommand")
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Seed(pub [u8; 32]);
+impl Seed {
+    fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+/// Accepts 32 bytes of hex, or an alias or token number from the [`catalog`].
+impl FromStr for Seed {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex_error = match HexBytes::<32>::from_str(s) {
+            Ok(HexBytes(bytes)) => return Ok(Seed(bytes)),
+            Err(e) => e,
+        };
+        if s.starts_with("0x") {
+            return Err(hex_error);
+        }
+        match catalog()?.get(s) {
+            Some(entry) => Ok(Seed(entry.seed)),
+            None => anyhow::bail!("Not a hex seed ({}) or a cataloged alias", hex_error),
+        }
+    }
+}
+impl Debug for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("0x")?;
+        let mut buf = [0u8; 2 * 32];
+        hex::encode_to_slice(self.0, &mut buf).unwrap();
+        f.write_str(std::str::from_utf8(&buf).unwrap())
+    }
+}
+impl Display for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as Debug>::fmt(self, f)
+    }
+}
+
+/// The seed catalog, loaded on first use from [`Catalog::default_path`].
+fn catalog() -> anyhow::Result<&'static Catalog> {
+    static CATALOG: OnceLock<Result<Catalog, String>> = OnceLock::new();
+    let catalog = CATALOG.get_or_init(|| {
+        let Some(path) = Catalog::default_path() else {
+            return Ok(Catalog::default());
+        };
+        Catalog::load(&path).map_err(|e| format!("Failed to read {}: {:#}", path.display(), e))
+    });
+    catalog.as_ref().map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// Replaces `--viewport NAME` arguments with the viewport of that name from the catalog entry of
+/// a seed alias in the same command line, like `qql085 --viewport face`. Arguments that are
+/// already `WxH+X+Y` are left alone, as are the arguments of `qql-cli catalog`, which defines
+/// viewport names.
+fn resolve_viewport_names(mut args: Vec<OsString>) -> Vec<OsString> {
+    if args.get(1).is_some_and(|arg| arg == "catalog") {
+        return args;
+    }
+    let lookup = |name: &str| -> Option<FractionalViewport> {
+        let found: Vec<&FractionalViewport> = args
+            .iter()
+            .filter_map(|arg| catalog().ok()?.get(arg.to_str()?))
+            .filter_map(|entry| entry.viewports.get(name))
+            .collect();
+        match found.as_slice() {
+            [first, rest @ ..] if rest.iter().all(|v| v == first) => Some((*first).clone()),
+            _ => None,
+        }
+    };
+    let mut replacements = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        let Some(arg) = arg.to_str() else {
+            continue;
+        };
+        let (index, name) = match arg.strip_prefix("--viewport") {
+            Some("") => match args.get(i + 1).and_then(|next| next.to_str()) {
+                Some(next) => (i + 1, next),
+                None => continue,
+            },
+            Some(rest) => match rest.strip_prefix('=') {
+                Some(value) => (i, value),
+                None => continue,
+            },
+            None => continue,
+        };
+        if name.parse::<FractionalViewport>().is_ok() {
+            continue;
+        }
+        let Some(viewport) = lookup(name) else {
+            usage_error(format!(
+                "--viewport {:?} is not WxH+X+Y, and no cataloged seed given has a \
+                 viewport by that name",
+                name
+            ));
+        };
+        let replacement = if index == i {
+            format!("--viewport={}", viewport)
+        } else {
+            viewport.to_string()
+        };
+        replacements.push((index, replacement));
+    }
+    for (index, replacement) in replacements {
+        args[index] = replacement.into();
+    }
+    args
+}
+
+/// A `TRAIT=VALUE` argument, like `flow_field=spiral`, for [`qql::traits::Traits::set`].
+#[derive(Clone)]
+struct TraitAssignment {
+    name: String,
+    value: String,
+}
+
+impl FromStr for TraitAssignment {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected TRAIT=VALUE, like flow_field=spiral"))?;
+        Ok(TraitAssignment {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+/// Exactly `N` bytes, written in hex with an optional `0x` prefix.
+#[derive(Copy, Clone)]
+struct HexBytes<const N: usize>([u8; N]);
+
+impl<const N: usize> FromStr for HexBytes<N> {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s))?;
+        let bytes: [u8; N] = <[u8; N]>::try_from(bytes)
+            .map_err(|e| anyhow::anyhow!("Expected {} bytes; got {}", N, e.len()))?;
+        Ok(HexBytes(bytes))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum OutputFormat {
+    Png,
+    Svg,
+    Pdf,
+    Hpgl,
+    Gcode,
+    Tiff,
+    Ora,
+    Gif,
+    Apng,
+}
+
+impl OutputFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(OsStr::to_str) {
+            Some(ext) if ext.eq_ignore_ascii_case("svg") => OutputFormat::Svg,
+            Some(ext) if ext.eq_ignore_ascii_case("pdf") => OutputFormat::Pdf,
+            Some(ext) if ext.eq_ignore_ascii_case("hpgl") || ext.eq_ignore_ascii_case("plt") => {
+                OutputFormat::Hpgl
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("gcode") || ext.eq_ignore_ascii_case("nc") => {
+                OutputFormat::Gcode
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("tif") || ext.eq_ignore_ascii_case("tiff") => {
+                OutputFormat::Tiff
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("ora") => OutputFormat::Ora,
+            Some(ext) if ext.eq_ignore_ascii_case("gif") => OutputFormat::Gif,
+            Some(ext) if ext.eq_ignore_ascii_case("apng") => OutputFormat::Apng,
+            _ => OutputFormat::Png,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Hpgl => "hpgl",
+            OutputFormat::Gcode => "gcode",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::Ora => "ora",
+            OutputFormat::Gif => "gif",
+            OutputFormat::Apng => "apng",
+        }
+    }
+
+    /// Whether this format is produced from a stroke recording rather than a raster render.
+    fn is_vector(self) -> bool {
+        !matches!(
+            self,
+            OutputFormat::Png
+                | OutputFormat::Tiff
+                | OutputFormat::Ora
+                | OutputFormat::Gif
+                | OutputFormat::Apng
+        )
+    }
+
+    fn animation_format(self) -> Option<qql::export::animation::AnimationFormat> {
+        use qql::export::animation::AnimationFormat;
+        match self {
+            OutputFormat::Gif => Some(AnimationFormat::Gif),
+            OutputFormat::Apng => Some(AnimationFormat::Apng),
+            _ => None,
+        }
+    }
+
+    fn has_page(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Pdf | OutputFormat::Hpgl | OutputFormat::Gcode | OutputFormat::Tiff
+        )
+    }
+}
+
+fn main() {
+    let cli = Cli::parse_from(resolve_viewport_names(std::env::args_os().collect()));
+    match cli.command {
+        Some(Command::Pyramid(opts)) => pyramid::main(opts),
+        Some(Command::Info(opts)) => info::main(opts),
+        Some(Command::Compose(opts)) => compose::main(opts),
+        Some(Command::Rarity(opts)) => rarity::main(opts),
+        Some(Command::Search(opts)) => search::main(opts),
+        Some(Command::Random(opts)) => random::main(opts),
+        Some(Command::Catalog(command)) => catalog::main(command),
+        Some(Command::Mutate(opts)) => mutate::main(opts),
+        Some(Command::Metadata(opts)) => metadata::main(opts),
+        Some(Command::Rerender(opts)) => rerender::main(opts),
+        None => render_main(cli.render),
+    }
+}
+
+fn render_main(opts: Opts) {
+    let color_db = qql::color::ColorDb::from_bundle();
+    let reporter = progress::Reporter::new(&opts.progress);
+
+    let changed =
+        qql::art::forced_version_changes_layout(opts.seed().as_bytes(), &color_db, &opts.config)
+            .unwrap_or_else(|e| render_failed(e));
+    if let Some(changed) = changed {
+        let own_version = qql::traits::Traits::from_seed(opts.seed().as_bytes()).version;
+        eprintln!(
+            "warning: rendering this {} seed as {}, which is not canonical; this {} the layout",
+            own_version,
+            opts.config.force_version.expect("a version is forced"),
+            if changed {
+                "changes"
+            } else {
+                "does not change"
+            }
+        );
+    }
+
+    let is_y4m_path = opts.output_filename.as_deref().is_some_and(|path| {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("y4m"))
+    });
+    if let Some(stream_format) = opts.stream.or(is_y4m_path.then_some(StreamFormat::Y4m)) {
+        if opts.bit_depth != BitDepth::Eight || opts.layer_pngs {
+            usage_error("--bit-depth and --layer-pngs do not apply to streamed output");
+        }
+        stream_main(&opts, &color_db, &reporter, stream_format);
+        return;
+    }
+
+    let base_filepath = if let Some(f) = opts.output_filename.clone() {
+        f
+    } else {
+        let mut basename = opts.seed().to_string();
+        if opts.config.inflate_draw_radius {
+            basename.push_str("-inflated");
+        }
+        if opts.config.fast_collisions {
+            basename.push_str("-fastcoll");
+        }
+        if let Some(version) = opts.config.force_version {
+            basename.push_str(&format!("-as-{}", version.to_string().to_lowercase()));
+        }
+        basename.push_str(".png");
+        PathBuf::from(basename)
+    };
+    let format = OutputFormat::from_path(&base_filepath);
+    if opts.page.is_some() && !format.has_page() {
+        usage_error("--page only applies to PDF, plotter, or TIFF output");
+    }
+    let is_plot = matches!(format, OutputFormat::Hpgl | OutputFormat::Gcode);
+    if (opts.plot.drop_hidden || opts.plot.plot_preview.is_some()) && !is_plot {
+        usage_error("--drop-hidden and --plot-preview only apply to plotter output");
+    }
+    if opts.layer_pngs && format != OutputFormat::Ora {
+        usage_error("--layer-pngs only applies to OpenRaster output");
+    }
+
+    if opts.bit_depth == BitDepth::Sixteen {
+        if format != OutputFormat::Png {
+            usage_error("--bit-depth only applies to PNG output");
+        }
+        if !matches!(opts.config.animate, Animation::None) {
+            usage_error("--bit-depth 16 is not supported with --animate");
+        }
+        let render_data = qql::art::try_draw16(
+            opts.seed().as_bytes(),
+            &color_db,
+            &opts.config,
+            opts.width,
+            &reporter,
+        )
+        .unwrap_or_else(|e| render_failed(e));
+        let text = png_text(&opts, None);
+        if let Err(e) = render_data.canvas.write_png(&base_filepath, &text) {
+            eprintln!("Failed to write PNG to {}: {}", base_filepath.display(), e);
+            std::process::exit(1);
+        }
+        eprintln!("wrote 16-bit png: {}", base_filepath.display());
+        print_stats(&color_db, &render_data);
+        return;
+    }
+
+    if format == OutputFormat::Tiff {
+        if !matches!(opts.config.animate, Animation::None) {
+            usage_error("--animate is not supported for TIFF output");
+        }
+        let render_data =
+            write_tiff(&opts, &color_db, &reporter, &base_filepath).unwrap_or_else(|e| {
+                eprintln!("Failed to write TIFF to {}: {}", base_filepath.display(), e);
+                std::process::exit(1);
+            });
+        eprintln!("wrote tiff: {}", base_filepath.display());
+        print_stats(&color_db, &render_data);
+        return;
+    }
+
+    if let Some(animation_format) = format.animation_format() {
+        if matches!(opts.config.animate, Animation::None) {
+            usage_error(format!(
+                "{} output requires --animate",
+                format.name().to_uppercase()
+            ));
+        }
+        if !(opts.anim.fps > 0.0 && opts.anim.fps.is_finite()) {
+            usage_error("--fps must be positive");
+        }
+        if !(opts.anim.hold >= 0.0 && opts.anim.hold.is_finite()) {
+            usage_error("--hold must be non-negative");
+        }
+        let anim_opts = qql::export::animation::AnimationOptions {
+            fps: opts.anim.fps,
+            plays: opts.anim.plays,
+            hold: std::time::Duration::from_secs_f64(opts.anim.hold),
+            frame_step: 1,
+        };
+        let layout = qql::art::Layout::try_from_seed(
+            opts.seed().as_bytes(),
+            &color_db,
+            &opts.config.layout_options(),
+            &reporter,
+        )
+        .unwrap_or_else(|e| render_failed(e));
+        let encoded = qql::export::animation::encode_animation(
+            &layout,
+            &color_db,
+            &opts.config.render_options(),
+            opts.width,
+            animation_format,
+            &anim_opts,
+            opts.anim.max_size.map(|size| size.0),
+            &reporter,
+        )
+        .and_then(|encoded| {
+            std::fs::write(&base_filepath, &encoded.data)?;
+            Ok(encoded)
+        })
+        .unwrap_or_else(|e| {
+            eprintln!(
+                "Failed to write {} to {}: {}",
+                format.name().to_uppercase(),
+                base_filepath.display(),
+                e
+            );
+            std::process::exit(1);
+        });
+        eprintln!(
+            "wrote {} with {} frames ({} bytes): {}",
+            format.name(),
+            encoded.num_frames,
+            encoded.data.len(),
+            base_filepath.display()
+        );
+        print_stats(&color_db, &encoded.render_data);
+        return;
+    }
+
+    if format == OutputFormat::Ora {
+        if !matches!(opts.config.animate, Animation::None) {
+            usage_error("--animate is not supported for OpenRaster output");
+        }
+        let render_data = qql::art::try_draw_layered(
+            opts.seed().as_bytes(),
+            &color_db,
+            &opts.config,
+            opts.width,
+            &reporter,
+        )
+        .unwrap_or_else(|e| render_failed(e));
+        if let Err(e) = write_layers(&opts, &render_data.canvas, &base_filepath) {
+            eprintln!("Failed to write ORA to {}: {}", base_filepath.display(), e);
+            std::process::exit(1);
+        }
+        print_stats(&color_db, &render_data);
+        return;
+    }
+
+    if format.is_vector() {
+        if !matches!(opts.config.animate, Animation::None) {
+            usage_error("--animate is not supported for vector output");
+        }
+        let render_data =
+            qql::art::try_record(opts.seed().as_bytes(), &color_db, &opts.config, &reporter)
+                .unwrap_or_else(|e| render_failed(e));
+        if let Err(e) = write_vector(&opts, &color_db, &render_data, format, &base_filepath) {
+            eprintln!(
+                "Failed to write {} to {}: {}",
+                format.name().to_uppercase(),
+                base_filepath.display(),
+                e
+            );
+            std::process::exit(1);
+        }
+        eprintln!("wrote {}: {}", format.name(), base_filepath.display());
+        print_stats(&color_db, &render_data);
+        return;
+    }
+
+    let consume_frame = |frame: qql::art::Frame| {
+        let filename = match frame.number {
+            None => base_filepath.clone(),
+            Some(n) => {
+                let mut filename = base_filepath
+                    .file_stem()
+                    .unwrap_or(OsStr::new(""))
+                    .to_owned();
+                filename.push(format!("{:04}.", n));
+                let mut filename = PathBuf::from(filename);
+                if let Some(ext) = base_filepath.extension() {
+                    filename.set_extension(ext);
+                }
+                base_filepath.with_file_name(filename)
+            }
+        };
+        if let Err(e) = write_png(frame.canvas, &png_text(&opts, frame.number), &filename) {
+            eprintln!("Failed to write PNG to {}: {}", filename.display(), e);
+            std::process::exit(1);
+        }
+        reporter.interrupt();
+        match frame.number {
+            None => eprintln!("wrote png: {}", filename.display()),
+            Some(n) => eprintln!("wrote frame {}: {}", n, filename.display()),
+        };
+    };
+
+    let render_data = qql::art::try_draw(
+        opts.seed().as_bytes(),
+        &color_db,
+        &opts.config,
+        opts.width,
+        &reporter,
+        consume_frame,
+    )
+    .unwrap_or_else(|e| render_failed(e));
+    print_stats(&color_db, &render_data);
+}
+
+/// Writes each frame to a stream as soon as it's rendered.
+fn stream_main(
+    opts: &Opts,
+    color_db: &qql::color::ColorDb,
+    reporter: &progress::Reporter,
+    format: StreamFormat,
+) {
+    use qql::export::stream::FrameStream;
+
+    let path = opts
+        .output_filename
+        .as_deref()
+        .filter(|&path| path != Path::new("-"));
+    let out: Box<dyn Write> = match path {
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+        Some(path) => Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| {
+            eprintln!("Failed to open {}: {}", path.display(), e);
+            std::process::exit(1);
+        }))),
+    };
+    let describe_output = || path.map_or("stdout".to_string(), |p| p.display().to_string());
+    let fail = |e: std::io::Error| -> ! {
+        eprintln!("Failed to write stream to {}: {}", describe_output(), e);
+        std::process::exit(1);
+    };
+
+    let viewport = opts.config.viewport.clone().unwrap_or_default();
+    let (width, height) = qql::art::canvas_dimensions(&viewport, opts.width);
+    let mut stream = FrameStream::new(out, format.into(), width, height, opts.anim.fps)
+        .unwrap_or_else(|e| fail(e));
+    let mut num_frames = 0;
+    let render_data = qql::art::try_draw(
+        opts.seed().as_bytes(),
+        color_db,
+        &opts.config,
+        opts.width,
+        reporter,
+        |frame| {
+            stream.write_frame(frame.canvas).unwrap_or_else(|e| fail(e));
+            num_frames += 1;
+        },
+    )
+    .unwrap_or_else(|e| render_failed(e));
+    stream.finish().unwrap_or_else(|e| fail(e));
+    eprintln!("streamed {} frames to {}", num_frames, describe_output());
+    // Keep standard output clean for the stream itself.
+    let stats_out: &mut dyn Write = match path {
+        None => &mut std::io::stderr(),
+        Some(_) => &mut std::io::stdout(),
+    };
+    write_stats(stats_out, color_db, &render_data);
+}
+
+fn write_vector(
+    opts: &Opts,
+    color_db: &qql::color::ColorDb,
+    render_data: &qql::art::RenderData<qql::art::Recording>,
+    format: OutputFormat,
+    path: &Path,
+) -> std::io::Result<()> {
+    use qql::export::{pdf, plot, svg, PageSize};
+
+    let recording = &render_data.canvas;
+    let page = opts
+        .page
+        .unwrap_or_else(|| PageSize::from_pixels(recording.dimensions(opts.width)));
+    let out = BufWriter::new(File::create(path)?);
+    match format {
+        OutputFormat::Svg => svg::write_svg(recording, opts.width, out),
+        OutputFormat::Pdf => {
+            let mut info = vec![
+                ("Title", format!("QQL {}", opts.seed())),
+                ("Creator", software()),
+            ];
+            info.extend(provenance(opts));
+            pdf::write_pdf(recording, &page, &info, out)
+        }
+        OutputFormat::Hpgl | OutputFormat::Gcode => {
+            let plot_opts = plot::PlotOptions {
+                remove_hidden: opts.plot.drop_hidden,
+                ..plot::PlotOptions::default()
+            };
+            let layers = plot::toolpaths(recording, &render_data.colors_used, &page, &plot_opts);
+            let num_paths: usize = layers.iter().map(|l| l.paths.len()).sum();
+            eprintln!("planned {} paths on {} pens", num_paths, layers.len());
+            if let Some(preview_path) = &opts.plot.plot_preview {
+                let preview = plot::render_preview(
+                    &layers,
+                    color_db,
+                    &page,
+                    opts.plot.pen_width,
+                    recording.dimensions(opts.width).0,
+                );
+                preview
+                    .write_png(preview_path, &[])
+                    .map_err(std::io::Error::other)?;
+                eprintln!("wrote plot preview: {}", preview_path.display());
+            }
+            if format == OutputFormat::Hpgl {
+                plot::write_hpgl(&layers, color_db, &page, out)
+            } else {
+                plot::write_gcode(&layers, color_db, &page, &Default::default(), out)
+            }
+        }
+        OutputFormat::Png
+        | OutputFormat::Tiff
+        | OutputFormat::Ora
+        | OutputFormat::Gif
+        | OutputFormat::Apng => unreachable!("not a vector format"),
+    }
+}
+
+/// Writes an OpenRaster file, plus one PNG per layer if requested.
+fn write_layers(opts: &Opts, layered: &qql::art::LayeredImage, path: &Path) -> std::io::Result<()> {
+    use qql::export::ora;
+
+    ora::write_ora(layered, BufWriter::new(File::create(path)?))?;
+    eprintln!("wrote ora: {}", path.display());
+    if opts.layer_pngs {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        for layer in ora::layers() {
+            let layer_path =
+                path.with_file_name(format!("{}-{}.png", stem, ora::layer_name(layer)));
+            layered
+                .layer(layer)
+                .write_png(&layer_path, &[])
+                .map_err(|e| std::io::Error::other(format!("{}: {}", layer_path.display(), e)))?;
+            eprintln!("wrote layer png: {}", layer_path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Renders chunk by chunk straight into a tiled TIFF, without compositing the full canvas.
+fn write_tiff(
+    opts: &Opts,
+    color_db: &qql::color::ColorDb,
+    reporter: &progress::Reporter,
+    path: &Path,
+) -> std::io::Result<qql::art::RenderData<()>> {
+    use qql::export::tiff::{TiffOptions, TiffWriter};
+
+    let viewport = opts.config.viewport.clone().unwrap_or_default();
+    let (width, height) = qql::art::canvas_dimensions(&viewport, opts.width);
+    let dpi = match opts.page {
+        None => qql::export::POINTS_PER_INCH,
+        Some(page) => {
+            // Match the fit-and-center scaling used for other paged output.
+            let pixels_per_point = f64::max(
+                f64::from(width) / page.width,
+                f64::from(height) / page.height,
+            );
+            pixels_per_point * qql::export::POINTS_PER_INCH
+        }
+    };
+    let mut description = format!("QQL {}", opts.seed());
+    for (key, value) in provenance(opts) {
+        description.push_str(&format!("\n{}: {}", key, value));
+    }
+    let tiff_opts = TiffOptions {
+        tile_size: opts.tile_size,
+        dpi: (dpi, dpi),
+        description,
+        software: software(),
+        ..TiffOptions::default()
+    };
+    let out = BufWriter::new(File::create(path)?);
+    let mut writer = TiffWriter::new(out, width as u32, height as u32, tiff_opts)?;
+    if writer.is_bigtiff() {
+        eprintln!("using BigTIFF for {}x{}px output", width, height);
+    }
+    let render_data = qql::art::try_draw_chunks(
+        opts.seed().as_bytes(),
+        color_db,
+        &opts.config,
+        opts.width,
+        opts.tile_size as i32,
+        reporter,
+        |chunk| {
+            let origin = (chunk.left as u32, chunk.top as u32);
+            if let Err(e) = writer.write_chunk(origin, chunk.canvas) {
+                eprintln!("Failed to write TIFF to {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+    )
+    .unwrap_or_else(|e| render_failed(e));
+    writer.finish()?;
+    Ok(render_data)
+}
+
+/// Exits after invalid arguments that clap can't check by itself, reporting them as clap does
+/// for the ones it can, with exit status 2.
+pub(crate) fn usage_error(message: impl std::fmt::Display) -> ! {
+    clap::Error::raw(ErrorKind::ValueValidation, format!("{}\n", message)).exit()
+}
+
+/// Exits after a library error, with an exit code for its kind so that scripts can tell them
+/// apart: 3 for missing or invalid color data, 4 for unusable chunking options, and 5 for
+/// internal inconsistencies. Other failures exit with 1, and invalid arguments with 2.
+fn render_failed(e: qql::Error) -> ! {
+    let code = match e {
+        qql::Error::Config(_) => 2,
+        qql::Error::MissingPalette(_) | qql::Error::InvalidColor(_) | qql::Error::ColorDb(_) => 3,
+        qql::Error::TooManyChunks { .. } => 4,
+        qql::Error::ChunkMismatch => 5,
+    };
+    eprintln!("fatal: {}", e);
+    std::process::exit(code)
+}
+
+fn software() -> String {
+    format!("qqlrs {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Metadata describing how an output was produced, for formats that can store it.
+///
+/// `QQLConfig` holds the config as command-line flags, which [`qql::config::RenderConfig::from_args`]
+/// parses back. `QQLCanonical` is `false` if any flag deliberately changes the output.
+fn provenance(opts: &Opts) -> [(&'static str, String); 5] {
+    let traits = qql::traits::Traits::from_seed(opts.seed().as_bytes());
+    [
+        ("QQLSeed", opts.seed().to_string()),
+        ("QQLWidth", opts.width.to_string()),
+        ("QQLTraits", format!("{:?}", traits)),
+        ("QQLConfig", opts.config.to_args().join(" ")),
+        ("QQLCanonical", opts.config.is_canonical().to_string()),
+    ]
+}
+
+/// PNG text chunks for a render or an animation frame. See [`provenance`]; `rerender` reads
+/// these back.
+fn png_text(opts: &Opts, frame_number: Option<u32>) -> Vec<(&'static str, String)> {
+    let mut text = vec![("Software", software())];
+    text.extend(provenance(opts));
+    if let Some(n) = frame_number {
+        text.push(("QQLFrame", n.to_string()));
+    }
+    text
+}
+
+fn write_png(
+    canvas: &qql::canvas::Canvas,
+    text: &[(&str, String)],
+    path: &Path,
+) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    qql::export::png::write_png(canvas, text, &mut out)?;
+    out.flush()
+}
+
+fn print_stats<C>(color_db: &qql::color::ColorDb, render_data: &qql::art::RenderData<C>) {
+    write_stats(&mut std::io::stdout(), color_db, render_data);
+}
+
+fn write_stats<C>(
+    out: &mut dyn Write,
+    color_db: &qql::color::ColorDb,
+    render_data: &qql::art::RenderData<C>,
+) {
+    let color_names: Vec<&str> = render_data
+        .colors_used
+        .iter()
+        .map(|k| {
+            color_db
+                .color(k)
+                .map_or("<invalid color>", |c| c.name.as_str())
+        })
+        .collect();
+    let stats = format!(
+        "num_points: {}\ncolors: {:?}\nring counts: {:?}\n",
+        render_data.num_points, color_names, render_data.ring_counts_used
+    );
+    // Like `println!`, treat failure to write stats as fatal.
+    out.write_all(stats.as_bytes())
+        .expect("failed to write stats");
+}
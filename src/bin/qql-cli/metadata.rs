@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use qql::token::TokenMetadata;
+
+use crate::Seed;
+
+#[derive(clap::Args)]
+pub struct MetadataOpts {
+    seed: Seed,
+
+    /// Path or URL of a thumbnail, to store as the `image`.
+    #[clap(long, value_name = "PATH")]
+    thumbnail: Option<String>,
+    /// Output file. Defaults to standard output.
+    #[clap(short = 'o')]
+    output_filename: Option<PathBuf>,
+}
+
+pub fn main(opts: MetadataOpts) {
+    let json = TokenMetadata::new(opts.seed.as_bytes(), opts.thumbnail).to_json();
+    match opts.output_filename {
+        None => println!("{}", json),
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, json + "\n") {
+                eprintln!("Failed to write metadata to {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+            eprintln!("wrote metadata: {}", path.display());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use qql::config::Animation;
+use qql::export::contact_sheet::{render_contact_sheet, Cell, SheetOptions};
+use qql::seed::{mutations, MutationKind};
+
+use crate::{software, write_png, Seed};
+
+#[derive(clap::Args)]
+pub struct MutateOpts {
+    seed: Seed,
+
+    /// Flip each trait bit in turn, instead of trying each other option of each trait.
+    #[clap(long)]
+    bits: bool,
+    /// Only vary these traits, like `flow_field` or `bullseye_rings.one`. Repeatable.
+    #[clap(long = "trait", value_name = "NAME")]
+    traits: Vec<String>,
+
+    /// Canvas width of each thumbnail.
+    #[clap(short, long, default_value = "300", value_parser = clap::value_parser!(i32).range(1..))]
+    width: i32,
+    /// Number of thumbnails in each row.
+    #[clap(long, default_value = "6")]
+    columns: usize,
+    /// Output file. Defaults to `<SEED>-mutations.png`.
+    #[clap(short = 'o')]
+    output_filename: Option<PathBuf>,
+    #[clap(flatten)]
+    config: crate::config_args::ConfigArgs,
+}
+
+pub fn main(opts: MutateOpts) {
+    if !matches!(opts.config.animate, Animation::None) || opts.config.splatter_immediately {
+        crate::usage_error("--animate is not supported for contact sheets");
+    }
+    if opts.columns == 0 {
+        crate::usage_error("--columns must be positive");
+    }
+
+    let kind = if opts.bits {
+        MutationKind::Bits
+    } else {
+        MutationKind::Options
+    };
+    let mut mutations = mutations(opts.seed.as_bytes(), kind);
+    if !opts.traits.is_empty() {
+        let wanted = |name: &str| opts.traits.iter().any(|t| t.replace('-', "_") == name);
+        mutations.retain(|m| wanted(m.trait_name));
+        if mutations.is_empty() {
+            crate::usage_error(format!("no traits match {:?}", opts.traits));
+        }
+    }
+
+    let mut cells = vec![Cell {
+        seed: *opts.seed.as_bytes(),
+        label: vec!["original".to_string()],
+    }];
+    for m in &mutations {
+        let heading = match kind {
+            MutationKind::Options => m.trait_name.to_string(),
+            MutationKind::Bits => format!("bit {}: {}", m.bits.start, m.trait_name),
+        };
+        let change = if m.changes_trait() {
+            format!("{} -> {}", m.from, m.to)
+        } else {
+            format!("{} (no change)", m.to)
+        };
+        cells.push(Cell {
+            seed: m.seed,
+            label: vec![heading, change],
+        });
+    }
+
+    let path = opts
+        .output_filename
+        .unwrap_or_else(|| PathBuf::from(format!("{}-mutations.png", opts.seed)));
+    let sheet_opts = SheetOptions {
+        columns: opts.columns,
+        padding: (opts.width / 20).max(4),
+        text_scale: (opts.width / 150).max(1),
+    };
+    let color_db = qql::color::ColorDb::from_bundle();
+    let sheet = render_contact_sheet(&cells, &color_db, &opts.config, opts.width, &sheet_opts);
+    // Not `QQLSeed`, since this isn't a render that `rerender` could reproduce.
+    let text = [
+        ("Software", software()),
+        ("Title", format!("Mutations of QQL {}", opts.seed)),
+    ];
+    if let Err(e) = write_png(&sheet, &text, &path) {
+        eprintln!("Failed to write PNG to {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+    eprintln!(
+        "wrote contact sheet of {} mutations: {}",
+        mutations.len(),
+        path.display()
+    );
+}
@@ -0,0 +1,185 @@
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use qql::progress::{Event, Observer, Stage};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ProgressOpts {
+    /// Don't report progress. Errors, warnings, and the files written are still printed.
+    #[clap(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Report every stage, chunk, and frame with timings, one per line, instead of showing a
+    /// progress bar.
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+impl ProgressOpts {
+    /// The flags that select this level of reporting, to pass on to another command line.
+    pub fn to_args(&self) -> Option<&'static str> {
+        if self.quiet {
+            Some("--quiet")
+        } else if self.verbose {
+            Some("--verbose")
+        } else {
+            None
+        }
+    }
+}
+
+/// How long to wait between redraws of the progress bar.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 30;
+
+/// Reports progress on standard error: a progress bar with an ETA while painting, or with
+/// `--verbose`, a line per event. The bar is only drawn if standard error is a terminal.
+pub struct Reporter {
+    verbose: bool,
+    show_bar: bool,
+    show_notes: bool,
+    bar: Mutex<Bar>,
+}
+
+#[derive(Default)]
+struct Bar {
+    paint_start: Option<Instant>,
+    last_drawn: Option<Instant>,
+}
+
+impl Reporter {
+    pub fn new(opts: &ProgressOpts) -> Self {
+        Reporter {
+            verbose: opts.verbose,
+            show_bar: !opts.quiet && !opts.verbose && std::io::stderr().is_terminal(),
+            show_notes: !opts.quiet,
+            bar: Mutex::new(Bar::default()),
+        }
+    }
+
+    /// Clears the progress bar, if it's showing, so that a message can be printed. The bar is
+    /// drawn again on the next update.
+    pub fn interrupt(&self) {
+        if !self.show_bar {
+            return;
+        }
+        let mut bar = self.bar.lock().unwrap();
+        if bar.last_drawn.take().is_some() {
+            clear_line();
+        }
+    }
+
+    fn log(&self, event: &Event) {
+        match event {
+            Event::StageFinished { stage, elapsed } => {
+                eprintln!("finished {} in {:.2?}", stage.name(), elapsed)
+            }
+            Event::ChunkStarted {
+                column,
+                row,
+                left,
+                top,
+                width,
+                height,
+            } => eprintln!(
+                "painting chunk ({}, {}): {}x{}+{}+{}px",
+                column, row, width, height, left, top
+            ),
+            Event::ChunkFinished {
+                column,
+                row,
+                elapsed,
+            } => eprintln!("painted chunk ({}, {}) in {:.2?}", column, row, elapsed),
+            Event::FrameEmitted {
+                number: Some(n),
+                elapsed,
+            } => eprintln!("painted frame {} at {:.2?}", n, elapsed),
+            _ => (),
+        }
+    }
+
+    fn update_bar(&self, event: &Event) {
+        let mut bar = self.bar.lock().unwrap();
+        match *event {
+            Event::StageStarted(Stage::Paint) => {
+                *bar = Bar {
+                    paint_start: Some(Instant::now()),
+                    last_drawn: None,
+                };
+            }
+            Event::StageFinished {
+                stage: Stage::Paint,
+                ..
+            } => {
+                if bar.last_drawn.is_some() {
+                    clear_line();
+                }
+                *bar = Bar::default();
+            }
+            Event::PointsPainted { painted, total } => {
+                let Some(start) = bar.paint_start else {
+                    return;
+                };
+                let now = Instant::now();
+                if bar
+                    .last_drawn
+                    .is_some_and(|last| now - last < REDRAW_INTERVAL)
+                {
+                    return;
+                }
+                bar.last_drawn = Some(now);
+                draw_bar(painted, total, now - start);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Observer for Reporter {
+    fn on_event(&self, event: &Event) {
+        if let Event::Note(note) = event {
+            if self.show_notes {
+                self.interrupt();
+                eprintln!("{}", note);
+            }
+            return;
+        }
+        if self.verbose {
+            self.log(event);
+        }
+        if self.show_bar {
+            self.update_bar(event);
+        }
+    }
+}
+
+fn draw_bar(painted: usize, total: usize, elapsed: Duration) {
+    let fraction = if total == 0 {
+        1.0
+    } else {
+        (painted as f64 / total as f64).min(1.0)
+    };
+    let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+    let eta = if fraction > 0.0 {
+        let remaining = elapsed.as_secs_f64() * (1.0 - fraction) / fraction;
+        format!("ETA {}s", remaining.ceil())
+    } else {
+        "ETA ?".to_string()
+    };
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(
+        stderr,
+        "\r\x1b[2Kpainting [{}{}] {:3.0}% {}",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        fraction * 100.0,
+        eta
+    );
+    let _ = stderr.flush();
+}
+
+fn clear_line() {
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(stderr, "\r\x1b[2K");
+    let _ = stderr.flush();
+}
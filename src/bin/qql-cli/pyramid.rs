@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use qql::config::Animation;
+use qql::export::pyramid::{write_dzi, PyramidOptions};
+
+use crate::Seed;
+
+#[derive(clap::Args)]
+pub struct PyramidOpts {
+    seed: Seed,
+
+    /// Canvas width of the most detailed level. Each shallower level is half as wide.
+    #[clap(short, long, default_value = "2400", value_parser = clap::value_parser!(i32).range(1..))]
+    width: i32,
+    /// Path to the `.dzi` descriptor. Tiles are written to a sibling `<name>_files` directory.
+    #[clap(short = 'o')]
+    output_filename: Option<PathBuf>,
+    /// Tile size in pixels, not counting overlap.
+    #[clap(long, value_name = "PX", default_value = "254")]
+    tile_size: u32,
+    /// Pixels of overlap between adjacent tiles.
+    #[clap(long, value_name = "PX", default_value = "1")]
+    overlap: u32,
+    #[clap(flatten)]
+    config: crate::config_args::ConfigArgs,
+    #[clap(flatten)]
+    progress: crate::progress::ProgressOpts,
+}
+
+pub fn main(opts: PyramidOpts) {
+    if opts.config.viewport.is_some() {
+        crate::usage_error("--viewport is not supported for pyramids");
+    }
+    if !matches!(opts.config.animate, Animation::None) || opts.config.splatter_immediately {
+        crate::usage_error("--animate is not supported for pyramids");
+    }
+    if opts.tile_size == 0 {
+        crate::usage_error("--tile-size must be positive");
+    }
+
+    let dzi_path = opts
+        .output_filename
+        .unwrap_or_else(|| PathBuf::from(format!("{}.dzi", opts.seed)));
+    let color_db = qql::color::ColorDb::from_bundle();
+    let reporter = crate::progress::Reporter::new(&opts.progress);
+    let layout = qql::art::Layout::try_from_seed(
+        opts.seed.as_bytes(),
+        &color_db,
+        &opts.config.layout_options(),
+        &reporter,
+    )
+    .unwrap_or_else(|e| crate::render_failed(e));
+    let pyramid_opts = PyramidOptions {
+        tile_size: opts.tile_size,
+        overlap: opts.overlap,
+    };
+    match write_dzi(
+        &layout,
+        &color_db,
+        &opts.config,
+        opts.width,
+        pyramid_opts,
+        &dzi_path,
+        &reporter,
+    ) {
+        Ok(num_tiles) => eprintln!("wrote {} tiles: {}", num_tiles, dzi_path.display()),
+        Err(e) => {
+            eprintln!("Failed to write pyramid to {}: {}", dzi_path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use qql::search::TraitFilter;
+use qql::seed::{fresh_rng, passphrase_rng, random};
+
+use crate::{render_main, Cli, HexBytes, Seed};
+
+#[derive(clap::Args)]
+pub struct RandomOpts {
+    /// Trait constraints, like `flow_field=spiral palette in [seoul,fidenza]`, as for `qql-cli
+    /// search`.
+    #[clap(value_name = "CONSTRAINT")]
+    constraints: Vec<String>,
+
+    /// Minter address, as 20 hex bytes. Defaults to a random address.
+    #[clap(long, value_name = "HEX")]
+    address: Option<HexBytes<20>>,
+    /// Derive seeds from this passphrase instead of fresh randomness, so that the same passphrase
+    /// always gives the same seeds.
+    #[clap(long, value_name = "TEXT")]
+    passphrase: Option<String>,
+    /// Number of seeds to generate.
+    #[clap(short = 'n', default_value = "1")]
+    count: usize,
+
+    /// Also render each seed, to `<SEED>.png` or to `-o`.
+    #[clap(long)]
+    render: bool,
+    /// Canvas width, with `--render`.
+    #[clap(short, long, default_value = "2400", requires = "render", value_parser = clap::value_parser!(i32).range(1..))]
+    width: i32,
+    /// Output file, with `--render` and a single seed. The format is chosen by extension, as
+    /// when rendering a seed directly.
+    #[clap(short = 'o', requires = "render")]
+    output_filename: Option<PathBuf>,
+}
+
+pub fn main(opts: RandomOpts) {
+    let filter: TraitFilter = match opts.constraints.join(" ").parse() {
+        Ok(filter) => filter,
+        Err(e) => crate::usage_error(e),
+    };
+    if opts.output_filename.is_some() && opts.count != 1 {
+        crate::usage_error("-o only applies to a single seed");
+    }
+    let mut rng = match &opts.passphrase {
+        Some(passphrase) => passphrase_rng(passphrase),
+        None => fresh_rng(),
+    };
+    let address = opts.address.map(|HexBytes(address)| address);
+
+    let mut seeds = Vec::with_capacity(opts.count);
+    for _ in 0..opts.count {
+        let Some(seed) = random(&mut rng, address.as_ref(), &filter) else {
+            eprintln!("fatal: no seed can satisfy these constraints");
+            std::process::exit(1);
+        };
+        println!("{}", Seed(seed));
+        seeds.push(seed);
+    }
+    if !opts.render {
+        return;
+    }
+
+    for seed in seeds {
+        // Render through the usual command line, so that the output is named and tagged just
+        // like `qql-cli <SEED>` would.
+        let mut args: Vec<OsString> = vec![
+            "qql-cli".into(),
+            Seed(seed).to_string().into(),
+            format!("--width={}", opts.width).into(),
+        ];
+        if let Some(path) = &opts.output_filename {
+            args.extend(["-o".into(), path.clone().into()]);
+        }
+        let render_opts = Cli::try_parse_from(&args)
+            .expect("render arguments should parse")
+            .render;
+        render_main(render_opts);
+    }
+}
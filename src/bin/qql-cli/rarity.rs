@@ -0,0 +1,76 @@
+use qql::rarity::{combination_probability, distributions, score, SeedRarity};
+
+use crate::{Seed, TraitAssignment};
+
+#[derive(clap::Args)]
+pub struct RarityOpts {
+    /// Seed whose traits to rate.
+    #[clap(conflicts_with_all = ["table", "traits"])]
+    seed: Option<Seed>,
+
+    /// Print the probability of every option of every trait instead.
+    #[clap(long, conflicts_with = "traits")]
+    table: bool,
+
+    /// Rate a combination of traits instead, like `-t flow_field=spiral -t color_palette=seoul`.
+    #[clap(short = 't', long = "trait", value_name = "TRAIT=VALUE")]
+    traits: Vec<TraitAssignment>,
+}
+
+pub fn main(opts: RarityOpts) {
+    if let Some(seed) = opts.seed {
+        let rarity = SeedRarity::new(seed.as_bytes());
+        println!("{:<21} {:<13} {:>11}", "trait", "value", "probability");
+        for t in &rarity.traits {
+            println!(
+                "{:<21} {:<13} {:>11}",
+                t.name,
+                t.value,
+                percent(t.probability)
+            );
+        }
+        print_combined("all traits", rarity.probability);
+    } else if opts.table {
+        for dist in distributions() {
+            println!(
+                "{} (bits {}..{}):",
+                dist.name, dist.bits.start, dist.bits.end
+            );
+            for option in &dist.options {
+                println!(
+                    "  {:<13} {:>8}  ({} of {} raw values)",
+                    option.value,
+                    percent(option.probability),
+                    option.raw_values,
+                    1 << (dist.bits.end - dist.bits.start)
+                );
+            }
+        }
+    } else if !opts.traits.is_empty() {
+        let traits: Vec<(&str, &str)> = opts
+            .traits
+            .iter()
+            .map(|t| (t.name.as_str(), t.value.as_str()))
+            .collect();
+        match combination_probability(&traits) {
+            Ok(0.0) => println!("combination: impossible; some traits conflict"),
+            Ok(p) => print_combined("combination", p),
+            Err(e) => crate::usage_error(e),
+        }
+    } else {
+        crate::usage_error("give a seed, --table, or at least one --trait");
+    }
+}
+
+fn percent(p: f64) -> String {
+    format!("{}%", p * 100.0)
+}
+
+fn print_combined(label: &str, probability: f64) {
+    println!(
+        "{}: 1 in {}, rarity score {:.2}",
+        label,
+        1.0 / probability,
+        score(probability)
+    );
+}
@@ -0,0 +1,146 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::Parser;
+
+use crate::{png_text, print_stats, render_failed, render_main, software, write_png, Cli};
+
+#[derive(clap::Args)]
+pub struct RerenderOpts {
+    /// A PNG written by `qql-cli`.
+    input: PathBuf,
+
+    /// Canvas width for the new render. Defaults to the width of the original.
+    #[clap(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+    width: Option<i32>,
+    /// Output file. Defaults to `<name>-rerender.png` next to the input.
+    #[clap(short = 'o')]
+    output_filename: Option<PathBuf>,
+    #[clap(flatten)]
+    progress: crate::progress::ProgressOpts,
+}
+
+/// What a PNG's text chunks say about how it was rendered.
+struct Provenance {
+    software: Option<String>,
+    seed: String,
+    width: String,
+    config: String,
+    frame: Option<u32>,
+    bit_depth: png::BitDepth,
+}
+
+fn read_provenance(path: &Path) -> anyhow::Result<Provenance> {
+    let reader = png::Decoder::new(BufReader::new(File::open(path)?)).read_info()?;
+    let text = qql::export::png::text_chunks(reader.info())?;
+    let get = |keyword: &str| {
+        text.iter()
+            .find(|(k, _)| k == keyword)
+            .map(|(_, v)| v.clone())
+    };
+    let seed = get("QQLSeed").context("No QQLSeed metadata; was this PNG written by qql-cli?")?;
+    let frame = match get("QQLFrame") {
+        None => None,
+        Some(n) => Some(n.parse().context("Invalid QQLFrame")?),
+    };
+    Ok(Provenance {
+        software: get("Software"),
+        seed,
+        width: get("QQLWidth").context("No QQLWidth metadata")?,
+        config: get("QQLConfig").unwrap_or_default(),
+        frame,
+        bit_depth: reader.info().bit_depth,
+    })
+}
+
+pub fn main(opts: RerenderOpts) {
+    let recorded = read_provenance(&opts.input).unwrap_or_else(|e| {
+        eprintln!("fatal: failed to read {}: {:#}", opts.input.display(), e);
+        std::process::exit(1);
+    });
+    if let Some(recorded_software) = &recorded.software {
+        if *recorded_software != software() {
+            eprintln!(
+                "warning: {} was rendered by {}, but this is {}; output may differ",
+                opts.input.display(),
+                recorded_software,
+                software()
+            );
+        }
+    }
+    let output_filename = opts.output_filename.unwrap_or_else(|| {
+        let stem = opts.input.file_stem().unwrap_or_default().to_string_lossy();
+        opts.input.with_file_name(format!("{}-rerender.png", stem))
+    });
+
+    // Rebuild the original command line, so that the new render goes through exactly the same
+    // option handling as the original did.
+    let width = opts.width.map_or(recorded.width, |w| w.to_string());
+    let bit_depth = match recorded.bit_depth {
+        png::BitDepth::Sixteen => "16",
+        _ => "8",
+    };
+    let mut args: Vec<OsString> = vec![
+        "qql-cli".into(),
+        recorded.seed.into(),
+        format!("--width={}", width).into(),
+        format!("--bit-depth={}", bit_depth).into(),
+        "-o".into(),
+        output_filename.clone().into(),
+    ];
+    args.extend(opts.progress.to_args().map(OsString::from));
+    args.extend(recorded.config.split_whitespace().map(OsString::from));
+    let render_opts = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli.render,
+        Err(e) => {
+            eprintln!("fatal: invalid metadata in {}: {}", opts.input.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let Some(frame_number) = recorded.frame else {
+        render_main(render_opts);
+        return;
+    };
+    // Only this frame of the animation was recorded, so write only this frame.
+    let color_db = qql::color::ColorDb::from_bundle();
+    let reporter = crate::progress::Reporter::new(&render_opts.progress);
+    let mut found = false;
+    let render_data = qql::art::try_draw(
+        render_opts.seed().as_bytes(),
+        &color_db,
+        &render_opts.config,
+        render_opts.width,
+        &reporter,
+        |frame| {
+            if frame.number != Some(frame_number) {
+                return;
+            }
+            let text = png_text(&render_opts, frame.number);
+            if let Err(e) = write_png(frame.canvas, &text, &output_filename) {
+                eprintln!(
+                    "Failed to write PNG to {}: {}",
+                    output_filename.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+            reporter.interrupt();
+            eprintln!(
+                "wrote frame {}: {}",
+                frame_number,
+                output_filename.display()
+            );
+            found = true;
+        },
+    )
+    .unwrap_or_else(|e| render_failed(e));
+    if !found {
+        eprintln!("fatal: animation has no frame {}", frame_number);
+        std::process::exit(1);
+    }
+    print_stats(&color_db, &render_data);
+}
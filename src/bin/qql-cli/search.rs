@@ -0,0 +1,132 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+use qql::art::analyze;
+use qql::color::ColorDb;
+use qql::config::RenderConfig;
+use qql::search::{search, CountRange, OutcomeFilter, SearchSpace, TraitFilter};
+
+use crate::{HexBytes, Seed};
+
+#[derive(clap::Args)]
+pub struct SearchOpts {
+    /// Minter address to search under, as 20 hex bytes.
+    #[clap(long, value_name = "HEX")]
+    address: HexBytes<20>,
+
+    /// Trait constraints, like `flow_field=spiral spacing=dense palette in [seoul,fidenza]`.
+    /// Each is `TRAIT=VALUE`, `TRAIT=VALUE,VALUE,...`, or `TRAIT in [VALUE,...]`, with names and
+    /// values as shown by `qql-cli info`.
+    #[clap(value_name = "CONSTRAINT")]
+    constraints: Vec<String>,
+
+    /// Only keep pieces with this many points, like `3000..5000`, `..=2000`, or `8000..`.
+    /// Filtering on outcomes lays out each seed whose traits match, which is much slower than
+    /// matching traits alone.
+    #[clap(long, value_name = "RANGE")]
+    points: Option<CountRange>,
+
+    /// Only keep pieces that use this color, by name, like `fPink`. Repeatable.
+    #[clap(long = "with-color", value_name = "NAME")]
+    with_colors: Vec<String>,
+
+    /// Only keep pieces that don't use this color, by name. Repeatable.
+    #[clap(long = "without-color", value_name = "NAME")]
+    without_colors: Vec<String>,
+
+    /// Only keep pieces with a number of points drawn with the given number of rings in a range,
+    /// like `7=10..` for at least 10 points with seven rings. Repeatable.
+    #[clap(long = "rings", value_name = "RINGS=RANGE")]
+    ring_counts: Vec<RingCountRange>,
+
+    /// First nonce to try, as 6 hex bytes. Later nonces count up from here.
+    #[clap(long, value_name = "HEX")]
+    nonce: Option<HexBytes<6>>,
+
+    /// Stop after trying this many nonces. Each nonce has 2^28 candidate seeds.
+    #[clap(long, default_value_t = 1 << 48)]
+    nonces: u64,
+
+    /// Stop after finding this many seeds.
+    #[clap(short = 'n', long, default_value = "10")]
+    limit: usize,
+}
+
+#[derive(Clone)]
+struct RingCountRange(u32, CountRange);
+
+impl FromStr for RingCountRange {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rings, range) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected RINGS=RANGE, like 7=10.."))?;
+        Ok(RingCountRange(rings.trim().parse()?, range.parse()?))
+    }
+}
+
+pub fn main(opts: SearchOpts) {
+    let color_db = ColorDb::from_bundle();
+    let (filter, outcomes) = match filters(&opts, &color_db) {
+        Ok(filters) => filters,
+        Err(e) => crate::usage_error(e),
+    };
+    if filter == TraitFilter::default() && outcomes.is_empty() {
+        crate::usage_error("give at least one trait or outcome constraint");
+    }
+    if filter.is_empty() {
+        eprintln!("fatal: no seed can satisfy these constraints");
+        std::process::exit(1);
+    }
+    let mut start_nonce = [0; 8];
+    if let Some(HexBytes(nonce)) = opts.nonce {
+        start_nonce[2..].copy_from_slice(&nonce);
+    }
+    let space = SearchSpace {
+        address: opts.address.0,
+        start_nonce: u64::from_be_bytes(start_nonce),
+        num_nonces: opts.nonces,
+    };
+
+    let start_time = Instant::now();
+    let config = RenderConfig::default();
+    let result = search(&space, &filter, opts.limit, |seed| {
+        outcomes.is_empty() || outcomes.matches(&analyze(seed, &color_db, &config))
+    });
+    for seed in &result.seeds {
+        println!("{}", Seed(*seed));
+    }
+    let laid_out = if outcomes.is_empty() {
+        String::new()
+    } else {
+        format!(", {} laid out", result.num_accept_calls)
+    };
+    eprintln!(
+        "found {} seeds in {} candidates{} ({:.2?})",
+        result.seeds.len(),
+        result.num_candidates,
+        laid_out,
+        start_time.elapsed()
+    );
+}
+
+fn filters(opts: &SearchOpts, color_db: &ColorDb) -> anyhow::Result<(TraitFilter, OutcomeFilter)> {
+    let filter = opts.constraints.join(" ").parse()?;
+    let colors = |names: &[String]| -> anyhow::Result<Vec<_>> {
+        names
+            .iter()
+            .map(|name| OutcomeFilter::color_key(color_db, name))
+            .collect()
+    };
+    let outcomes = OutcomeFilter {
+        num_points: opts.points,
+        required_colors: colors(&opts.with_colors)?,
+        forbidden_colors: colors(&opts.without_colors)?,
+        ring_counts: opts
+            .ring_counts
+            .iter()
+            .map(|&RingCountRange(rings, range)| (rings, range))
+            .collect(),
+    };
+    Ok((filter, outcomes))
+}
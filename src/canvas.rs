@@ -0,0 +1,180 @@
+//! A raster canvas with 8 bits per channel, as produced by [`crate::art::draw`].
+//!
+//! Pixels are stored premultiplied, in whatever layout the rasterizer paints in. [`Canvas`] gives
+//! access to them as straight (non-premultiplied) RGBA, which is what image formats and libraries
+//! expect. For 16-bit output, see [`crate::canvas16::Canvas16`].
+
+use std::io::Write;
+use std::path::Path;
+
+use raqote::DrawTarget;
+
+use crate::export::straight_rgba;
+
+/// An image with 8 bits per channel. Read its pixels as straight RGBA with
+/// [`Canvas::straight_pixels`], [`Canvas::rows`], or [`Canvas::to_rgba8`].
+pub struct Canvas {
+    dt: DrawTarget,
+}
+
+impl Canvas {
+    /// Creates a fully transparent canvas.
+    pub fn new(width: i32, height: i32) -> Self {
+        Canvas {
+            dt: DrawTarget::new(width, height),
+        }
+    }
+
+    /// Creates a canvas from straight RGBA bytes in row-major order, or `None` if there aren't
+    /// exactly `4 * width * height` of them.
+    pub fn from_rgba8(width: i32, height: i32, rgba: &[u8]) -> Option<Self> {
+        let len = (width.max(0) as usize) * (height.max(0) as usize);
+        if rgba.len() != len * 4 {
+            return None;
+        }
+        let premul = |c: u8, a: u8| ((u32::from(c) * u32::from(a) + 127) / 255) as u8;
+        let data = rgba
+            .chunks_exact(4)
+            .map(|px| {
+                let [r, g, b, a] = [px[0], px[1], px[2], px[3]];
+                u32::from_be_bytes([a, premul(r, a), premul(g, a), premul(b, a)])
+            })
+            .collect();
+        Some(Canvas {
+            dt: DrawTarget::from_vec(width, height, data),
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.dt.width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.dt.height()
+    }
+
+    /// The pixel at `(x, y)` as straight `[r, g, b, a]`. Panics if it's outside the canvas.
+    pub fn pixel(&self, x: i32, y: i32) -> [u8; 4] {
+        assert!(
+            (0..self.width()).contains(&x) && (0..self.height()).contains(&y),
+            "pixel ({}, {}) is outside a {}x{} canvas",
+            x,
+            y,
+            self.width(),
+            self.height()
+        );
+        straight_rgba(self.data()[(y * self.width() + x) as usize])
+    }
+
+    /// Iterates over pixels as straight `[r, g, b, a]`, in row-major order.
+    pub fn straight_pixels(&self) -> impl Iterator<Item = [u8; 4]> + '_ {
+        self.data().iter().map(|&px| straight_rgba(px))
+    }
+
+    /// Iterates over rows from top to bottom, each as an iterator over its pixels from left to
+    /// right, as for [`Canvas::straight_pixels`].
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = [u8; 4]> + '_> + '_ {
+        self.data()
+            .chunks(self.width().max(1) as usize)
+            .map(|row| row.iter().map(|&px| straight_rgba(px)))
+    }
+
+    /// All pixels as straight RGBA bytes, in row-major order.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.straight_pixels().flatten().collect()
+    }
+
+    /// Encodes this canvas as an 8-bit RGBA PNG, with a text chunk for each `(keyword, text)`
+    /// pair. See [`crate::export::png::add_text_chunks`].
+    pub fn write_png_to<W: Write>(
+        &self,
+        out: W,
+        text: &[(&str, String)],
+    ) -> Result<(), png::EncodingError> {
+        crate::export::png::write_png(self, text, out)
+    }
+
+    /// Writes this canvas to an 8-bit RGBA PNG file.
+    pub fn write_png<P: AsRef<Path>>(
+        &self,
+        path: P,
+        text: &[(&str, String)],
+    ) -> Result<(), png::EncodingError> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_png_to(&mut out, text)?;
+        Ok(out.flush()?)
+    }
+
+    /// Converts this canvas to an [`image::RgbaImage`].
+    #[cfg(feature = "image")]
+    pub fn to_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(self.width() as u32, self.height() as u32, self.to_rgba8())
+            .expect("buffer matches dimensions")
+    }
+
+    pub(crate) fn from_draw_target(dt: DrawTarget) -> Self {
+        Canvas { dt }
+    }
+
+    pub(crate) fn draw_target_mut(&mut self) -> &mut DrawTarget {
+        &mut self.dt
+    }
+
+    pub(crate) fn into_draw_target(self) -> DrawTarget {
+        self.dt
+    }
+
+    /// Premultiplied pixels in raqote's layout: `0xAARRGGBB` in native byte order.
+    pub(crate) fn data(&self) -> &[u32] {
+        self.dt.get_data()
+    }
+}
+
+impl Clone for Canvas {
+    fn clone(&self) -> Self {
+        Canvas {
+            dt: DrawTarget::from_vec(self.width(), self.height(), self.data().to_vec()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Canvas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Canvas")
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<&Canvas> for image::RgbaImage {
+    fn from(canvas: &Canvas) -> Self {
+        canvas.to_image()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_straight_rgba_access() {
+        let rgba = [
+            0x10, 0x20, 0x30, 0xff, //
+            0x80, 0x40, 0x00, 0x80, //
+            0x00, 0x00, 0x00, 0x00, //
+            0xff, 0xff, 0xff, 0x40, //
+        ];
+        let canvas = Canvas::from_rgba8(2, 2, &rgba).unwrap();
+        assert_eq!(canvas.data()[0], 0xff_10_20_30);
+        assert_eq!(canvas.data()[1], 0x80_40_20_00);
+        assert_eq!(canvas.to_rgba8(), rgba);
+        assert_eq!(canvas.pixel(1, 0), [0x80, 0x40, 0x00, 0x80]);
+        assert_eq!(canvas.pixel(0, 1), [0, 0, 0, 0]);
+        let rows: Vec<Vec<[u8; 4]>> = canvas.rows().map(Iterator::collect).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], [[0, 0, 0, 0], [0xff, 0xff, 0xff, 0x40]]);
+        assert!(Canvas::from_rgba8(2, 2, &rgba[..12]).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+//! A local catalog of seeds, so that pieces can be named by alias (like `qql085`) or by token
+//! number (like `#85`) instead of by hex.
+//!
+//! The catalog is a JSON file like this:
+//!
+//! ```json
+//! {
+//!   "entries": [
+//!     {
+//!       "alias": "qql085",
+//!       "token": 85,
+//!       "seed": "0x33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46",
+//!       "notes": "the one with the spiral",
+//!       "viewports": { "face": "0.25x0.25+0.4+0.3" }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Only `alias` and `seed` are required. Each entry may name preferred viewports, which can be
+//! used in place of a `WxH+X+Y` viewport when rendering that entry.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::FractionalViewport;
+
+/// Environment variable with the path of the catalog file, overriding [`Catalog::default_path`].
+pub const CATALOG_ENV: &str = "QQL_CATALOG";
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Catalog {
+    pub entries: Vec<Entry>,
+}
+
+/// A seed with a name, and optionally a token number, notes, and named viewports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub alias: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<u32>,
+    #[serde(with = "hex_seed")]
+    pub seed: [u8; 32],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub viewports: BTreeMap<String, FractionalViewport>,
+}
+
+impl Catalog {
+    /// Where the catalog is kept: the path in [`CATALOG_ENV`] if set, or else `qql/catalog.json`
+    /// in the user's config directory (`$XDG_CONFIG_HOME`, or `~/.config`). `None` if there's no
+    /// home directory to put it in.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CATALOG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("qql").join("catalog.json"))
+    }
+
+    /// Reads a catalog file. A missing file is an empty catalog.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Catalog::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let catalog: Catalog = serde_json::from_str(&json).context("Invalid catalog")?;
+        for entry in &catalog.entries {
+            validate_alias(&entry.alias)?;
+        }
+        Ok(catalog)
+    }
+
+    /// Writes the catalog file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).expect("catalog is always serializable");
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+
+    /// Finds an entry by alias, or by token number written as `#85` or `85`. Aliases are matched
+    /// case-insensitively.
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        if let Some(entry) = self
+            .entries
+            .iter()
+            .find(|e| e.alias.eq_ignore_ascii_case(name))
+        {
+            return Some(entry);
+        }
+        let token: u32 = name.strip_prefix('#').unwrap_or(name).parse().ok()?;
+        self.entries.iter().find(|e| e.token == Some(token))
+    }
+
+    /// Adds an entry. If another entry has the same alias or token number, this fails unless
+    /// `replace` is set, in which case the other entry is removed.
+    pub fn add(&mut self, entry: Entry, replace: bool) -> anyhow::Result<()> {
+        validate_alias(&entry.alias)?;
+        let conflicts = |e: &Entry| {
+            e.alias.eq_ignore_ascii_case(&entry.alias)
+                || (entry.token.is_some() && e.token == entry.token)
+        };
+        if !replace {
+            if let Some(existing) = self.entries.iter().find(|e| conflicts(e)) {
+                anyhow::bail!(
+                    "Catalog already has {:?}{}",
+                    existing.alias,
+                    existing
+                        .token
+                        .map_or(String::new(), |t| format!(" (#{})", t))
+                );
+            }
+        }
+        self.entries.retain(|e| !conflicts(e));
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Removes the entry with this alias or token number, as for [`Catalog::get`].
+    pub fn remove(&mut self, name: &str) -> Option<Entry> {
+        let alias = self.get(name)?.alias.clone();
+        let index = self.entries.iter().position(|e| e.alias == alias)?;
+        Some(self.entries.remove(index))
+    }
+}
+
+/// Aliases must not be mistaken for token numbers or hex seeds.
+fn validate_alias(alias: &str) -> anyhow::Result<()> {
+    if alias.is_empty() || alias.contains(char::is_whitespace) {
+        anyhow::bail!(
+            "Invalid alias {:?}: must be non-empty, without spaces",
+            alias
+        );
+    }
+    if alias.starts_with('#') || alias.bytes().all(|b| b.is_ascii_digit()) {
+        anyhow::bail!("Invalid alias {:?}: looks like a token number", alias);
+    }
+    if alias.starts_with("0x") || (alias.len() == 64 && hex::decode(alias).is_ok()) {
+        anyhow::bail!("Invalid alias {:?}: looks like a seed", alias);
+    }
+    Ok(())
+}
+
+/// Serializes a seed as `0x`-prefixed hex.
+mod hex_seed {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(seed: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("0x{}", hex::encode(seed)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        use serde::de::Error;
+        let s = String::deserialize(deserializer)?;
+        let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(&s)).map_err(D::Error::custom)?;
+        <[u8; 32]>::try_from(bytes)
+            .map_err(|e| D::Error::custom(format!("Seed must be 32 bytes; got {}", e.len())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hex_literal::hex;
+
+    fn entry(alias: &str, token: Option<u32>) -> Entry {
+        Entry {
+            alias: alias.to_string(),
+            token,
+            seed: hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46"),
+            notes: None,
+            viewports: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_catalog_lookup() {
+        let mut catalog = Catalog::default();
+        catalog.add(entry("qql085", Some(85)), false).unwrap();
+        catalog.add(entry("spiral", None), false).unwrap();
+        assert_eq!(catalog.get("QQL085").unwrap().alias, "qql085");
+        assert_eq!(catalog.get("#85").unwrap().alias, "qql085");
+        assert_eq!(catalog.get("85").unwrap().alias, "qql085");
+        assert_eq!(catalog.get("spiral").unwrap().token, None);
+        assert!(catalog.get("#86").is_none());
+        assert!(catalog.get("nope").is_none());
+
+        assert!(catalog.add(entry("other", Some(85)), false).is_err());
+        assert!(catalog.add(entry("Spiral", None), false).is_err());
+        catalog.add(entry("other", Some(85)), true).unwrap();
+        assert_eq!(catalog.get("#85").unwrap().alias, "other");
+        assert!(catalog.get("qql085").is_none());
+
+        assert_eq!(catalog.remove("#85").unwrap().alias, "other");
+        assert_eq!(catalog.entries.len(), 1);
+
+        for alias in ["", "has space", "#1", "123", "0xabc"] {
+            assert!(
+                catalog.add(entry(alias, None), false).is_err(),
+                "{:?}",
+                alias
+            );
+        }
+    }
+
+    #[test]
+    fn test_catalog_json() {
+        let mut qql085 = entry("qql085", Some(85));
+        qql085.notes = Some("spiral".to_string());
+        qql085.viewports.insert(
+            "face".to_string(),
+            FractionalViewport::from_whlt(0.25, 0.25, 0.4, 0.3),
+        );
+        let catalog = Catalog {
+            entries: vec![qql085, entry("plain", None)],
+        };
+        let json: serde_json::Value = serde_json::to_value(&catalog).unwrap();
+        assert_eq!(
+            json["entries"][0],
+            serde_json::json!({
+                "alias": "qql085",
+                "token": 85,
+                "seed": "0x33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46",
+                "notes": "spiral",
+                "viewports": {"face": "0.25x0.25+0.4+0.3"},
+            })
+        );
+        assert_eq!(
+            json["entries"][1].as_object().unwrap().len(),
+            2,
+            "only alias and seed"
+        );
+        let parsed: Catalog = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, catalog);
+    }
+}
@@ -89,6 +89,23 @@ pub enum WireFormatError {
     UndefinedColor { name: String, palette: String },
 }
 
+impl std::fmt::Display for WireFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireFormatError::TooManyColors => f.write_str("Too many colors"),
+            WireFormatError::DuplicateColor { name } => write!(f, "Duplicate color {:?}", name),
+            WireFormatError::DuplicatePalette { name } => {
+                write!(f, "Duplicate palette {:?}", name)
+            }
+            WireFormatError::UndefinedColor { name, palette } => {
+                write!(f, "Palette {:?} uses undefined color {:?}", palette, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WireFormatError {}
+
 impl ColorDb {
     pub fn from_bundle() -> Self {
         let wire: WireColorDb =
@@ -193,7 +210,11 @@ impl ColorDb {
     }
 
     pub fn color_by_name(&self, name: &str) -> Option<&ColorSpec> {
-        self.color(*self.colors_by_name.get(name)?)
+        self.color(self.color_key(name)?)
+    }
+
+    pub fn color_key(&self, name: &str) -> Option<ColorKey> {
+        self.colors_by_name.get(name).copied()
     }
 
     pub fn palette(&self, palette_key: crate::traits::ColorPalette) -> Option<&PaletteSpec> {
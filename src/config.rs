@@ -1,57 +1,323 @@
 use std::{fmt::Display, num::NonZeroU32, str::FromStr};
 
 use anyhow::Context;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Default, clap::Args)]
-pub struct Config {
+use crate::traits::{serde_via_display, Version};
+
+/// Options for laying out and painting a piece. The defaults render pieces canonically.
+///
+/// Build one with [`RenderConfig::builder`] to have it validated, or set its fields directly and
+/// call [`RenderConfig::validate`]. Configs (de)serialize as JSON objects whose keys are the field
+/// names; missing keys take their default values. Deserializing validates the config.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+// Derive the (de)serialization as inherent functions, so that `Deserialize` can validate.
+#[serde(remote = "Self", default, deny_unknown_fields)]
+pub struct RenderConfig {
     /// Speed up collision checking by avoiding our slow `sqrt` implementation. May slightly
     /// affect layout.
-    #[clap(long)]
     pub fast_collisions: bool,
 
     /// At paint time, ensure that all points have at least a small positive radius.
-    #[clap(long)]
     pub inflate_draw_radius: bool,
 
+    /// Lay out the piece as if its seed had this version, instead of the version in its trait
+    /// bits. The output is not canonical.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_version: Option<Version>,
+
     /// Use at least this many segments for every circle. Values below `8` have no effect.
-    ///
-    /// At typical resolutions, circles should look smooth without tweaking. But at very large
-    /// resolutions (say, above 10k pixels wide), the segments may start to become visible,
-    /// especially on small circles. Crank this value up linearly to compensate, at the cost of
-    /// render time.
-    #[clap(long, value_name = "STEPS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub min_circle_steps: Option<u32>,
 
     /// Restrict rendering to a region of the canvas.
-    ///
-    /// Values are specified as floats from 0.0 (top/left) to 1.0 (bottom/right). For instance,
-    /// `0.1x0.1+0.45+0.45` renders the center 1% of the canvas. See `--width` about how this
-    /// affects the output image size.
-    #[clap(long, value_name = "WxH+X+Y")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub viewport: Option<FractionalViewport>,
 
     /// Chunks for parallel rendering.
-    #[clap(long, value_name = "WxH", default_value_t)]
     pub chunks: Chunks,
 
     /// Output multiple frames showing the construction of the piece.
-    ///
-    /// May be `none` for no animation, `groups` to paint one flow line group at a time, or
-    /// `points:N` (where `N` is a positive integer) to show paint `N` points at a time.
-    #[clap(long, default_value_t)]
     pub animate: Animation,
 
-    /// Animate in splatter points immediately after their parents.
-    ///
-    /// By default, all splatters are deferred to the end of the animation. With this option,
-    /// each splatter point is instead drawn immediately after the point that spawned it. This
-    /// takes additional processing and may increase render time. Can only be used if `--animate`
-    /// is also set.
-    #[clap(long, default_value_t)]
+    /// Animate in splatter points immediately after their parents, instead of deferring them to
+    /// the end of the animation. Only valid with an `animate` setting other than `none`.
     pub splatter_immediately: bool,
 }
 
-#[derive(Default, Debug, Clone)]
+impl Serialize for RenderConfig {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RenderConfig::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RenderConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = RenderConfig::deserialize(deserializer)?;
+        config.validate().map_err(serde::de::Error::custom)?;
+        Ok(config)
+    }
+}
+
+/// Why a [`RenderConfig`] is invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The viewport has a width or height that isn't positive.
+    EmptyViewport(FractionalViewport),
+    /// The viewport extends past the edges of the canvas.
+    ViewportOutOfBounds(FractionalViewport),
+    /// Animating `points:0` would never finish.
+    ZeroPointsPerFrame,
+    /// `splatter_immediately` is set without an animation.
+    SplatterWithoutAnimation,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::EmptyViewport(viewport) => {
+                write!(f, "Viewport {} must have a positive size", viewport)
+            }
+            ConfigError::ViewportOutOfBounds(viewport) => {
+                write!(f, "Viewport {} must lie within 0.0 and 1.0", viewport)
+            }
+            ConfigError::ZeroPointsPerFrame => f.write_str("Must add at least 1 point per frame"),
+            ConfigError::SplatterWithoutAnimation => {
+                f.write_str("splatter_immediately does not apply unless animate is also set")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl RenderConfig {
+    pub fn builder() -> RenderConfigBuilder {
+        RenderConfigBuilder::default()
+    }
+
+    /// Checks that the options make sense together: the viewport is a non-empty region of the
+    /// canvas, animations make progress, and animation-only options are only set when animating.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(viewport) = &self.viewport {
+            // Allow for rounding when a viewport is computed, like the last tile of a grid.
+            const SLACK: f64 = 1e-9;
+            if !(viewport.width > 0.0 && viewport.height > 0.0) {
+                return Err(ConfigError::EmptyViewport(viewport.clone()));
+            }
+            let in_bounds = |start: f64, end: f64| start >= -SLACK && end <= 1.0 + SLACK;
+            if !in_bounds(viewport.left, viewport.right())
+                || !in_bounds(viewport.top, viewport.bottom())
+            {
+                return Err(ConfigError::ViewportOutOfBounds(viewport.clone()));
+            }
+        }
+        match self.animate {
+            Animation::Points { step: 0 } => return Err(ConfigError::ZeroPointsPerFrame),
+            Animation::None if self.splatter_immediately => {
+                return Err(ConfigError::SplatterWithoutAnimation)
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Whether this config renders pieces exactly as the original algorithm does. Options that
+    /// change the output, like `fast_collisions`, `inflate_draw_radius`, `force_version`, and a
+    /// `min_circle_steps` above the default of 8, make a config non-canonical; options that only
+    /// affect performance or framing don't.
+    pub fn is_canonical(&self) -> bool {
+        !self.fast_collisions
+            && !self.inflate_draw_radius
+            && self.force_version.is_none()
+            && self.min_circle_steps.is_none_or(|steps| steps <= 8)
+    }
+
+    /// Command-line arguments that reproduce this config, omitting defaults. Each argument is a
+    /// single `--flag` or `--flag=value` word. See [`RenderConfig::from_args`].
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.fast_collisions {
+            args.push("--fast-collisions".to_string());
+        }
+        if self.inflate_draw_radius {
+            args.push("--inflate-draw-radius".to_string());
+        }
+        if let Some(version) = self.force_version {
+            args.push(format!(
+                "--force-version={}",
+                version.to_string().to_lowercase()
+            ));
+        }
+        if let Some(steps) = self.min_circle_steps {
+            args.push(format!("--min-circle-steps={}", steps));
+        }
+        if let Some(viewport) = &self.viewport {
+            args.push(format!("--viewport={}", viewport));
+        }
+        if self.chunks != Chunks::default() {
+            args.push(format!("--chunks={}", self.chunks));
+        }
+        if !matches!(self.animate, Animation::None) {
+            args.push(format!("--animate={}", self.animate));
+        }
+        if self.splatter_immediately {
+            args.push("--splatter-immediately".to_string());
+        }
+        args
+    }
+
+    /// Parses and validates arguments as written by [`RenderConfig::to_args`]: each one a single
+    /// `--flag` or `--flag=value` word.
+    pub fn from_args<I, T>(args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut builder = RenderConfig::builder();
+        for arg in args {
+            let arg = arg.as_ref();
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg, None),
+            };
+            let name = name
+                .strip_prefix("--")
+                .with_context(|| format!("Expected a --flag; got {:?}", arg))?;
+            let no_value = || match value {
+                Some(_) => anyhow::bail!("--{} does not take a value", name),
+                None => Ok(true),
+            };
+            let value = || value.with_context(|| format!("--{} requires a value", name));
+            let invalid = || format!("Invalid value for --{}", name);
+            builder = match name {
+                "fast-collisions" => builder.fast_collisions(no_value()?),
+                "inflate-draw-radius" => builder.inflate_draw_radius(no_value()?),
+                "force-version" => builder.force_version(value()?.parse().with_context(invalid)?),
+                "min-circle-steps" => {
+                    builder.min_circle_steps(value()?.parse().with_context(invalid)?)
+                }
+                "viewport" => builder.viewport(value()?.parse().with_context(invalid)?),
+                "chunks" => builder.chunks(value()?.parse().with_context(invalid)?),
+                "animate" => builder.animate(value()?.parse().with_context(invalid)?),
+                "splatter-immediately" => builder.splatter_immediately(no_value()?),
+                _ => anyhow::bail!("Unknown option --{}", name),
+            };
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// Builds a [`RenderConfig`] from the defaults, one option at a time. See [`RenderConfig`] for
+/// what each option means.
+#[derive(Debug, Default, Clone)]
+pub struct RenderConfigBuilder {
+    config: RenderConfig,
+}
+
+impl RenderConfigBuilder {
+    pub fn fast_collisions(mut self, fast_collisions: bool) -> Self {
+        self.config.fast_collisions = fast_collisions;
+        self
+    }
+
+    pub fn inflate_draw_radius(mut self, inflate_draw_radius: bool) -> Self {
+        self.config.inflate_draw_radius = inflate_draw_radius;
+        self
+    }
+
+    pub fn force_version(mut self, version: Version) -> Self {
+        self.config.force_version = Some(version);
+        self
+    }
+
+    pub fn min_circle_steps(mut self, steps: u32) -> Self {
+        self.config.min_circle_steps = Some(steps);
+        self
+    }
+
+    pub fn viewport(mut self, viewport: FractionalViewport) -> Self {
+        self.config.viewport = Some(viewport);
+        self
+    }
+
+    pub fn chunks(mut self, chunks: Chunks) -> Self {
+        self.config.chunks = chunks;
+        self
+    }
+
+    pub fn animate(mut self, animate: Animation) -> Self {
+        self.config.animate = animate;
+        self
+    }
+
+    pub fn splatter_immediately(mut self, splatter_immediately: bool) -> Self {
+        self.config.splatter_immediately = splatter_immediately;
+        self
+    }
+
+    /// The config, if it passes [`RenderConfig::validate`].
+    pub fn build(self) -> Result<RenderConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// The options of a [`RenderConfig`] that affect layout, for [`crate::art::Layout::from_seed`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LayoutOptions {
+    pub fast_collisions: bool,
+    pub inflate_draw_radius: bool,
+    pub force_version: Option<Version>,
+}
+
+/// The options of a [`RenderConfig`] that only affect painting, for [`crate::art::Layout::render`].
+/// Any number of renders with different options can share one layout.
+#[derive(Debug, Default, Clone)]
+pub struct RenderOptions {
+    pub min_circle_steps: Option<u32>,
+    pub viewport: Option<FractionalViewport>,
+    pub chunks: Chunks,
+    pub animate: Animation,
+    pub splatter_immediately: bool,
+}
+
+impl RenderConfig {
+    pub fn layout_options(&self) -> LayoutOptions {
+        LayoutOptions {
+            fast_collisions: self.fast_collisions,
+            inflate_draw_radius: self.inflate_draw_radius,
+            force_version: self.force_version,
+        }
+    }
+
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            min_circle_steps: self.min_circle_steps,
+            viewport: self.viewport.clone(),
+            chunks: self.chunks.clone(),
+            animate: self.animate.clone(),
+            splatter_immediately: self.splatter_immediately,
+        }
+    }
+
+    /// The config with both sets of options; the inverse of [`RenderConfig::layout_options`] and
+    /// [`RenderConfig::render_options`].
+    pub fn from_options(layout: &LayoutOptions, render: &RenderOptions) -> Self {
+        RenderConfig {
+            fast_collisions: layout.fast_collisions,
+            inflate_draw_radius: layout.inflate_draw_radius,
+            force_version: layout.force_version,
+            min_circle_steps: render.min_circle_steps,
+            viewport: render.viewport.clone(),
+            chunks: render.chunks.clone(),
+            animate: render.animate.clone(),
+            splatter_immediately: render.splatter_immediately,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
 pub enum Animation {
     #[default]
     None,
@@ -95,6 +361,9 @@ impl Display for Animation {
     }
 }
 
+// Serializes as a string like `points:100`.
+serde_via_display!(Animation);
+
 /// A viewport/crop specification in fractional space, where both axes range from `0.0` to `1.0`.
 #[derive(Debug, PartialEq, Clone)]
 pub struct FractionalViewport {
@@ -165,6 +434,20 @@ impl FromStr for FractionalViewport {
     }
 }
 
+/// Formats as `WxH+X+Y`, the same syntax accepted by [`FromStr`].
+impl Display for FractionalViewport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{}+{}+{}",
+            self.width, self.height, self.left, self.top
+        )
+    }
+}
+
+// Serializes as a `WxH+X+Y` string.
+serde_via_display!(FractionalViewport);
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Chunks {
     pub w: NonZeroU32,
@@ -197,6 +480,9 @@ impl Display for Chunks {
     }
 }
 
+// Serializes as a `WxH` string.
+serde_via_display!(Chunks);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -226,4 +512,121 @@ mod tests {
         check("100x200+BAZ+60", "Invalid x-offset");
         check("100x200+30+QUUX", "Invalid y-offset");
     }
+
+    #[test]
+    fn test_config_args_roundtrip() {
+        assert!(RenderConfig::default().to_args().is_empty());
+        let config = RenderConfig {
+            fast_collisions: true,
+            inflate_draw_radius: false,
+            force_version: Some(Version::V1),
+            min_circle_steps: Some(32),
+            viewport: Some(FractionalViewport::from_whlt(0.1, 1.0 / 3.0, 0.45, 0.5)),
+            chunks: "2x3".parse().unwrap(),
+            animate: Animation::Points { step: 100 },
+            splatter_immediately: true,
+        };
+        let args = config.to_args();
+        assert_eq!(
+            args,
+            vec![
+                "--fast-collisions",
+                "--force-version=v1",
+                "--min-circle-steps=32",
+                "--viewport=0.1x0.3333333333333333+0.45+0.5",
+                "--chunks=2x3",
+                "--animate=points:100",
+                "--splatter-immediately",
+            ]
+        );
+        let parsed = RenderConfig::from_args(&args).unwrap();
+        assert_eq!(parsed.to_args(), args);
+        assert_eq!(parsed.viewport, config.viewport);
+        assert_eq!(parsed.force_version, Some(Version::V1));
+        assert!(!parsed.is_canonical());
+        assert!(RenderConfig::from_args(["--min-circle-steps=8"])
+            .unwrap()
+            .is_canonical());
+        assert!(!RenderConfig::from_args(["--min-circle-steps=9"])
+            .unwrap()
+            .is_canonical());
+        assert!(RenderConfig::from_args(["--no-such-flag"]).is_err());
+        assert!(RenderConfig::from_args(["--fast-collisions=yes"]).is_err());
+        assert!(RenderConfig::from_args(["--chunks"]).is_err());
+        assert!(RenderConfig::from_args(["--splatter-immediately"]).is_err());
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let viewport = |s: &str| RenderConfig::builder().viewport(s.parse().unwrap()).build();
+        assert!(viewport("0.5x0.5+0.25+0.25").is_ok());
+        assert!(viewport("1x1+0+0").is_ok());
+        assert!(matches!(
+            viewport("0x0.5+0.25+0.25"),
+            Err(ConfigError::EmptyViewport(_))
+        ));
+        assert!(matches!(
+            viewport("NaNx0.5+0.25+0.25"),
+            Err(ConfigError::EmptyViewport(_))
+        ));
+        assert!(matches!(
+            viewport("0.5x0.5+0.75+0.25"),
+            Err(ConfigError::ViewportOutOfBounds(_))
+        ));
+        assert!(matches!(
+            viewport("0.5x0.5+0.25+-0.1"),
+            Err(ConfigError::ViewportOutOfBounds(_))
+        ));
+
+        let config = RenderConfig {
+            animate: Animation::Points { step: 0 },
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Err(ConfigError::ZeroPointsPerFrame));
+        let builder = RenderConfig::builder().splatter_immediately(true);
+        assert_eq!(
+            builder.clone().build(),
+            Err(ConfigError::SplatterWithoutAnimation)
+        );
+        assert!(builder.animate(Animation::Groups).build().is_ok());
+    }
+
+    #[test]
+    fn test_config_json() {
+        assert_eq!(
+            serde_json::to_value(RenderConfig::default()).unwrap(),
+            serde_json::json!({
+                "fast_collisions": false,
+                "inflate_draw_radius": false,
+                "chunks": "1x1",
+                "animate": "none",
+                "splatter_immediately": false,
+            })
+        );
+        let config = RenderConfig::builder()
+            .force_version(Version::V0)
+            .viewport("0.5x0.5+0.25+0.25".parse().unwrap())
+            .chunks("2x2".parse().unwrap())
+            .animate(Animation::Points { step: 100 })
+            .build()
+            .unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(serde_json::from_str::<RenderConfig>(&json).unwrap(), config);
+
+        let parsed: RenderConfig = serde_json::from_str(r#"{"animate": "groups"}"#).unwrap();
+        assert_eq!(parsed.animate, Animation::Groups);
+        assert_eq!(parsed.chunks, Chunks::default());
+        assert!(serde_json::from_str::<RenderConfig>(r#"{"animation": "groups"}"#).is_err());
+
+        // Deserialized configs are validated like built ones.
+        let invalid = [
+            (r#"{"viewport": "0x1+0+0"}"#, "positive size"),
+            (r#"{"viewport": "1x1+0.5+0"}"#, "within 0.0 and 1.0"),
+            (r#"{"splatter_immediately": true}"#, "unless animate"),
+        ];
+        for (json, reason) in invalid {
+            let err = serde_json::from_str::<RenderConfig>(json).unwrap_err();
+            assert!(err.to_string().contains(reason), "{}: {}", json, err);
+        }
+    }
 }
@@ -0,0 +1,64 @@
+//! Errors from laying out and painting pieces, as returned by the `try_` functions in
+//! [`crate::art`].
+
+use crate::color::{ColorKey, WireFormatError};
+use crate::config::ConfigError;
+use crate::traits::ColorPalette;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A [`crate::config::RenderConfig`] whose options don't make sense together, as when one is
+    /// built by hand instead of with its builder.
+    Config(ConfigError),
+    /// The color database has no palette for a piece's [`ColorPalette`] trait.
+    MissingPalette(ColorPalette),
+    /// A color key that isn't in the color database, as when a layout built with one database is
+    /// painted with another.
+    InvalidColor(ColorKey),
+    /// The `chunks` grid has more cells than this platform can count.
+    TooManyChunks { w: u32, h: u32 },
+    /// Chunks painted in parallel ended up with different RNG states, so they can't be composited
+    /// into one consistent image. This is a bug.
+    ChunkMismatch,
+    /// A color database that isn't well-formed.
+    ColorDb(WireFormatError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Config(e) => write!(f, "Invalid config: {}", e),
+            Error::MissingPalette(palette) => {
+                write!(f, "Missing color data for palette {:?}", palette)
+            }
+            Error::InvalidColor(key) => write!(f, "Color {} is not in the color database", key),
+            Error::TooManyChunks { w, h } => write!(f, "Too many chunks: {}x{}", w, h),
+            Error::ChunkMismatch => f.write_str("Chunks disagree on RNG state"),
+            Error::ColorDb(e) => write!(f, "Invalid color database: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(e) => Some(e),
+            Error::ColorDb(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<WireFormatError> for Error {
+    fn from(e: WireFormatError) -> Self {
+        Error::ColorDb(e)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
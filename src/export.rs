@@ -0,0 +1,174 @@
+//! Output formats other than the raster canvas produced by [`crate::art::draw`].
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::Context;
+
+use crate::art::Recording;
+
+pub mod animation;
+pub mod contact_sheet;
+pub mod ora;
+pub mod pdf;
+pub mod plot;
+pub mod png;
+pub mod pyramid;
+pub mod stream;
+pub mod svg;
+pub mod tiff;
+
+/// PostScript points per inch, the native unit of PDF user space.
+pub const POINTS_PER_INCH: f64 = 72.0;
+
+/// A physical page size, stored in PostScript points.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PageSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PageSize {
+    /// A page whose size in points equals the given size in pixels, i.e., 72 pixels per inch.
+    pub fn from_pixels((width, height): (i32, i32)) -> Self {
+        PageSize {
+            width: f64::from(width),
+            height: f64::from(height),
+        }
+    }
+}
+
+/// Expects a string like `24x30in`. Supported units are `in`, `cm`, `mm`, and `pt`.
+impl FromStr for PageSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const UNITS: &[(&str, f64)] = &[
+            ("in", POINTS_PER_INCH),
+            ("cm", POINTS_PER_INCH / 2.54),
+            ("mm", POINTS_PER_INCH / 25.4),
+            ("pt", 1.0),
+        ];
+        let (s, multiplier) = UNITS
+            .iter()
+            .find_map(|&(suffix, m)| Some((s.strip_suffix(suffix)?, m)))
+            .context("Missing unit; expected one of in, cm, mm, pt")?;
+        let (width, height) = s.split_once('x').context("Invalid format; expected WxH")?;
+        let width: f64 = width.parse().context("Invalid width")?;
+        let height: f64 = height.parse().context("Invalid height")?;
+        if !(width > 0.0 && height > 0.0 && width.is_finite() && height.is_finite()) {
+            anyhow::bail!("Page dimensions must be positive");
+        }
+        Ok(PageSize {
+            width: width * multiplier,
+            height: height * multiplier,
+        })
+    }
+}
+
+impl Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}pt", self.width, self.height)
+    }
+}
+
+/// Where a recording's viewport lands on a page: scaled to fit, preserving aspect ratio, and
+/// centered. All values are in page units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Placement {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Page units per unit of virtual canvas space.
+    pub scale: f64,
+}
+
+impl Placement {
+    pub fn fit(recording: &Recording, page: &PageSize) -> Self {
+        // Work in raster space at unit width, where the full canvas is 1 by 1.25, per its 4:5
+        // aspect ratio.
+        let (vp_width, vp_height) = (
+            recording.viewport.width(),
+            recording.viewport.height() * 1.25,
+        );
+        let fit = f64::min(page.width / vp_width, page.height / vp_height);
+        let (width, height) = (vp_width * fit, vp_height * fit);
+        Placement {
+            left: (page.width - width) / 2.0,
+            top: (page.height - height) / 2.0,
+            width,
+            height,
+            scale: recording.scale_ratio(1) * fit,
+        }
+    }
+
+    /// Maps a point relative to the recording's origin (as given by [`crate::art::Stroke::vertices`])
+    /// to page coordinates, measured from the top-left corner of the page.
+    pub fn map(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (self.left + x * self.scale, self.top + y * self.scale)
+    }
+}
+
+/// Converts a premultiplied raqote pixel to straight (non-premultiplied) `[r, g, b, a]` bytes.
+pub(crate) fn straight_rgba(px: u32) -> [u8; 4] {
+    let [a, r, g, b] = px.to_be_bytes();
+    let unmul = |c: u8| match a {
+        0 => 0,
+        255 => c,
+        _ => ((u32::from(c) * 255 + u32::from(a) / 2) / u32::from(a)).min(255) as u8,
+    };
+    [unmul(r), unmul(g), unmul(b), a]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_page_size_fromstr() {
+        assert_eq!(
+            "24x30in".parse::<PageSize>().unwrap(),
+            PageSize {
+                width: 1728.0,
+                height: 2160.0
+            }
+        );
+        let page: PageSize = "254x508mm".parse().unwrap();
+        assert!((page.width - 720.0).abs() < 1e-9);
+        assert!((page.height - 1440.0).abs() < 1e-9);
+        fn check_err(input: &str, expected_err: &str) {
+            let msg = input.parse::<PageSize>().unwrap_err().to_string();
+            assert_eq!(msg, expected_err);
+        }
+        check_err("24x30", "Missing unit; expected one of in, cm, mm, pt");
+        check_err("24in", "Invalid format; expected WxH");
+        check_err("AxBin", "Invalid width");
+        check_err("0x30in", "Page dimensions must be positive");
+    }
+
+    #[test]
+    fn test_placement_fit() {
+        let recording = Recording {
+            background: crate::art::Hsb(0.0, 0.0, 0.0),
+            viewport: crate::config::FractionalViewport::default(),
+            strokes: Vec::new(),
+        };
+        let page = PageSize {
+            width: 100.0,
+            height: 100.0,
+        };
+        let placement = Placement::fit(&recording, &page);
+        assert_eq!(
+            placement,
+            Placement {
+                left: 10.0,
+                top: 0.0,
+                width: 80.0,
+                height: 100.0,
+                scale: 0.04,
+            }
+        );
+        assert_eq!(placement.map((2000.0, 2500.0)), (90.0, 100.0));
+    }
+}
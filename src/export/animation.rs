@@ -0,0 +1,753 @@
+//! Animated APNG and GIF output, encoded straight from the frames of an animated render.
+//!
+//! Frames of an animation differ only where new points were painted, so each frame after the
+//! first is stored as just the bounding box of its changes, with unchanged pixels transparent.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use super::straight_rgba;
+use crate::art::{canvas_dimensions, ColorScheme, ColorsUsed, Hsb, Layout, RenderData};
+use crate::canvas::Canvas;
+use crate::color::{ColorDb, ColorKey};
+use crate::config::RenderOptions;
+use crate::progress::{Event, Observer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Apng,
+    Gif,
+}
+
+/// Options for an [`AnimationEncoder`].
+#[derive(Debug, Clone)]
+pub struct AnimationOptions {
+    /// Frames per second, counting every frame pushed to the encoder.
+    pub fps: f64,
+    /// Number of times to play the animation, or 0 to loop forever.
+    pub plays: u16,
+    /// How long to show the last frame before the animation ends or loops.
+    pub hold: Duration,
+    /// Keep at most one of every `frame_step` frames, showing each kept frame for the duration of
+    /// the frames dropped after it. The last frame is always kept.
+    pub frame_step: u32,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        AnimationOptions {
+            fps: 30.0,
+            plays: 0,
+            hold: Duration::from_secs(2),
+            frame_step: 1,
+        }
+    }
+}
+
+/// Palette index reserved for transparent pixels in GIF frames.
+const TRANSPARENT: u8 = 255;
+
+/// Builds a GIF palette for a piece, with up to 255 colors, leaving index 255 for transparency.
+///
+/// Colors come from the background, then `colors_used`, then the rest of the color scheme, since
+/// (as in the original algorithm) `ColorsUsed` doesn't record colors that a flow line group
+/// switches to partway through. Each color contributes, in decreasing order of priority, its base
+/// value, its antialiased blend with the background, the extremes of its brightness and
+/// saturation ranges, lighter and heavier blends with the background, and the extremes of its hue
+/// range. Lower-priority entries are dropped once the palette is full.
+pub fn gif_palette(
+    color_db: &ColorDb,
+    color_scheme: &ColorScheme,
+    colors_used: &ColorsUsed,
+) -> Vec<[u8; 3]> {
+    let mut keys: Vec<ColorKey> = Vec::new();
+    for key in colors_used
+        .iter()
+        .chain(color_scheme.primary_seq.iter().copied())
+        .chain(color_scheme.secondary_seq.iter().copied())
+        .chain(color_scheme.splatter_choices.iter().copied())
+    {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    let specs: Vec<_> = keys.iter().filter_map(|&k| color_db.color(k)).collect();
+    let to_bytes = |c: Hsb| {
+        let rgb = c.to_rgb();
+        [rgb.0 as u8, rgb.1 as u8, rgb.2 as u8]
+    };
+    let background = color_db
+        .color(color_scheme.background)
+        .map_or([255; 3], |spec| {
+            to_bytes(Hsb(spec.hue, spec.sat, spec.bright))
+        });
+    let base: Vec<[u8; 3]> = specs
+        .iter()
+        .map(|spec| to_bytes(Hsb(spec.hue, spec.sat, spec.bright)))
+        .collect();
+    let blend = |c: [u8; 3], t: f64| {
+        [0, 1, 2]
+            .map(|i| (f64::from(c[i]) * t + f64::from(background[i]) * (1.0 - t)).round() as u8)
+    };
+
+    let mut candidates = vec![background];
+    candidates.extend(&base);
+    candidates.extend(base.iter().map(|&c| blend(c, 0.5)));
+    for spec in &specs {
+        candidates.push(to_bytes(Hsb(spec.hue, spec.sat, spec.bright_min)));
+        candidates.push(to_bytes(Hsb(spec.hue, spec.sat, spec.bright_max)));
+    }
+    for spec in &specs {
+        candidates.push(to_bytes(Hsb(spec.hue, spec.sat_min, spec.bright)));
+        candidates.push(to_bytes(Hsb(spec.hue, spec.sat_max, spec.bright)));
+    }
+    for &c in &base {
+        candidates.push(blend(c, 0.25));
+        candidates.push(blend(c, 0.75));
+    }
+    for spec in &specs {
+        candidates.push(to_bytes(Hsb(spec.hue_min, spec.sat, spec.bright)));
+        candidates.push(to_bytes(Hsb(spec.hue_max, spec.sat, spec.bright)));
+    }
+
+    let mut palette: Vec<[u8; 3]> = Vec::with_capacity(usize::from(TRANSPARENT));
+    for c in candidates {
+        if palette.len() == usize::from(TRANSPARENT) {
+            break;
+        }
+        if !palette.contains(&c) {
+            palette.push(c);
+        }
+    }
+    palette
+}
+
+/// Maps colors to the nearest entry of a fixed palette, caching results since animation frames
+/// repeat the same colors heavily.
+struct Quantizer {
+    palette: Vec<[u8; 3]>,
+    cache: HashMap<[u8; 3], u8>,
+}
+
+impl Quantizer {
+    fn index(&mut self, c: [u8; 3]) -> u8 {
+        let palette = &self.palette;
+        *self.cache.entry(c).or_insert_with(|| {
+            let dist2 = |p: &[u8; 3]| -> i32 {
+                (0..3)
+                    .map(|i| (i32::from(c[i]) - i32::from(p[i])).pow(2))
+                    .sum()
+            };
+            (0..palette.len())
+                .min_by_key(|&i| dist2(&palette[i]))
+                .expect("empty palette") as u8
+        })
+    }
+}
+
+enum Sink<W: Write + Seek> {
+    Apng(ApngWriter<W>),
+    Gif {
+        encoder: gif::Encoder<W>,
+        quantizer: Quantizer,
+    },
+}
+
+/// Encodes a stream of frames, like those passed to the callback of [`crate::art::draw`], as an
+/// APNG or GIF.
+///
+/// Each frame is written once the next one arrives, so that the last frame can be held for
+/// [`AnimationOptions::hold`]. Frames identical to the previous one just extend its duration.
+pub struct AnimationEncoder<W: Write + Seek> {
+    sink: Sink<W>,
+    opts: AnimationOptions,
+    width: i32,
+    height: i32,
+    /// Pixels of the last frame written, or empty before the first.
+    shown: Vec<u32>,
+    /// The frame to write next, and the index of the input frame at which it starts showing.
+    pending: Option<(u32, Vec<u32>)>,
+    /// The latest frame dropped since `pending`, if any, which is written if it turns out to be
+    /// the last frame.
+    skipped: Option<(u32, Vec<u32>)>,
+    num_pushed: u32,
+    num_written: usize,
+}
+
+impl<W: Write + Seek> AnimationEncoder<W> {
+    pub fn apng(out: W, width: i32, height: i32, opts: &AnimationOptions) -> io::Result<Self> {
+        let sink = Sink::Apng(ApngWriter::new(out, width, height, opts.plays)?);
+        Ok(Self::with_sink(sink, width, height, opts))
+    }
+
+    /// Creates a GIF encoder with the given global palette, which must have at most 255 colors.
+    /// See [`gif_palette`].
+    pub fn gif(
+        out: W,
+        width: i32,
+        height: i32,
+        palette: Vec<[u8; 3]>,
+        opts: &AnimationOptions,
+    ) -> io::Result<Self> {
+        assert!(
+            !palette.is_empty() && palette.len() <= usize::from(TRANSPARENT),
+            "GIF palette must have 1 to 255 colors"
+        );
+        let too_large = || io::Error::other("GIF dimensions are limited to 65535 pixels");
+        let (w, h) = (
+            u16::try_from(width).map_err(|_| too_large())?,
+            u16::try_from(height).map_err(|_| too_large())?,
+        );
+        let mut global_palette: Vec<u8> = palette.iter().flatten().copied().collect();
+        global_palette.resize(256 * 3, 0);
+        let mut encoder = gif::Encoder::new(out, w, h, &global_palette).map_err(gif_error)?;
+        let repeat = match opts.plays {
+            0 => gif::Repeat::Infinite,
+            n => gif::Repeat::Finite(n - 1),
+        };
+        if repeat != gif::Repeat::Finite(0) {
+            encoder.set_repeat(repeat).map_err(gif_error)?;
+        }
+        let quantizer = Quantizer {
+            palette,
+            cache: HashMap::new(),
+        };
+        Ok(Self::with_sink(
+            Sink::Gif { encoder, quantizer },
+            width,
+            height,
+            opts,
+        ))
+    }
+
+    fn with_sink(sink: Sink<W>, width: i32, height: i32, opts: &AnimationOptions) -> Self {
+        AnimationEncoder {
+            sink,
+            opts: opts.clone(),
+            width,
+            height,
+            shown: Vec::new(),
+            pending: None,
+            skipped: None,
+            num_pushed: 0,
+            num_written: 0,
+        }
+    }
+
+    /// Number of frames in the output so far, including any that [`AnimationEncoder::finish`]
+    /// has yet to write.
+    pub fn num_frames(&self) -> usize {
+        self.num_written + usize::from(self.pending.is_some()) + usize::from(self.skipped.is_some())
+    }
+
+    pub fn push(&mut self, canvas: &Canvas) -> io::Result<()> {
+        assert_eq!(
+            (canvas.width(), canvas.height()),
+            (self.width, self.height),
+            "frame dimensions changed"
+        );
+        let index = self.num_pushed;
+        self.num_pushed += 1;
+        let data = canvas.data();
+
+        let Some((start, _)) = &self.pending else {
+            self.pending = Some((index, data.to_vec()));
+            return Ok(());
+        };
+        let start = *start;
+        let latest = self.skipped.as_ref().or(self.pending.as_ref());
+        if latest.is_some_and(|(_, pixels)| pixels == data) {
+            return Ok(());
+        }
+        let delay = self.ticks(index) - self.ticks(start);
+        if index - start < self.opts.frame_step.max(1) || delay < self.min_delay() {
+            match &mut self.skipped {
+                Some((i, pixels)) => {
+                    *i = index;
+                    pixels.copy_from_slice(data);
+                }
+                None => self.skipped = Some((index, data.to_vec())),
+            }
+            return Ok(());
+        }
+        let (_, pixels) = self.pending.take().expect("no pending frame");
+        self.write_frame(pixels, delay)?;
+        // Reuse the buffer of the frame being superseded.
+        let mut buf = std::mem::take(&mut self.skipped).map_or_else(Vec::new, |(_, buf)| buf);
+        buf.clear();
+        buf.extend_from_slice(data);
+        self.pending = Some((index, buf));
+        Ok(())
+    }
+
+    /// Writes any remaining frames, holding the last one, and finishes the file.
+    pub fn finish(mut self) -> io::Result<W> {
+        let (start, pixels) = self
+            .pending
+            .take()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no frames to encode"))?;
+        let pixels = match self.skipped.take() {
+            None => pixels,
+            Some((index, last)) => {
+                let delay = self.ticks(index) - self.ticks(start);
+                self.write_frame(pixels, delay)?;
+                last
+            }
+        };
+        let hold = (self.opts.hold.as_secs_f64() * self.ticks_per_second()).round() as u64;
+        self.write_frame(pixels, hold.max(self.min_delay()))?;
+        match self.sink {
+            Sink::Apng(apng) => apng.finish(),
+            Sink::Gif { encoder, .. } => encoder.into_inner(),
+        }
+    }
+
+    /// Frame delays are in centiseconds for GIF, and in milliseconds for APNG.
+    fn ticks_per_second(&self) -> f64 {
+        match self.sink {
+            Sink::Apng(_) => 1000.0,
+            Sink::Gif { .. } => 100.0,
+        }
+    }
+
+    /// The shortest delay to use between frames. Browsers show GIF frames with delays under 2
+    /// centiseconds for 10 centiseconds instead, so shorter frames are merged.
+    fn min_delay(&self) -> u64 {
+        match self.sink {
+            Sink::Apng(_) => 1,
+            Sink::Gif { .. } => 2,
+        }
+    }
+
+    /// Time at which the input frame with the given index starts, in ticks. Rounding each
+    /// timestamp, rather than each delay, keeps the overall frame rate exact.
+    fn ticks(&self, index: u32) -> u64 {
+        (f64::from(index) * self.ticks_per_second() / self.opts.fps).round() as u64
+    }
+
+    fn write_frame(&mut self, pixels: Vec<u32>, delay: u64) -> io::Result<()> {
+        let delay = u16::try_from(delay).unwrap_or(u16::MAX);
+        let width = self.width as usize;
+        let (x, y, w, h) = if self.shown.is_empty() {
+            (0, 0, width, self.height as usize)
+        } else {
+            changed_bounds(&self.shown, &pixels, width).unwrap_or((0, 0, 1, 1))
+        };
+        let is_delta = !self.shown.is_empty();
+        let region =
+            || (y..y + h).flat_map(move |row| (x..x + w).map(move |col| row * width + col));
+
+        match &mut self.sink {
+            Sink::Apng(apng) => {
+                // Unchanged pixels can be left transparent and blended over the previous frame,
+                // unless some changed pixel is itself translucent.
+                let blend_over = is_delta && region().all(|i| pixels[i] >> 24 == 0xff);
+                let mut rgba = Vec::with_capacity(w * h * 4);
+                for i in region() {
+                    if blend_over && pixels[i] == self.shown[i] {
+                        rgba.extend_from_slice(&[0; 4]);
+                    } else {
+                        rgba.extend_from_slice(&straight_rgba(pixels[i]));
+                    }
+                }
+                apng.write_frame(
+                    (x as u32, y as u32, w as u32, h as u32),
+                    &rgba,
+                    delay,
+                    blend_over,
+                )?;
+            }
+            Sink::Gif { encoder, quantizer } => {
+                let indices: Vec<u8> = region()
+                    .map(|i| {
+                        if is_delta && pixels[i] == self.shown[i] {
+                            TRANSPARENT
+                        } else {
+                            let [r, g, b, _] = straight_rgba(pixels[i]);
+                            quantizer.index([r, g, b])
+                        }
+                    })
+                    .collect();
+                let frame = gif::Frame {
+                    delay,
+                    dispose: gif::DisposalMethod::Keep,
+                    transparent: is_delta.then_some(TRANSPARENT),
+                    left: x as u16,
+                    top: y as u16,
+                    width: w as u16,
+                    height: h as u16,
+                    buffer: Cow::Owned(indices),
+                    ..gif::Frame::default()
+                };
+                encoder.write_frame(&frame).map_err(gif_error)?;
+            }
+        }
+        self.shown = pixels;
+        self.num_written += 1;
+        Ok(())
+    }
+}
+
+/// Finds the bounding box `(x, y, width, height)` of the pixels that differ between two frames,
+/// or `None` if they're identical.
+fn changed_bounds(a: &[u32], b: &[u32], width: usize) -> Option<(usize, usize, usize, usize)> {
+    let rows: Vec<usize> = (0..a.len() / width)
+        .filter(|&row| a[row * width..][..width] != b[row * width..][..width])
+        .collect();
+    let (&top, &bottom) = (rows.first()?, rows.last()?);
+    let (mut left, mut right) = (width, 0);
+    for &row in &rows {
+        let (a, b) = (&a[row * width..][..width], &b[row * width..][..width]);
+        if let Some(first) = (0..width).find(|&i| a[i] != b[i]) {
+            left = left.min(first);
+            right = right.max((first..width).rfind(|&i| a[i] != b[i]).unwrap_or(first));
+        }
+    }
+    Some((left, top, right - left + 1, bottom - top + 1))
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    match e {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+/// Writes an APNG chunk by chunk. The frame count in the `acTL` chunk isn't known until the end,
+/// so it's patched in by [`ApngWriter::finish`].
+struct ApngWriter<W: Write + Seek> {
+    out: W,
+    actl_pos: u64,
+    plays: u16,
+    sequence: u32,
+    num_frames: u32,
+}
+
+impl<W: Write + Seek> ApngWriter<W> {
+    fn new(mut out: W, width: i32, height: i32, plays: u16) -> io::Result<Self> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend((width as u32).to_be_bytes());
+        ihdr.extend((height as u32).to_be_bytes());
+        // 8-bit RGBA, default compression and filtering, not interlaced.
+        ihdr.extend([8, 6, 0, 0, 0]);
+        write_chunk(&mut out, b"IHDR", &ihdr)?;
+        let actl_pos = out.stream_position()?;
+        let mut apng = ApngWriter {
+            out,
+            actl_pos,
+            plays,
+            sequence: 0,
+            num_frames: 0,
+        };
+        apng.write_actl()?;
+        Ok(apng)
+    }
+
+    fn write_actl(&mut self) -> io::Result<()> {
+        let mut actl = Vec::with_capacity(8);
+        actl.extend(self.num_frames.to_be_bytes());
+        actl.extend(u32::from(self.plays).to_be_bytes());
+        write_chunk(&mut self.out, b"acTL", &actl)
+    }
+
+    /// Writes a frame covering the given `(x, y, width, height)` region. The first frame must
+    /// cover the whole image.
+    fn write_frame(
+        &mut self,
+        (x, y, w, h): (u32, u32, u32, u32),
+        rgba: &[u8],
+        delay_ms: u16,
+        blend_over: bool,
+    ) -> io::Result<()> {
+        let mut fctl = Vec::with_capacity(26);
+        fctl.extend(self.sequence.to_be_bytes());
+        for v in [w, h, x, y] {
+            fctl.extend(v.to_be_bytes());
+        }
+        fctl.extend(delay_ms.to_be_bytes());
+        fctl.extend(1000u16.to_be_bytes());
+        // Dispose op: none. Blend op: source (0) or over (1).
+        fctl.extend([0, u8::from(blend_over)]);
+        write_chunk(&mut self.out, b"fcTL", &fctl)?;
+        self.sequence += 1;
+
+        let data = compress_image_data(w, h, rgba)?;
+        if self.num_frames == 0 {
+            write_chunk(&mut self.out, b"IDAT", &data)?;
+        } else {
+            let mut fdat = Vec::with_capacity(4 + data.len());
+            fdat.extend(self.sequence.to_be_bytes());
+            fdat.extend(data);
+            write_chunk(&mut self.out, b"fdAT", &fdat)?;
+            self.sequence += 1;
+        }
+        self.num_frames += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        write_chunk(&mut self.out, b"IEND", &[])?;
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(self.actl_pos))?;
+        self.write_actl()?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| io::Error::other("PNG chunk too large"))?;
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&crc.finalize().to_be_bytes())
+}
+
+/// Filters and compresses RGBA pixels into the zlib stream that `IDAT` and `fdAT` chunks carry,
+/// by encoding a standalone PNG and extracting its image data.
+fn compress_image_data(width: u32, height: u32, rgba: &[u8]) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+
+    let mut data = Vec::new();
+    let mut pos = 8; // skip the signature
+    while pos + 8 <= png.len() {
+        let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+        if &png[pos + 4..pos + 8] == b"IDAT" {
+            data.extend_from_slice(&png[pos + 8..pos + 8 + len]);
+        }
+        pos += 12 + len;
+    }
+    Ok(data)
+}
+
+/// An animation encoded by [`encode_animation`].
+pub struct EncodedAnimation {
+    pub data: Vec<u8>,
+    pub num_frames: usize,
+    /// The frame step that was needed to fit the size limit; see
+    /// [`AnimationOptions::frame_step`].
+    pub frame_step: u32,
+    pub render_data: RenderData,
+}
+
+/// Renders an animation of `layout`, with frames as configured by `options.animate`, and encodes
+/// it in memory.
+///
+/// If the output is larger than `max_bytes`, it's rendered again keeping half as many frames,
+/// until it fits. This fails if even just the first and last frames don't fit. Progress, including
+/// any retries, is reported to `observer`.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_animation(
+    layout: &Layout,
+    color_db: &ColorDb,
+    options: &RenderOptions,
+    canvas_width: i32,
+    format: AnimationFormat,
+    opts: &AnimationOptions,
+    max_bytes: Option<u64>,
+    observer: &dyn Observer,
+) -> io::Result<EncodedAnimation> {
+    let viewport = options.viewport.clone().unwrap_or_default();
+    let (width, height) = canvas_dimensions(&viewport, canvas_width);
+    let palette = gif_palette(color_db, layout.color_scheme(), layout.colors_used());
+    let mut opts = opts.clone();
+    loop {
+        let out = Cursor::new(Vec::new());
+        let mut encoder = match format {
+            AnimationFormat::Apng => AnimationEncoder::apng(out, width, height, &opts)?,
+            AnimationFormat::Gif => {
+                AnimationEncoder::gif(out, width, height, palette.clone(), &opts)?
+            }
+        };
+        let mut result = Ok(());
+        let render_data = layout
+            .try_render(color_db, options, canvas_width, observer, |frame| {
+                if result.is_ok() {
+                    result = encoder.push(frame.canvas);
+                }
+            })
+            .map_err(io::Error::other)?;
+        result?;
+        let num_frames = encoder.num_frames();
+        let data = encoder.finish()?.into_inner();
+
+        match max_bytes {
+            Some(max) if data.len() as u64 > max => {
+                if num_frames <= 2 {
+                    return Err(io::Error::other(format!(
+                        "animation is {} bytes even with only {} frames, over the limit of {}",
+                        data.len(),
+                        num_frames,
+                        max
+                    )));
+                }
+                opts.frame_step = opts.frame_step.max(1).saturating_mul(2);
+                observer.on_event(&Event::Note(format!(
+                    "animation with {} frames is {} bytes, over the limit of {}; retrying with frame step {}",
+                    num_frames,
+                    data.len(),
+                    max,
+                    opts.frame_step
+                )));
+            }
+            _ => {
+                return Ok(EncodedAnimation {
+                    data,
+                    num_frames,
+                    frame_step: opts.frame_step,
+                    render_data,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A background frame, then frames that each add one pixel, in premultiplied ARGB.
+    fn frames() -> Vec<Vec<u32>> {
+        let mut pixels = vec![0xff_fa_f0_e6; 4 * 3];
+        let mut frames = vec![pixels.clone()];
+        for (i, color) in [0xff_20_40_60, 0xff_80_10_10, 0xff_10_80_10]
+            .into_iter()
+            .enumerate()
+        {
+            pixels[5 * i + 1] = color;
+            frames.push(pixels.clone());
+        }
+        frames
+    }
+
+    fn encode(format: AnimationFormat, opts: &AnimationOptions) -> Vec<u8> {
+        let out = Cursor::new(Vec::new());
+        let mut encoder = match format {
+            AnimationFormat::Apng => AnimationEncoder::apng(out, 4, 3, opts).unwrap(),
+            AnimationFormat::Gif => {
+                let palette = vec![[250, 240, 230], [0x20, 0x40, 0x60], [0x80, 0x10, 0x10]];
+                AnimationEncoder::gif(out, 4, 3, palette, opts).unwrap()
+            }
+        };
+        let mut frames = frames();
+        // A repeated frame should just extend the duration of the last one.
+        frames.push(frames.last().unwrap().clone());
+        for pixels in frames {
+            encoder
+                .push(&Canvas::from_draw_target(raqote::DrawTarget::from_backing(
+                    4, 3, pixels,
+                )))
+                .unwrap();
+        }
+        encoder.finish().unwrap().into_inner()
+    }
+
+    /// Decodes every frame of an animation, fully composited, with its delay in milliseconds.
+    fn decode(data: &[u8], format: AnimationFormat) -> Vec<(Vec<[u8; 4]>, u32)> {
+        use image::AnimationDecoder;
+        let frames = match format {
+            AnimationFormat::Apng => image::codecs::png::PngDecoder::new(data)
+                .unwrap()
+                .apng()
+                .into_frames(),
+            AnimationFormat::Gif => image::codecs::gif::GifDecoder::new(data)
+                .unwrap()
+                .into_frames(),
+        };
+        frames
+            .map(|frame| {
+                let frame = frame.unwrap();
+                let (num, den) = frame.delay().numer_denom_ms();
+                let pixels = frame.buffer().pixels().map(|p| p.0).collect();
+                (pixels, num / den)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apng_roundtrip() {
+        let opts = AnimationOptions {
+            fps: 20.0,
+            hold: Duration::from_millis(1500),
+            ..AnimationOptions::default()
+        };
+        let data = encode(AnimationFormat::Apng, &opts);
+        let decoded = decode(&data, AnimationFormat::Apng);
+        let expected: Vec<_> = frames()
+            .iter()
+            .map(|pixels| {
+                pixels
+                    .iter()
+                    .map(|&px| straight_rgba(px))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let delays: Vec<u32> = decoded.iter().map(|(_, delay)| *delay).collect();
+        assert_eq!(delays, vec![50, 50, 50, 1500]);
+        let pixels: Vec<_> = decoded.into_iter().map(|(pixels, _)| pixels).collect();
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn test_gif_frame_step_keeps_last_frame() {
+        let opts = AnimationOptions {
+            fps: 10.0,
+            frame_step: 2,
+            ..AnimationOptions::default()
+        };
+        let data = encode(AnimationFormat::Gif, &opts);
+        let decoded = decode(&data, AnimationFormat::Gif);
+        let delays: Vec<u32> = decoded.iter().map(|(_, delay)| *delay).collect();
+        assert_eq!(delays, vec![200, 100, 2000]);
+        let (last, _) = decoded.last().unwrap();
+        // The last frame's green pixel isn't in the palette, so it maps to the nearest color.
+        let expected: Vec<[u8; 4]> = frames()
+            .last()
+            .unwrap()
+            .iter()
+            .map(|&px| match px {
+                0xff_10_80_10 => [0x20, 0x40, 0x60, 255],
+                px => straight_rgba(px),
+            })
+            .collect();
+        assert_eq!(last, &expected);
+    }
+
+    #[test]
+    fn test_gif_palette_starts_with_colors_used() {
+        let color_db = ColorDb::from_bundle();
+        let key = |name: &str| color_db.color_key(name).unwrap();
+        let (bg, red, blue) = (key("Austin White"), key("fRed"), key("fDarkBlue"));
+        let scheme = ColorScheme {
+            background: bg,
+            primary_seq: vec![blue, red],
+            secondary_seq: vec![red],
+            splatter_odds: 0.0,
+            splatter_center: (0.0, 0.0),
+            splatter_choices: vec![],
+        };
+        let mut colors_used = ColorsUsed::new();
+        colors_used.insert(red);
+        let palette = gif_palette(&color_db, &scheme, &colors_used);
+        let base = |k: ColorKey| {
+            let spec = color_db.color(k).unwrap();
+            let rgb = Hsb(spec.hue, spec.sat, spec.bright).to_rgb();
+            [rgb.0 as u8, rgb.1 as u8, rgb.2 as u8]
+        };
+        assert_eq!(palette[..3], [base(bg), base(red), base(blue)]);
+        assert!(palette.len() > 10 && palette.len() <= 255);
+    }
+}
@@ -0,0 +1,345 @@
+//! Contact sheets: a grid of labelled thumbnails, for comparing variants of a piece side by side.
+//!
+//! Labels are drawn with a built-in 5x7 pixel font, so that no font files are needed. It covers
+//! printable ASCII; other characters are drawn as `?`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use raqote::{DrawOptions, DrawTarget, Image, SolidSource, Source};
+
+use crate::art::Layout;
+use crate::canvas::Canvas;
+use crate::color::ColorDb;
+use crate::config::{Animation, RenderConfig, RenderOptions};
+
+/// Options for a [`contact_sheet`].
+#[derive(Debug, Copy, Clone)]
+pub struct SheetOptions {
+    /// Number of thumbnails in each row.
+    pub columns: usize,
+    /// Pixels of margin around the sheet and between cells.
+    pub padding: i32,
+    /// Size of each pixel of the label font, in sheet pixels.
+    pub text_scale: i32,
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        SheetOptions {
+            columns: 6,
+            padding: 16,
+            text_scale: 2,
+        }
+    }
+}
+
+/// One cell of a contact sheet: a seed to render, with lines of text to show under it.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub seed: [u8; 32],
+    pub label: Vec<String>,
+}
+
+const BACKGROUND: SolidSource = SolidSource {
+    r: 0xff,
+    g: 0xff,
+    b: 0xff,
+    a: 0xff,
+};
+const TEXT: SolidSource = SolidSource {
+    r: 0x20,
+    g: 0x20,
+    b: 0x20,
+    a: 0xff,
+};
+
+/// Glyphs are 5 columns of 7 rows, plus a column and a row of spacing.
+const GLYPH_WIDTH: i32 = 6;
+const LINE_HEIGHT: i32 = 9;
+
+/// The width, height, and pixels of a rendered thumbnail.
+type Pixels = (i32, i32, Vec<u32>);
+
+/// Renders each cell's seed at `thumbnail_width` and assembles them with [`contact_sheet`].
+/// Thumbnails are rendered in parallel.
+///
+/// The paint-time options of `config` apply to every thumbnail, as do its layout options.
+pub fn render_contact_sheet(
+    cells: &[Cell],
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    thumbnail_width: i32,
+    opts: &SheetOptions,
+) -> Canvas {
+    let options = RenderOptions {
+        animate: Animation::None,
+        ..config.render_options()
+    };
+    let next_cell = AtomicUsize::new(0);
+    // Draw targets can't move between threads, so collect their pixels instead.
+    let thumbnails: Mutex<Vec<Option<Pixels>>> = Mutex::new(vec![None; cells.len()]);
+    let render_cells = || loop {
+        let i = next_cell.fetch_add(1, Ordering::Relaxed);
+        let Some(cell) = cells.get(i) else {
+            break;
+        };
+        let layout = Layout::from_seed(&cell.seed, color_db, &config.layout_options());
+        let canvas = layout
+            .render(color_db, &options, thumbnail_width, |_| {})
+            .canvas;
+        let (width, height) = (canvas.width(), canvas.height());
+        let data = canvas.into_draw_target().into_vec();
+        thumbnails.lock().unwrap()[i] = Some((width, height, data));
+    };
+    let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    std::thread::scope(|s| {
+        for _ in 0..num_threads.min(cells.len()) {
+            s.spawn(render_cells);
+        }
+    });
+
+    let thumbnails: Vec<Canvas> = thumbnails
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|thumbnail| {
+            let (width, height, data) = thumbnail.expect("every cell is rendered");
+            Canvas::from_draw_target(DrawTarget::from_vec(width, height, data))
+        })
+        .collect();
+    let labels: Vec<&[String]> = cells.iter().map(|c| c.label.as_slice()).collect();
+    contact_sheet(&thumbnails, &labels, opts)
+}
+
+/// Lays out `thumbnails` in a grid, left to right and then top to bottom, with the lines of each
+/// label under its thumbnail. Every cell is as large as the largest thumbnail, and labels are
+/// cut short to fit its width.
+///
+/// # Panics
+///
+/// Panics if `thumbnails` and `labels` have different lengths, or if `opts.columns` is zero.
+pub fn contact_sheet(thumbnails: &[Canvas], labels: &[&[String]], opts: &SheetOptions) -> Canvas {
+    assert_eq!(thumbnails.len(), labels.len(), "one label per thumbnail");
+    assert!(opts.columns > 0, "columns must be positive");
+    let cell_width = thumbnails.iter().map(|dt| dt.width()).max().unwrap_or(0);
+    let thumbnail_height = thumbnails.iter().map(|dt| dt.height()).max().unwrap_or(0);
+    let label_lines = labels.iter().map(|l| l.len()).max().unwrap_or(0) as i32;
+    let label_height = label_lines * LINE_HEIGHT * opts.text_scale;
+    let cell_height = thumbnail_height + opts.padding / 2 + label_height;
+
+    let columns = opts.columns.min(thumbnails.len()).max(1);
+    let rows = thumbnails.len().div_ceil(columns);
+    let pad = opts.padding;
+    let mut sheet = DrawTarget::new(
+        pad + columns as i32 * (cell_width + pad),
+        pad + rows as i32 * (cell_height + pad),
+    );
+    sheet.clear(BACKGROUND);
+
+    let max_chars = (cell_width / (GLYPH_WIDTH * opts.text_scale)).max(0) as usize;
+    for (i, (thumbnail, label)) in thumbnails.iter().zip(labels).enumerate() {
+        let x = pad + (i % columns) as i32 * (cell_width + pad);
+        let y = pad + (i / columns) as i32 * (cell_height + pad);
+        let image = Image {
+            width: thumbnail.width(),
+            height: thumbnail.height(),
+            data: thumbnail.data(),
+        };
+        sheet.draw_image_at(x as f32, y as f32, &image, &DrawOptions::new());
+        for (line_index, line) in label.iter().enumerate() {
+            let line_y =
+                y + thumbnail_height + pad / 2 + line_index as i32 * LINE_HEIGHT * opts.text_scale;
+            draw_text(
+                &mut sheet,
+                &truncate(line, max_chars),
+                x,
+                line_y,
+                opts.text_scale,
+            );
+        }
+    }
+    Canvas::from_draw_target(sheet)
+}
+
+/// Cuts `line` down to at most `max_chars` characters, ending in `..` if anything was cut.
+fn truncate(line: &str, max_chars: usize) -> String {
+    if line.chars().count() <= max_chars {
+        return line.to_string();
+    }
+    let mut cut: String = line.chars().take(max_chars.saturating_sub(2)).collect();
+    cut.push_str(&".."[..max_chars.min(2)]);
+    cut
+}
+
+/// Draws one line of text with its top left corner at `(x, y)`.
+fn draw_text(dt: &mut DrawTarget, text: &str, x: i32, y: i32, scale: i32) {
+    let source = Source::Solid(TEXT);
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + i as i32 * GLYPH_WIDTH * scale;
+        for (col, bits) in glyph(c).iter().enumerate() {
+            for row in 0..7 {
+                if bits & (1 << row) != 0 {
+                    dt.fill_rect(
+                        (glyph_x + col as i32 * scale) as f32,
+                        (y + row * scale) as f32,
+                        scale as f32,
+                        scale as f32,
+                        &source,
+                        &DrawOptions::new(),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// The columns of a character's glyph, left to right, with the top row in the least significant
+/// bit.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        ' '..='~' => FONT[c as usize - ' ' as usize],
+        _ => FONT['?' as usize - ' ' as usize],
+    }
+}
+
+/// A 5x7 font for the printable ASCII characters, from `' '` to `'~'`.
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // '#'
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x56, 0x20, 0x50], // '&'
+    [0x00, 0x00, 0x07, 0x00, 0x00], // '\''
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // ')'
+    [0x14, 0x08, 0x3e, 0x08, 0x14], // '*'
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // '0'
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // '@'
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // 'A'
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // 'D'
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // 'G'
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // 'H'
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // 'J'
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // 'M'
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // 'N'
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // 'O'
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // 'Q'
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // 'T'
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // 'U'
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // 'V'
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78], // 'a'
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // 'f'
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // 'g'
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // 'j'
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // 'l'
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // 'm'
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // 'p'
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // 'q'
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20], // 's'
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // 't'
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // 'u'
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // 'v'
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // 'y'
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08], // '~'
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn solid(width: i32, height: i32, argb: u32) -> Canvas {
+        let mut dt = DrawTarget::new(width, height);
+        dt.get_data_mut().fill(argb);
+        Canvas::from_draw_target(dt)
+    }
+
+    #[test]
+    fn test_contact_sheet_layout() {
+        let thumbnails: Vec<_> = (0..5).map(|_| solid(10, 20, 0xff_ff_00_00)).collect();
+        let labels = vec![vec!["a".to_string(), "b".to_string()]; 5];
+        let labels: Vec<&[String]> = labels.iter().map(Vec::as_slice).collect();
+        let opts = SheetOptions {
+            columns: 3,
+            padding: 4,
+            text_scale: 1,
+        };
+        let sheet = contact_sheet(&thumbnails, &labels, &opts);
+        // Cells are 10 wide and 20 + 2 + 2 * 9 = 40 tall, with 4px of padding all around.
+        assert_eq!((sheet.width(), sheet.height()), (4 + 3 * 14, 4 + 2 * 44));
+        let px = |x: i32, y: i32| sheet.data()[(y * sheet.width() + x) as usize];
+        assert_eq!(px(4, 4), 0xff_ff_00_00);
+        assert_eq!(px(18, 48), 0xff_ff_00_00);
+        assert_eq!(px(2, 2), 0xff_ff_ff_ff);
+        assert_eq!(px(32, 48), 0xff_ff_ff_ff, "no sixth thumbnail");
+        // The stem of the `b` under the first thumbnail.
+        assert_eq!(px(4, 4 + 20 + 2 + 9), 0xff_20_20_20);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("flow_field", 10), "flow_field");
+        assert_eq!(truncate("flow_field", 6), "flow..");
+        assert_eq!(truncate("flow_field", 1), ".");
+        assert_eq!(glyph('\u{2192}'), glyph('?'));
+    }
+}
@@ -0,0 +1,288 @@
+//! OpenRaster (`.ora`) output, with one layer per painting phase.
+//!
+//! An OpenRaster file is a zip archive holding a `stack.xml` layer list, one PNG per layer, a
+//! merged image, and a thumbnail. See <https://www.openraster.org/>.
+
+use std::io::{self, Write};
+
+use raqote::{DrawOptions, DrawTarget};
+
+use crate::art::{Layer, LayeredImage};
+use crate::canvas::Canvas;
+
+/// Largest width or height of the embedded thumbnail, per the OpenRaster spec.
+const THUMBNAIL_SIZE: i32 = 256;
+
+/// Name of a layer in the stack and in per-layer file names: `"background"` for `None`, or the
+/// [`Layer::name`] otherwise.
+pub fn layer_name(layer: Option<Layer>) -> &'static str {
+    layer.map_or("background", Layer::name)
+}
+
+/// All layers of an OpenRaster export, from bottom to top.
+pub fn layers() -> impl Iterator<Item = Option<Layer>> {
+    std::iter::once(None).chain(Layer::ALL.map(Some))
+}
+
+/// Writes a layered image as an OpenRaster file, with a transparent layer for each of the
+/// background, shadow, normal, and splatter phases. The layers composite to exactly
+/// [`LayeredImage::image`]; see [`LayeredImage::layer`].
+pub fn write_ora<W: Write>(layered: &LayeredImage, out: W) -> io::Result<()> {
+    let (width, height) = (layered.image.width(), layered.image.height());
+    let mut zip = ZipWriter::new(out);
+    // The mimetype must come first, uncompressed, so that it can be sniffed at a fixed offset.
+    zip.add("mimetype", b"image/openraster")?;
+
+    let mut stack = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<image version=\"0.0.5\" w=\"{}\" h=\"{}\">\n",
+            "  <stack>\n",
+        ),
+        width, height
+    );
+    // The stack lists layers from top to bottom.
+    let all_layers: Vec<Option<Layer>> = layers().collect();
+    for &layer in all_layers.iter().rev() {
+        let name = layer_name(layer);
+        stack.push_str(&format!(
+            concat!(
+                "    <layer name=\"{name}\" src=\"data/{name}.png\" x=\"0\" y=\"0\" ",
+                "opacity=\"1.0\" visibility=\"visible\" composite-op=\"svg:src-over\"/>\n",
+            ),
+            name = name
+        ));
+    }
+    stack.push_str("  </stack>\n</image>\n");
+    zip.add("stack.xml", stack.as_bytes())?;
+
+    for &layer in &all_layers {
+        let png = encode_png(&layered.layer(layer))?;
+        zip.add(&format!("data/{}.png", layer_name(layer)), &png)?;
+    }
+    zip.add("mergedimage.png", &encode_png(&layered.image)?)?;
+    zip.add(
+        "Thumbnail/thumbnail.png",
+        &encode_png(&thumbnail(&layered.image))?,
+    )?;
+    zip.finish()
+}
+
+/// Scales an image down to fit in a `THUMBNAIL_SIZE` square, preserving its aspect ratio.
+fn thumbnail(image: &Canvas) -> Canvas {
+    let (w, h) = (image.width(), image.height());
+    let scale = f64::min(1.0, f64::from(THUMBNAIL_SIZE) / f64::from(w.max(h).max(1)));
+    let (tw, th) = (
+        ((f64::from(w) * scale).round() as i32).max(1),
+        ((f64::from(h) * scale).round() as i32).max(1),
+    );
+    let mut thumb = DrawTarget::new(tw, th);
+    let src = raqote::Image {
+        width: w,
+        height: h,
+        data: image.data(),
+    };
+    thumb.draw_image_with_size_at(tw as f32, th as f32, 0.0, 0.0, &src, &DrawOptions::new());
+    Canvas::from_draw_target(thumb)
+}
+
+/// Encodes a canvas as an RGBA PNG in memory.
+fn encode_png(canvas: &Canvas) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    super::png::write_png(canvas, &[], &mut buf)?;
+    Ok(buf)
+}
+
+/// A minimal writer for zip archives with uncompressed ("stored") entries. PNG data is already
+/// compressed, so there is little to gain from deflating it again.
+struct ZipWriter<W> {
+    out: W,
+    offset: u64,
+    entries: Vec<ZipEntry>,
+}
+
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// MS-DOS timestamp for 1980-01-01 00:00, so that output is reproducible.
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+impl<W: Write> ZipWriter<W> {
+    fn new(out: W) -> Self {
+        ZipWriter {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    fn too_large() -> io::Error {
+        io::Error::other("archive too large for zip without zip64 extensions")
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let offset = u32::try_from(self.offset).map_err(|_| Self::too_large())?;
+        let size = u32::try_from(data.len()).map_err(|_| Self::too_large())?;
+        let crc = crc32fast::hash(data);
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend(0x04034b50u32.to_le_bytes()); // local file header signature
+        header.extend(10u16.to_le_bytes()); // version needed to extract
+        header.extend(0u16.to_le_bytes()); // flags
+        header.extend(0u16.to_le_bytes()); // compression: stored
+        header.extend(DOS_TIME.to_le_bytes());
+        header.extend(DOS_DATE.to_le_bytes());
+        header.extend(crc.to_le_bytes());
+        header.extend(size.to_le_bytes()); // compressed size
+        header.extend(size.to_le_bytes()); // uncompressed size
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // extra field length
+        header.extend(name.as_bytes());
+        self.write_raw(&header)?;
+        self.write_raw(data)?;
+        self.entries.push(ZipEntry {
+            name: name.to_string(),
+            crc,
+            size,
+            offset,
+        });
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let cd_offset = u32::try_from(self.offset).map_err(|_| Self::too_large())?;
+        let mut cd = Vec::new();
+        for entry in &self.entries {
+            cd.extend(0x02014b50u32.to_le_bytes()); // central directory header signature
+            cd.extend(20u16.to_le_bytes()); // version made by
+            cd.extend(10u16.to_le_bytes()); // version needed to extract
+            cd.extend(0u16.to_le_bytes()); // flags
+            cd.extend(0u16.to_le_bytes()); // compression: stored
+            cd.extend(DOS_TIME.to_le_bytes());
+            cd.extend(DOS_DATE.to_le_bytes());
+            cd.extend(entry.crc.to_le_bytes());
+            cd.extend(entry.size.to_le_bytes());
+            cd.extend(entry.size.to_le_bytes());
+            cd.extend((entry.name.len() as u16).to_le_bytes());
+            cd.extend(0u16.to_le_bytes()); // extra field length
+            cd.extend(0u16.to_le_bytes()); // comment length
+            cd.extend(0u16.to_le_bytes()); // disk number
+            cd.extend(0u16.to_le_bytes()); // internal attributes
+            cd.extend(0u32.to_le_bytes()); // external attributes
+            cd.extend(entry.offset.to_le_bytes());
+            cd.extend(entry.name.as_bytes());
+        }
+        let num_entries = self.entries.len() as u16;
+        let cd_size = cd.len() as u32;
+        cd.extend(0x06054b50u32.to_le_bytes()); // end of central directory signature
+        cd.extend(0u16.to_le_bytes()); // this disk
+        cd.extend(0u16.to_le_bytes()); // disk with central directory
+        cd.extend(num_entries.to_le_bytes());
+        cd.extend(num_entries.to_le_bytes());
+        cd.extend(cd_size.to_le_bytes());
+        cd.extend(cd_offset.to_le_bytes());
+        cd.extend(0u16.to_le_bytes()); // comment length
+        self.write_raw(&cd)?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use raqote::SolidSource;
+
+    /// Reads the names and contents of a stored-only zip archive, via its central directory.
+    fn read_zip(buf: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]) as usize;
+        let u32_at = |i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap()) as usize;
+        let eocd = buf.len() - 22;
+        assert_eq!(u32_at(eocd), 0x06054b50);
+        let (count, mut pos) = (u16_at(eocd + 10), u32_at(eocd + 16));
+        let mut files = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(pos), 0x02014b50);
+            let (size, name_len, offset) = (u32_at(pos + 24), u16_at(pos + 28), u32_at(pos + 42));
+            let name = String::from_utf8(buf[pos + 46..pos + 46 + name_len].to_vec()).unwrap();
+            let data_start = offset + 30 + u16_at(offset + 26) + u16_at(offset + 28);
+            let data = buf[data_start..data_start + size].to_vec();
+            assert_eq!(crc32fast::hash(&data) as usize, u32_at(pos + 16));
+            files.push((name, data));
+            pos += 46 + name_len;
+        }
+        files
+    }
+
+    #[test]
+    fn test_write_ora() {
+        let mut image = DrawTarget::new(3, 1);
+        image.clear(SolidSource::from_unpremultiplied_argb(255, 10, 20, 30));
+        image.get_data_mut()[1] = 0xff_40_50_60;
+        image.get_data_mut()[2] = 0xff_70_80_90;
+        let layered = LayeredImage {
+            image: Canvas::from_draw_target(image),
+            owners: vec![None, Some(Layer::Normal), Some(Layer::Splatter)],
+        };
+        let mut buf = Vec::new();
+        write_ora(&layered, &mut buf).unwrap();
+
+        assert_eq!(&buf[30..38], b"mimetype");
+        assert_eq!(&buf[38..54], b"image/openraster");
+        let files = read_zip(&buf);
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "mimetype",
+                "stack.xml",
+                "data/background.png",
+                "data/shadow.png",
+                "data/normal.png",
+                "data/splatter.png",
+                "mergedimage.png",
+                "Thumbnail/thumbnail.png",
+            ]
+        );
+        let stack = std::str::from_utf8(&files[1].1).unwrap();
+        let splatter = stack.find("data/splatter.png").unwrap();
+        let background = stack.find("data/background.png").unwrap();
+        assert!(
+            splatter < background,
+            "stack should list the top layer first"
+        );
+
+        let decode = |name: &str| {
+            let (_, data) = files.iter().find(|(n, _)| n == name).unwrap();
+            image::load_from_memory(data).unwrap().into_rgba8()
+        };
+        let pixels = |name: &str| -> Vec<[u8; 4]> { decode(name).pixels().map(|p| p.0).collect() };
+        assert_eq!(
+            pixels("data/background.png"),
+            vec![[10, 20, 30, 255], [0; 4], [0; 4]]
+        );
+        assert_eq!(pixels("data/shadow.png"), vec![[0; 4]; 3]);
+        assert_eq!(
+            pixels("data/normal.png"),
+            vec![[0; 4], [0x40, 0x50, 0x60, 255], [0; 4]]
+        );
+        assert_eq!(
+            pixels("mergedimage.png"),
+            vec![
+                [10, 20, 30, 255],
+                [0x40, 0x50, 0x60, 255],
+                [0x70, 0x80, 0x90, 255]
+            ]
+        );
+    }
+}
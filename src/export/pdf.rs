@@ -0,0 +1,233 @@
+use std::io::{self, Write};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::{PageSize, Placement};
+use crate::art::Recording;
+
+/// Writes a recording as a single-page vector PDF.
+///
+/// Each stroke is emitted as the same closed polygon that the raster renderer strokes, in paint
+/// order. The recording's viewport is scaled to fit the page, centered, and clipped; any leftover
+/// margin is left blank. Each `(key, value)` pair in `info` is added to the document information
+/// dictionary. Keys must be valid PDF names, like `Title` or `QQLSeed`.
+pub fn write_pdf<W: Write>(
+    recording: &Recording,
+    page: &PageSize,
+    info: &[(&str, String)],
+    out: W,
+) -> io::Result<()> {
+    let content = {
+        let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
+        write_content(recording, page, &mut enc)?;
+        enc.finish()?
+    };
+
+    let mut pdf = PdfWriter::new(out);
+    pdf.write_raw(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n")?;
+    pdf.write_object(1, b"<< /Type /Catalog /Pages 2 0 R >>")?;
+    pdf.write_object(2, b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>")?;
+    pdf.write_object(
+        3,
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.3} {:.3}] /Resources << >> /Contents 4 0 R >>",
+            page.width, page.height
+        )
+        .as_bytes(),
+    )?;
+    pdf.begin_object(4)?;
+    pdf.write_raw(
+        format!(
+            "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+            content.len()
+        )
+        .as_bytes(),
+    )?;
+    pdf.write_raw(&content)?;
+    pdf.write_raw(b"\nendstream")?;
+    pdf.end_object()?;
+    let mut info_dict = String::from("<<");
+    for (key, value) in info {
+        info_dict.push_str(&format!(" /{} {}", key, pdf_string(value)));
+    }
+    info_dict.push_str(" >>");
+    pdf.write_object(5, info_dict.as_bytes())?;
+    pdf.finish(1, 5)
+}
+
+fn write_content<W: Write>(recording: &Recording, page: &PageSize, out: &mut W) -> io::Result<()> {
+    let placement = Placement::fit(recording, page);
+    let origin = recording.origin();
+    let art_rect = format!(
+        "{:.3} {:.3} {:.3} {:.3}",
+        placement.left, placement.top, placement.width, placement.height
+    );
+
+    // Flip to a top-down coordinate system, clip to the artwork, and paint the background.
+    writeln!(out, "1 0 0 -1 0 {:.3} cm", page.height)?;
+    writeln!(out, "{} re W n", art_rect)?;
+    writeln!(out, "{} rg", pdf_color(recording.background))?;
+    writeln!(out, "{} re f", art_rect)?;
+
+    let mut last_color = None;
+    let mut last_width = None;
+    for stroke in &recording.strokes {
+        let color = pdf_color(stroke.color);
+        if last_color.as_ref() != Some(&color) {
+            writeln!(out, "{} RG", color)?;
+            last_color = Some(color);
+        }
+        let width = format!("{:.4}", stroke.width * placement.scale);
+        if last_width.as_ref() != Some(&width) {
+            writeln!(out, "{} w", width)?;
+            last_width = Some(width);
+        }
+        for (i, p) in stroke.vertices(origin).enumerate() {
+            let op = if i == 0 { "m" } else { "l" };
+            let (x, y) = placement.map(p);
+            writeln!(out, "{:.3} {:.3} {}", x, y, op)?;
+        }
+        writeln!(out, "s")?;
+    }
+    Ok(())
+}
+
+/// Formats a color as PDF RGB components, rounded to 8 bits as in the raster output.
+fn pdf_color(color: crate::art::Hsb) -> String {
+    let c = color.to_rgb().to_solid_source();
+    let f = |v: u8| f64::from(v) / 255.0;
+    format!("{:.4} {:.4} {:.4}", f(c.r), f(c.g), f(c.b))
+}
+
+/// Encodes a PDF literal string, using a UTF-16 text string if it's not plain ASCII.
+fn pdf_string(s: &str) -> String {
+    if !s.is_ascii() {
+        let mut hex = String::from("<FEFF");
+        for unit in s.encode_utf16() {
+            hex.push_str(&format!("{:04X}", unit));
+        }
+        hex.push('>');
+        return hex;
+    }
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('(');
+    for c in s.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            _ => result.push(c),
+        }
+    }
+    result.push(')');
+    result
+}
+
+/// Writes PDF objects while tracking byte offsets for the cross-reference table.
+struct PdfWriter<W> {
+    out: W,
+    offset: usize,
+    /// Byte offset of each object, indexed by object number minus one.
+    xref: Vec<usize>,
+}
+
+impl<W: Write> PdfWriter<W> {
+    fn new(out: W) -> Self {
+        PdfWriter {
+            out,
+            offset: 0,
+            xref: Vec::new(),
+        }
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len();
+        Ok(())
+    }
+
+    fn begin_object(&mut self, num: usize) -> io::Result<()> {
+        assert_eq!(num, self.xref.len() + 1, "objects must be written in order");
+        self.xref.push(self.offset);
+        self.write_raw(format!("{} 0 obj\n", num).as_bytes())
+    }
+
+    fn end_object(&mut self) -> io::Result<()> {
+        self.write_raw(b"\nendobj\n")
+    }
+
+    fn write_object(&mut self, num: usize, body: &[u8]) -> io::Result<()> {
+        self.begin_object(num)?;
+        self.write_raw(body)?;
+        self.end_object()
+    }
+
+    fn finish(mut self, root: usize, info: usize) -> io::Result<()> {
+        let xref_offset = self.offset;
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.xref.len() + 1);
+        for offset in &self.xref {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.xref.len() + 1,
+            root,
+            info,
+            xref_offset
+        ));
+        self.write_raw(table.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::art::{Hsb, Layer, Stroke};
+    use crate::config::FractionalViewport;
+
+    #[test]
+    fn test_pdf_string() {
+        assert_eq!(pdf_string("Orbital (x)\\"), "(Orbital \\(x\\)\\\\)");
+        assert_eq!(pdf_string("é"), "<FEFF00E9>");
+    }
+
+    #[test]
+    fn test_write_pdf_xref() {
+        let recording = Recording {
+            background: Hsb(0.0, 0.0, 100.0),
+            viewport: FractionalViewport::default(),
+            strokes: vec![Stroke {
+                center: (1000.0, 1250.0),
+                rx: 10.0,
+                ry: 10.0,
+                width: 1.0,
+                steps: 8.0,
+                color: Hsb(0.0, 100.0, 100.0),
+                color_key: 0,
+                layer: Layer::Normal,
+                group: 0,
+            }],
+        };
+        let mut buf = Vec::new();
+        let page = "8x10in".parse().unwrap();
+        write_pdf(&recording, &page, &[("Title", "QQL".into())], &mut buf).unwrap();
+
+        assert!(buf.starts_with(b"%PDF-1.4\n"));
+        assert!(buf.ends_with(b"%%EOF\n"));
+        let text = String::from_utf8_lossy(&buf);
+        assert!(text.contains("/MediaBox [0 0 576.000 720.000]"));
+        assert!(text.contains("/Info 5 0 R"));
+        // Each xref entry must point at the start of its object.
+        let xref_start = text.rfind("xref\n").unwrap();
+        for (i, line) in text[xref_start..].lines().skip(3).take(5).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            let expected = format!("{} 0 obj", i + 1);
+            assert_eq!(&buf[offset..offset + expected.len()], expected.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! Pen plotter toolpaths, with one pen layer per palette color.
+
+use std::io::{self, Write};
+
+use raqote::{
+    AntialiasMode, DrawOptions, DrawTarget, PathBuilder, SolidSource, Source, StrokeStyle,
+};
+
+use super::{PageSize, Placement, POINTS_PER_INCH};
+use crate::art::{ColorsUsed, Hsb, Recording};
+use crate::canvas::Canvas;
+use crate::color::{ColorDb, ColorKey};
+
+const MM_PER_POINT: f64 = 25.4 / POINTS_PER_INCH;
+
+/// A closed polyline in page millimeters, measured from the top-left corner of the page. The
+/// pen returns to the first vertex after the last one.
+pub type Toolpath = Vec<(f64, f64)>;
+
+/// All the toolpaths to be drawn with a single pen, in plotting order.
+#[derive(Debug, Clone)]
+pub struct PenLayer {
+    pub color: ColorKey,
+    pub paths: Vec<Toolpath>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    /// Drop strokes that are entirely covered by later strokes.
+    pub remove_hidden: bool,
+    /// Width in pixels of the raster used to decide which strokes are hidden. Strokes that only
+    /// show through in areas smaller than about one such pixel are considered hidden.
+    pub occlusion_resolution: i32,
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        PlotOptions {
+            remove_hidden: false,
+            occlusion_resolution: 2000,
+        }
+    }
+}
+
+/// Converts a recording into pen layers, ordered to reduce pen-up travel.
+///
+/// Each palette color gets its own layer, holding the strokes that were perturbed from it (see
+/// [`crate::art::Stroke::color_key`]). Layers are returned in the order of `colors_used`, then any
+/// other colors in the order that they were first painted, skipping any that end up empty. Within
+/// a layer, paths are ordered greedily by nearest neighbor, and each path starts at the vertex
+/// nearest to where the previous one ended.
+pub fn toolpaths(
+    recording: &Recording,
+    colors_used: &ColorsUsed,
+    page: &PageSize,
+    opts: &PlotOptions,
+) -> Vec<PenLayer> {
+    let visible = if opts.remove_hidden {
+        visible_strokes(recording, opts.occlusion_resolution)
+    } else {
+        vec![true; recording.strokes.len()]
+    };
+
+    let mut pens: Vec<ColorKey> = colors_used.iter().collect();
+    for stroke in &recording.strokes {
+        if !pens.contains(&stroke.color_key) {
+            pens.push(stroke.color_key);
+        }
+    }
+
+    let placement = Placement::fit(recording, page);
+    let origin = recording.origin();
+    let mut paths_by_pen: Vec<Vec<Toolpath>> = vec![Vec::new(); pens.len()];
+    for (stroke, _) in recording
+        .strokes
+        .iter()
+        .zip(&visible)
+        .filter(|(_, &visible)| visible)
+    {
+        let pen = pens
+            .iter()
+            .position(|&key| key == stroke.color_key)
+            .expect("every stroke color has a pen");
+        let path: Toolpath = stroke
+            .vertices(origin)
+            .map(|p| {
+                let (x, y) = placement.map(p);
+                (x * MM_PER_POINT, y * MM_PER_POINT)
+            })
+            .collect();
+        paths_by_pen[pen].push(path);
+    }
+
+    pens.into_iter()
+        .zip(paths_by_pen)
+        .filter(|(_, paths)| !paths.is_empty())
+        .map(|(color, paths)| PenLayer {
+            color,
+            paths: order_paths(paths),
+        })
+        .collect()
+}
+
+/// Determines which strokes are at least partly visible in the final image, by painting each
+/// stroke in a unique color without antialiasing and seeing which colors survive.
+fn visible_strokes(recording: &Recording, canvas_width: i32) -> Vec<bool> {
+    let (width, height) = recording.dimensions(canvas_width);
+    let mut dt = DrawTarget::new(width, height);
+    let origin = recording.origin();
+    let scale = recording.scale_ratio(canvas_width) as f32;
+    let options = DrawOptions {
+        antialias: AntialiasMode::None,
+        ..DrawOptions::new()
+    };
+    assert!(
+        recording.strokes.len() < 1 << 24,
+        "too many strokes to check occlusion"
+    );
+    for (i, stroke) in recording.strokes.iter().enumerate() {
+        // Encode `i + 1` in the color channels, reserving zero for "nothing painted here".
+        let [_, r, g, b] = (i as u32 + 1).to_be_bytes();
+        let mut pb = PathBuilder::new();
+        for (j, (x, y)) in stroke.vertices(origin).enumerate() {
+            let (x, y) = (x as f32 * scale, y as f32 * scale);
+            if j == 0 {
+                pb.move_to(x, y);
+            } else {
+                pb.line_to(x, y);
+            }
+        }
+        pb.close();
+        dt.stroke(
+            &pb.finish(),
+            &Source::Solid(SolidSource { r, g, b, a: 255 }),
+            &StrokeStyle {
+                width: stroke.width as f32 * scale,
+                ..StrokeStyle::default()
+            },
+            &options,
+        );
+    }
+
+    let mut visible = vec![false; recording.strokes.len()];
+    for px in dt.get_data() {
+        let id = px & 0x00ff_ffff;
+        if id > 0 {
+            visible[id as usize - 1] = true;
+        }
+    }
+    visible
+}
+
+/// Orders paths by a greedy nearest-neighbor walk starting from the page origin, rotating each
+/// closed path to start at its vertex nearest to the pen.
+fn order_paths(paths: Vec<Toolpath>) -> Vec<Toolpath> {
+    let centers: Vec<(f64, f64)> = paths.iter().map(|path| centroid(path)).collect();
+    let mut grid = Grid::new(&centers);
+    let mut paths: Vec<Option<Toolpath>> = paths.into_iter().map(Some).collect();
+    let mut result = Vec::with_capacity(paths.len());
+    let mut pen = (0.0, 0.0);
+    while let Some(i) = grid.take_nearest(pen, &centers) {
+        let mut path = paths[i].take().expect("path visited twice");
+        let start = (0..path.len())
+            .min_by(|&a, &b| dist2(path[a], pen).total_cmp(&dist2(path[b], pen)))
+            .unwrap_or(0);
+        path.rotate_left(start);
+        pen = path.first().copied().unwrap_or(pen);
+        result.push(path);
+    }
+    result
+}
+
+fn centroid(path: &[(f64, f64)]) -> (f64, f64) {
+    let n = path.len().max(1) as f64;
+    let (sx, sy) = path
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), &(x, y)| (sx + x, sy + y));
+    (sx / n, sy / n)
+}
+
+fn dist2((x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> f64 {
+    (x1 - x2).powi(2) + (y1 - y2).powi(2)
+}
+
+/// A uniform bucket grid over a set of points, for repeated nearest-neighbor queries with
+/// removal.
+struct Grid {
+    min: (f64, f64),
+    cell_size: f64,
+    cols: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+    remaining: usize,
+}
+
+impl Grid {
+    fn new(points: &[(f64, f64)]) -> Self {
+        let (mut min, mut max) = (
+            (f64::INFINITY, f64::INFINITY),
+            (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        );
+        for &(x, y) in points {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        // Aim for a few points per cell.
+        let side = (points.len() as f64 / 4.0).sqrt().ceil().max(1.0);
+        let cell_size = (f64::max(max.0 - min.0, max.1 - min.1) / side).max(1e-6);
+        let cols = ((max.0 - min.0) / cell_size) as usize + 1;
+        let rows = ((max.1 - min.1) / cell_size) as usize + 1;
+        let mut grid = Grid {
+            min,
+            cell_size,
+            cols: if points.is_empty() { 0 } else { cols },
+            rows: if points.is_empty() { 0 } else { rows },
+            cells: Vec::new(),
+            remaining: points.len(),
+        };
+        grid.cells = vec![Vec::new(); grid.cols * grid.rows];
+        for (i, &p) in points.iter().enumerate() {
+            let (cx, cy) = grid.cell_of(p);
+            grid.cells[cy * grid.cols + cx].push(i);
+        }
+        grid
+    }
+
+    fn cell_of(&self, (x, y): (f64, f64)) -> (usize, usize) {
+        let cx = ((x - self.min.0) / self.cell_size).floor().max(0.0) as usize;
+        let cy = ((y - self.min.1) / self.cell_size).floor().max(0.0) as usize;
+        (
+            cx.min(self.cols.saturating_sub(1)),
+            cy.min(self.rows.saturating_sub(1)),
+        )
+    }
+
+    /// Removes and returns the index of the point nearest to `p`, if any remain.
+    fn take_nearest(&mut self, p: (f64, f64), points: &[(f64, f64)]) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (cx, cy) = self.cell_of(p);
+        let mut best: Option<(usize, usize, f64)> = None; // (cell, position in cell, distance^2)
+        let max_ring = self.cols.max(self.rows);
+        for ring in 0..=max_ring {
+            if let Some((_, _, d2)) = best {
+                // Any point in this ring or beyond is at least this far away, even if `p` is
+                // outside the grid and its cell was clamped.
+                let ring_dist = (ring as f64 - 1.0) * self.cell_size;
+                if ring_dist > 0.0 && ring_dist * ring_dist > d2 {
+                    break;
+                }
+            }
+            let (x0, x1) = (cx as isize - ring as isize, cx as isize + ring as isize);
+            let (y0, y1) = (cy as isize - ring as isize, cy as isize + ring as isize);
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    let on_ring = x == x0 || x == x1 || y == y0 || y == y1;
+                    if !on_ring || x < 0 || y < 0 {
+                        continue;
+                    }
+                    let (x, y) = (x as usize, y as usize);
+                    if x >= self.cols || y >= self.rows {
+                        continue;
+                    }
+                    let cell = y * self.cols + x;
+                    for (pos, &i) in self.cells[cell].iter().enumerate() {
+                        let d2 = dist2(points[i], p);
+                        if best.is_none_or(|(_, _, best_d2)| d2 < best_d2) {
+                            best = Some((cell, pos, d2));
+                        }
+                    }
+                }
+            }
+        }
+        let (cell, pos, _) = best?;
+        self.remaining -= 1;
+        Some(self.cells[cell].swap_remove(pos))
+    }
+}
+
+/// Settings for pen control in G-code output.
+#[derive(Debug, Clone)]
+pub struct GcodeOptions {
+    /// Z height (in millimeters) for traveling with the pen lifted.
+    pub pen_up_z: f64,
+    /// Z height (in millimeters) for drawing.
+    pub pen_down_z: f64,
+    /// Drawing speed, in millimeters per minute.
+    pub feed_rate: f64,
+}
+
+impl Default for GcodeOptions {
+    fn default() -> Self {
+        GcodeOptions {
+            pen_up_z: 5.0,
+            pen_down_z: 0.0,
+            feed_rate: 3000.0,
+        }
+    }
+}
+
+/// Writes pen layers as HPGL, selecting pen `n` for the `n`th layer (1-indexed). HPGL's origin is
+/// at the bottom-left, so coordinates are flipped vertically within the page.
+pub fn write_hpgl<W: Write>(
+    layers: &[PenLayer],
+    color_db: &ColorDb,
+    page: &PageSize,
+    mut out: W,
+) -> io::Result<()> {
+    // HPGL plotter units are 40 per millimeter.
+    const UNITS_PER_MM: f64 = 40.0;
+    let page_height_mm = page.height * MM_PER_POINT;
+    let coords = |(x, y): (f64, f64)| {
+        (
+            (x * UNITS_PER_MM).round() as i64,
+            ((page_height_mm - y) * UNITS_PER_MM).round() as i64,
+        )
+    };
+    writeln!(out, "IN;")?;
+    for (i, layer) in layers.iter().enumerate() {
+        writeln!(out, "CO \"{}\";", color_name(color_db, layer.color))?;
+        writeln!(out, "SP{};", i + 1)?;
+        for path in &layer.paths {
+            let Some(&first) = path.first() else { continue };
+            let (x, y) = coords(first);
+            write!(out, "PU{},{};PD", x, y)?;
+            for (j, &p) in path.iter().skip(1).chain([first].iter()).enumerate() {
+                let (x, y) = coords(p);
+                if j > 0 {
+                    write!(out, ",")?;
+                }
+                write!(out, "{},{}", x, y)?;
+            }
+            writeln!(out, ";")?;
+        }
+    }
+    writeln!(out, "PU;SP0;")?;
+    out.flush()
+}
+
+/// Writes pen layers as G-code in millimeters, pausing with `M0` before each layer so that the
+/// pen can be changed. Coordinates are flipped vertically so that the origin is at the
+/// bottom-left of the page.
+pub fn write_gcode<W: Write>(
+    layers: &[PenLayer],
+    color_db: &ColorDb,
+    page: &PageSize,
+    opts: &GcodeOptions,
+    mut out: W,
+) -> io::Result<()> {
+    let page_height_mm = page.height * MM_PER_POINT;
+    writeln!(out, "G21 ; millimeters")?;
+    writeln!(out, "G90 ; absolute positioning")?;
+    writeln!(out, "G0 Z{:.3}", opts.pen_up_z)?;
+    for (i, layer) in layers.iter().enumerate() {
+        let name = color_name(color_db, layer.color);
+        writeln!(out, "; layer {}: {}", i + 1, name)?;
+        writeln!(out, "M0 ; insert pen: {}", name)?;
+        for path in &layer.paths {
+            let Some(&first) = path.first() else { continue };
+            writeln!(out, "G0 X{:.3} Y{:.3}", first.0, page_height_mm - first.1)?;
+            writeln!(out, "G1 Z{:.3} F{:.0}", opts.pen_down_z, opts.feed_rate)?;
+            for &(x, y) in path.iter().skip(1).chain([first].iter()) {
+                writeln!(out, "G1 X{:.3} Y{:.3}", x, page_height_mm - y)?;
+            }
+            writeln!(out, "G0 Z{:.3}", opts.pen_up_z)?;
+        }
+    }
+    writeln!(out, "G0 X0 Y0")?;
+    out.flush()
+}
+
+/// Renders a simulated plot: every toolpath drawn in its pen's base color at the given pen width
+/// (in millimeters), on a white page that is `width` pixels wide.
+pub fn render_preview(
+    layers: &[PenLayer],
+    color_db: &ColorDb,
+    page: &PageSize,
+    pen_width: f64,
+    width: i32,
+) -> Canvas {
+    let px_per_mm = f64::from(width) / (page.width * MM_PER_POINT);
+    let height = (page.height * MM_PER_POINT * px_per_mm).round() as i32;
+    let mut dt = DrawTarget::new(width, height);
+    dt.clear(SolidSource {
+        r: 255,
+        g: 255,
+        b: 255,
+        a: 255,
+    });
+    let style = StrokeStyle {
+        width: (pen_width * px_per_mm) as f32,
+        ..StrokeStyle::default()
+    };
+    for layer in layers {
+        let source = match color_db.color(layer.color) {
+            Some(spec) => Hsb(spec.hue, spec.sat, spec.bright).to_rgb().to_source(),
+            None => continue,
+        };
+        for path in &layer.paths {
+            let mut pb = PathBuilder::new();
+            for (i, &(x, y)) in path.iter().enumerate() {
+                let (x, y) = ((x * px_per_mm) as f32, (y * px_per_mm) as f32);
+                if i == 0 {
+                    pb.move_to(x, y);
+                } else {
+                    pb.line_to(x, y);
+                }
+            }
+            pb.close();
+            dt.stroke(&pb.finish(), &source, &style, &DrawOptions::new());
+        }
+    }
+    Canvas::from_draw_target(dt)
+}
+
+fn color_name(color_db: &ColorDb, key: ColorKey) -> &str {
+    color_db
+        .color(key)
+        .map_or("<invalid color>", |c| c.name.as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_one_layer_per_palette_color() {
+        let seed =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let color_db = ColorDb::from_bundle();
+        let recording = crate::art::record(&seed, &color_db, &Default::default());
+        let page = PageSize::from_pixels(recording.canvas.dimensions(800));
+        let layers = toolpaths(
+            &recording.canvas,
+            &recording.colors_used,
+            &page,
+            &PlotOptions::default(),
+        );
+
+        // `colors_used` only counts the color that each flow line group starts with, but points
+        // can switch palette colors partway through a group, so those get layers, too.
+        let mut keys: Vec<ColorKey> = recording.colors_used.iter().collect();
+        for stroke in &recording.canvas.strokes {
+            if !keys.contains(&stroke.color_key) {
+                keys.push(stroke.color_key);
+            }
+        }
+        let colors: Vec<ColorKey> = layers.iter().map(|layer| layer.color).collect();
+        assert_eq!(colors, keys);
+        assert!(layers.len() >= recording.colors_used.as_slice().len());
+        for layer in &layers {
+            let num_strokes = recording
+                .canvas
+                .strokes
+                .iter()
+                .filter(|stroke| stroke.color_key == layer.color)
+                .count();
+            assert_eq!(layer.paths.len(), num_strokes);
+        }
+    }
+
+    #[test]
+    fn test_order_paths_nearest_neighbor() {
+        let square = |x: f64, y: f64| vec![(x, y), (x + 1.0, y), (x + 1.0, y + 1.0), (x, y + 1.0)];
+        let paths = vec![
+            square(50.0, 50.0),
+            square(10.0, 0.0),
+            square(0.0, 0.0),
+            square(20.0, 0.0),
+        ];
+        let ordered = order_paths(paths);
+        let starts: Vec<(f64, f64)> = ordered.iter().map(|p| p[0]).collect();
+        assert_eq!(
+            starts,
+            vec![(0.0, 0.0), (10.0, 0.0), (20.0, 0.0), (50.0, 50.0)]
+        );
+        // Paths are rotated to start near the pen, but keep all their vertices.
+        assert_eq!(ordered[3].len(), 4);
+    }
+
+    #[test]
+    fn test_grid_take_nearest_exhausts() {
+        let points: Vec<(f64, f64)> = (0..100)
+            .map(|i| ((i * 37 % 100) as f64, (i * 61 % 100) as f64))
+            .collect();
+        let mut grid = Grid::new(&points);
+        let mut seen = vec![false; points.len()];
+        let mut pos = (1000.0, -1000.0);
+        while let Some(i) = grid.take_nearest(pos, &points) {
+            // Must agree with a brute-force search over the remaining points.
+            let best = (0..points.len())
+                .filter(|&j| !seen[j])
+                .map(|j| dist2(points[j], pos))
+                .fold(f64::INFINITY, f64::min);
+            assert_eq!(dist2(points[i], pos), best);
+            assert!(!seen[i]);
+            seen[i] = true;
+            pos = points[i];
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+}
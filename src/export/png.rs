@@ -0,0 +1,93 @@
+//! PNG output with text metadata, for recording how an image was made.
+
+use std::io::Write;
+
+use crate::canvas::Canvas;
+use ::png::{BitDepth, ColorType, DecodingError, Encoder, EncodingError, Info};
+
+/// Writes a canvas as an 8-bit RGBA PNG, with a text chunk for each `(keyword, text)` pair. See
+/// [`add_text_chunks`] for how they're stored.
+pub fn write_png<W: Write>(
+    canvas: &Canvas,
+    text: &[(&str, String)],
+    out: W,
+) -> Result<(), EncodingError> {
+    let mut encoder = Encoder::new(out, canvas.width() as u32, canvas.height() as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    add_text_chunks(&mut encoder, text)?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&canvas.to_rgba8())?;
+    writer.finish()
+}
+
+/// Adds text chunks to be written before the image data. Printable ASCII text is stored in
+/// `tEXt` chunks, which every PNG reader understands; anything else goes in UTF-8 `iTXt` chunks.
+///
+/// Keywords must be 1 to 79 printable Latin-1 characters.
+pub fn add_text_chunks<W: Write>(
+    encoder: &mut Encoder<W>,
+    text: &[(&str, String)],
+) -> Result<(), EncodingError> {
+    for (keyword, text) in text {
+        let is_plain = text
+            .bytes()
+            .all(|b| b == b'\n' || (b' '..=b'~').contains(&b));
+        if is_plain {
+            encoder.add_text_chunk(keyword.to_string(), text.clone())?;
+        } else {
+            encoder.add_itxt_chunk(keyword.to_string(), text.clone())?;
+        }
+    }
+    Ok(())
+}
+
+/// All text chunks read so far by a PNG decoder, as `(keyword, text)` pairs in the order of
+/// `tEXt`, `zTXt`, then `iTXt` chunks. Chunks before the image data are read along with the
+/// header.
+pub fn text_chunks(info: &Info) -> Result<Vec<(String, String)>, DecodingError> {
+    let mut text = Vec::new();
+    for chunk in &info.uncompressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.text.clone()));
+    }
+    for chunk in &info.compressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.get_text()?));
+    }
+    for chunk in &info.utf8_text {
+        text.push((chunk.keyword.clone(), chunk.get_text()?));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_text_chunks_roundtrip() {
+        let mut dt = raqote::DrawTarget::new(2, 1);
+        dt.get_data_mut()[0] = 0xff_10_20_30;
+        dt.get_data_mut()[1] = 0x80_40_00_00;
+        let canvas = Canvas::from_draw_target(dt);
+        let text = [
+            ("Software", "qqlrs 0.1.0".to_string()),
+            ("QQLTraits", "Traits {\n  ring: Thin,\n}".to_string()),
+            ("Comment", "caf\u{e9} \u{2615}".to_string()),
+        ];
+        let mut buf = Vec::new();
+        write_png(&canvas, &text, &mut buf).unwrap();
+
+        let mut reader = ::png::Decoder::new(&buf[..]).read_info().unwrap();
+        let read: Vec<(String, String)> = text_chunks(reader.info()).unwrap();
+        let expected: Vec<(String, String)> = text
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        assert_eq!(read, expected);
+        assert_eq!(reader.info().uncompressed_latin1_text.len(), 2);
+
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, [0x10, 0x20, 0x30, 255, 0x80, 0, 0, 0x80]);
+    }
+}
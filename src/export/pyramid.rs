@@ -0,0 +1,339 @@
+//! Deep Zoom (DZI) tile pyramids, for zoomable viewers like OpenSeadragon.
+//!
+//! Every tile of every level is painted as its own viewport render of one shared [`Layout`], so
+//! deep levels show real detail instead of upsampled pixels, and shallow levels are true
+//! low-resolution renders rather than downsampled ones.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::art::{canvas_dimensions, Layout};
+use crate::canvas::Canvas;
+use crate::color::ColorDb;
+use crate::config::{Animation, Chunks, FractionalViewport, RenderConfig, RenderOptions};
+use crate::progress::{Event, Observer, Stage, StageTimer};
+
+/// Options for a [`Pyramid`].
+#[derive(Debug, Copy, Clone)]
+pub struct PyramidOptions {
+    /// Width and height of each tile in pixels, not counting overlap.
+    pub tile_size: u32,
+    /// Pixels that each tile shares with each of its neighbors.
+    pub overlap: u32,
+}
+
+impl Default for PyramidOptions {
+    fn default() -> Self {
+        PyramidOptions {
+            tile_size: 254,
+            overlap: 1,
+        }
+    }
+}
+
+/// One level of a pyramid. Level 0 is a single pixel, and each level doubles the size of the
+/// previous one, up to the full image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Level {
+    pub index: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A tile of one level, in that level's pixel coordinates, including any overlap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    pub level: u32,
+    pub col: u32,
+    pub row: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The geometry of a DZI pyramid for an image of a given size.
+#[derive(Debug, Copy, Clone)]
+pub struct Pyramid {
+    width: u32,
+    height: u32,
+    opts: PyramidOptions,
+}
+
+impl Pyramid {
+    pub fn new(width: u32, height: u32, opts: PyramidOptions) -> Self {
+        assert!(opts.tile_size > 0, "tile size must be positive");
+        Pyramid {
+            width,
+            height,
+            opts,
+        }
+    }
+
+    /// The index of the full-resolution level: `ceil(log2(max(width, height)))`.
+    pub fn max_level(&self) -> u32 {
+        let max_dim = self.width.max(self.height).max(1);
+        u32::BITS - (max_dim - 1).leading_zeros()
+    }
+
+    pub fn levels(&self) -> impl Iterator<Item = Level> + '_ {
+        (0..=self.max_level()).map(move |index| {
+            let shift = self.max_level() - index;
+            let scaled = |dim: u32| ((u64::from(dim) + (1 << shift) - 1) >> shift) as u32;
+            Level {
+                index,
+                width: scaled(self.width).max(1),
+                height: scaled(self.height).max(1),
+            }
+        })
+    }
+
+    pub fn tiles(&self, level: Level) -> impl Iterator<Item = Tile> {
+        let PyramidOptions { tile_size, overlap } = self.opts;
+        let cols = level.width.div_ceil(tile_size);
+        let rows = level.height.div_ceil(tile_size);
+        let span = move |i: u32, limit: u32| {
+            let start = (i * tile_size).saturating_sub(overlap);
+            let end = ((i + 1) * tile_size + overlap).min(limit);
+            (start, end - start)
+        };
+        (0..rows).flat_map(move |row| {
+            (0..cols).map(move |col| {
+                let (x, width) = span(col, level.width);
+                let (y, height) = span(row, level.height);
+                Tile {
+                    level: level.index,
+                    col,
+                    row,
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            })
+        })
+    }
+
+    /// The `.dzi` descriptor for this pyramid, with PNG tiles.
+    pub fn descriptor(&self) -> String {
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "\n",
+                r#"<Image xmlns="http://schemas.microsoft.com/deepzoom/2008" Format="png" Overlap="{}" TileSize="{}">"#,
+                "\n",
+                r#"  <Size Width="{}" Height="{}"/>"#,
+                "\n</Image>\n",
+            ),
+            self.opts.overlap, self.opts.tile_size, self.width, self.height
+        )
+    }
+
+    /// Path of a tile image relative to the `_files` directory.
+    pub fn tile_path(tile: &Tile) -> PathBuf {
+        PathBuf::from(tile.level.to_string()).join(format!("{}_{}.png", tile.col, tile.row))
+    }
+}
+
+/// Paints a DZI pyramid of `layout` whose full-resolution level is a render at `canvas_width`,
+/// writing the descriptor to `dzi_path` and tiles to the sibling `<name>_files` directory.
+/// Tiles are painted in parallel. Returns the number of tiles written.
+///
+/// The paint-time options of `config` apply, but its viewport and chunks are ignored.
+///
+/// Painting all the tiles is reported to `observer` as one paint stage. Every tile paints every
+/// point, so as with chunks, the points painted count each point once per tile.
+pub fn write_dzi(
+    layout: &Layout,
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    canvas_width: i32,
+    opts: PyramidOptions,
+    dzi_path: &Path,
+    observer: &dyn Observer,
+) -> io::Result<usize> {
+    let (width, height) = canvas_dimensions(&FractionalViewport::default(), canvas_width);
+    let pyramid = Pyramid::new(width as u32, height as u32, opts);
+
+    let stem = dzi_path.file_stem().unwrap_or_default().to_string_lossy();
+    let files_dir = dzi_path.with_file_name(format!("{}_files", stem));
+    let mut tiles = Vec::new();
+    for level in pyramid.levels() {
+        std::fs::create_dir_all(files_dir.join(level.index.to_string()))?;
+        tiles.extend(pyramid.tiles(level).map(|tile| (level, tile)));
+    }
+
+    let timer = StageTimer::start(observer, Stage::Paint);
+    let num_points = layout.points().len();
+    let next_tile = AtomicUsize::new(0);
+    let tiles_painted = AtomicUsize::new(0);
+    let first_error: Mutex<Option<io::Error>> = Mutex::new(None);
+    let paint_tiles = || {
+        while let Some(&(level, tile)) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+            if first_error.lock().unwrap().is_some() {
+                break;
+            }
+            let canvas = paint_tile(layout, color_db, config, level, &tile);
+            let path = files_dir.join(Pyramid::tile_path(&tile));
+            if let Err(e) = canvas.write_png(&path, &[]) {
+                let e = io::Error::other(format!("{}: {}", path.display(), e));
+                first_error.lock().unwrap().get_or_insert(e);
+            }
+            let painted = tiles_painted.fetch_add(1, Ordering::Relaxed) + 1;
+            observer.on_event(&Event::PointsPainted {
+                painted: painted * num_points,
+                total: tiles.len() * num_points,
+            });
+        }
+    };
+    let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    std::thread::scope(|s| {
+        for _ in 0..num_threads {
+            s.spawn(paint_tiles);
+        }
+    });
+    if let Some(e) = first_error.into_inner().unwrap() {
+        return Err(e);
+    }
+    timer.finish();
+
+    let mut dzi = std::fs::File::create(dzi_path)?;
+    dzi.write_all(pyramid.descriptor().as_bytes())?;
+    Ok(tiles.len())
+}
+
+fn paint_tile(
+    layout: &Layout,
+    color_db: &ColorDb,
+    config: &RenderConfig,
+    level: Level,
+    tile: &Tile,
+) -> Canvas {
+    // Each level is a render with the level's width as the canvas width. The renderer derives the
+    // canvas height from the width, rounding down, and DZI rounds level sizes up, so the render
+    // can be a row or two shorter than the level: the bottom tiles end where the render ends. The
+    // viewport is placed in pixel rows of the unrounded canvas height, so that vertically
+    // adjacent tiles line up exactly.
+    let canvas_width = level.width as i32;
+    let (_, render_height) = canvas_dimensions(&FractionalViewport::default(), canvas_width);
+    let render_height = render_height.max(1) as u32;
+    let exact_height = f64::from(canvas_width) * 5.0 / 4.0;
+    let top = tile.y.min(render_height - 1);
+    let bottom = (tile.y + tile.height).clamp(top + 1, render_height);
+    let viewport = FractionalViewport::from_whlt(
+        f64::from(tile.width) / f64::from(level.width),
+        f64::from(bottom - top) / f64::from(render_height),
+        f64::from(tile.x) / f64::from(level.width),
+        f64::from(top) / exact_height,
+    );
+    let options = RenderOptions {
+        viewport: Some(viewport),
+        chunks: Chunks::default(),
+        animate: Animation::None,
+        ..config.render_options()
+    };
+    layout
+        .render(color_db, &options, canvas_width, |_| {})
+        .canvas
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pyramid_levels() {
+        let pyramid = Pyramid::new(2400, 3000, PyramidOptions::default());
+        assert_eq!(pyramid.max_level(), 12);
+        let levels: Vec<Level> = pyramid.levels().collect();
+        assert_eq!(levels.len(), 13);
+        assert_eq!(
+            levels[0],
+            Level {
+                index: 0,
+                width: 1,
+                height: 1
+            }
+        );
+        assert_eq!((levels[9].width, levels[9].height), (300, 375));
+        assert_eq!((levels[11].width, levels[11].height), (1200, 1500));
+        assert_eq!((levels[12].width, levels[12].height), (2400, 3000));
+        assert_eq!(Pyramid::new(1, 1, PyramidOptions::default()).max_level(), 0);
+        assert_eq!(
+            Pyramid::new(256, 2, PyramidOptions::default()).max_level(),
+            8
+        );
+        assert_eq!(
+            Pyramid::new(257, 2, PyramidOptions::default()).max_level(),
+            9
+        );
+    }
+
+    #[test]
+    fn test_pyramid_tiles_overlap() {
+        let pyramid = Pyramid::new(
+            600,
+            300,
+            PyramidOptions {
+                tile_size: 254,
+                overlap: 1,
+            },
+        );
+        let level = pyramid.levels().last().unwrap();
+        let tiles: Vec<Tile> = pyramid.tiles(level).collect();
+        let spans: Vec<_> = tiles
+            .iter()
+            .map(|t| (t.col, t.row, t.x, t.y, t.width, t.height))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 0, 0, 0, 255, 255),
+                (1, 0, 253, 0, 256, 255),
+                (2, 0, 507, 0, 93, 255),
+                (0, 1, 0, 253, 255, 47),
+                (1, 1, 253, 253, 256, 47),
+                (2, 1, 507, 253, 93, 47),
+            ]
+        );
+        assert_eq!(
+            Pyramid::tile_path(&tiles[4]),
+            PathBuf::from("10").join("1_1.png")
+        );
+    }
+
+    #[test]
+    fn test_adjacent_tiles_agree() {
+        // At width 250, the render is 312 rows tall, but the level is 313 rows tall.
+        let seed =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let color_db = ColorDb::from_bundle();
+        let config = RenderConfig::default();
+        let layout = Layout::from_seed(&seed, &color_db, &config.layout_options());
+        let pyramid = Pyramid::new(2000, 2500, PyramidOptions::default());
+        let level = pyramid.levels().find(|level| level.width == 250).unwrap();
+        assert_eq!(level.height, 313);
+        let tiles: Vec<Tile> = pyramid.tiles(level).collect();
+        let (upper, lower) = (&tiles[0], &tiles[1]);
+        assert_eq!(
+            (upper.y, upper.height, lower.y, lower.height),
+            (0, 255, 253, 60)
+        );
+
+        let upper = paint_tile(&layout, &color_db, &config, level, upper);
+        let lower = paint_tile(&layout, &color_db, &config, level, lower);
+        assert_eq!((upper.width(), upper.height()), (250, 255));
+        // The lower tile ends with the render, a row short of the level.
+        assert_eq!((lower.width(), lower.height()), (250, 59));
+        // The overlap rows match, up to rounding in rasterizing paths with different origins.
+        let overlap = |canvas: &Canvas, skip: usize| -> Vec<[u8; 4]> {
+            canvas.rows().skip(skip).take(2).flatten().collect()
+        };
+        for (a, b) in overlap(&upper, 253).iter().zip(overlap(&lower, 0)) {
+            let close = a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 1);
+            assert!(close, "{:?} != {:?}", a, b);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Uncompressed frame streams, for piping animations into video encoders without intermediate
+//! files.
+
+use std::io::{self, Write};
+
+use crate::canvas::Canvas;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// YUV4MPEG2 with 4:2:0 chroma subsampling and limited-range BT.709 colors, as read by FFmpeg
+    /// and most video encoders.
+    Y4m,
+    /// A one-line text header, `RGBA <width> <height> <fps>`, followed by each frame as straight
+    /// (non-premultiplied) 8-bit RGBA pixels in row-major order.
+    Rgba,
+}
+
+/// Writes frames of equal size as an uncompressed [`StreamFormat`] stream.
+pub struct FrameStream<W: Write> {
+    out: W,
+    format: StreamFormat,
+    width: i32,
+    height: i32,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> FrameStream<W> {
+    /// Creates a stream and writes its header. `fps` is only recorded in the header; frames are
+    /// written as fast as they're pushed.
+    pub fn new(
+        mut out: W,
+        format: StreamFormat,
+        width: i32,
+        height: i32,
+        fps: f64,
+    ) -> io::Result<Self> {
+        match format {
+            StreamFormat::Y4m => {
+                let (num, den) = frame_rate_ratio(fps);
+                writeln!(
+                    out,
+                    "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C420jpeg XCOLORRANGE=LIMITED",
+                    width, height, num, den
+                )?;
+            }
+            StreamFormat::Rgba => writeln!(out, "RGBA {} {} {}", width, height, fps)?,
+        }
+        Ok(FrameStream {
+            out,
+            format,
+            width,
+            height,
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn write_frame(&mut self, canvas: &Canvas) -> io::Result<()> {
+        assert_eq!(
+            (canvas.width(), canvas.height()),
+            (self.width, self.height),
+            "frame dimensions changed"
+        );
+        self.buf.clear();
+        match self.format {
+            StreamFormat::Y4m => {
+                self.out.write_all(b"FRAME\n")?;
+                y4m_planes(canvas, &mut self.buf);
+            }
+            StreamFormat::Rgba => self.buf.extend(canvas.straight_pixels().flatten()),
+        }
+        self.out.write_all(&self.buf)?;
+        // Consumers like video encoders should see each frame as soon as it's ready.
+        self.out.flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Approximates a frame rate as a ratio of integers, exactly for whole numbers and NTSC-style
+/// rates like 29.97.
+fn frame_rate_ratio(fps: f64) -> (u64, u64) {
+    if fps.fract() == 0.0 {
+        return (fps as u64, 1);
+    }
+    let den = 1000;
+    let num = (fps * 1000.0).round() as u64;
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    let g = gcd(num, den).max(1);
+    (num / g, den / g)
+}
+
+/// Converts a frame to Y, Cb, and Cr planes, with limited-range BT.709 coefficients and chroma
+/// averaged over each 2x2 block. Transparent pixels are treated as if over black.
+fn y4m_planes(canvas: &Canvas, out: &mut Vec<u8>) {
+    const KR: f32 = 0.2126;
+    const KB: f32 = 0.0722;
+    const KG: f32 = 1.0 - KR - KB;
+    let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+    let data = canvas.data();
+    // Premultiplied channels are already composited over black.
+    let rgb = |px: u32| {
+        let [_, r, g, b] = px.to_be_bytes();
+        [r, g, b].map(f32::from)
+    };
+    let luma = |[r, g, b]: [f32; 3]| KR * r + KG * g + KB * b;
+
+    out.extend(data.iter().map(|&px| {
+        let y = luma(rgb(px));
+        (16.0 + y * 219.0 / 255.0).round() as u8
+    }));
+
+    let (cw, ch) = (width.div_ceil(2), height.div_ceil(2));
+    let mut cb = Vec::with_capacity(cw * ch);
+    let mut cr = Vec::with_capacity(cw * ch);
+    for cy in 0..ch {
+        for cx in 0..cw {
+            let mut sum = [0.0f32; 3];
+            let mut n = 0.0;
+            for y in (2 * cy)..(2 * cy + 2).min(height) {
+                for x in (2 * cx)..(2 * cx + 2).min(width) {
+                    let c = rgb(data[y * width + x]);
+                    (0..3).for_each(|i| sum[i] += c[i]);
+                    n += 1.0;
+                }
+            }
+            let avg = sum.map(|s| s / n);
+            let y = luma(avg);
+            let scale = 224.0 / 255.0;
+            cb.push((128.0 + (avg[2] - y) / (2.0 * (1.0 - KB)) * scale).round() as u8);
+            cr.push((128.0 + (avg[0] - y) / (2.0 * (1.0 - KR)) * scale).round() as u8);
+        }
+    }
+    out.extend(cb);
+    out.extend(cr);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use raqote::SolidSource;
+
+    #[test]
+    fn test_y4m_stream() {
+        let mut canvas = Canvas::new(3, 2);
+        let dt = canvas.draw_target_mut();
+        dt.clear(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255));
+        let mut stream = FrameStream::new(Vec::new(), StreamFormat::Y4m, 3, 2, 29.97).unwrap();
+        stream.write_frame(&canvas).unwrap();
+        let dt = canvas.draw_target_mut();
+        dt.clear(SolidSource::from_unpremultiplied_argb(255, 255, 0, 0));
+        dt.get_data_mut()[2] = 0xff_00_00_00;
+        stream.write_frame(&canvas).unwrap();
+        let out = stream.finish().unwrap();
+
+        let header = b"YUV4MPEG2 W3 H2 F2997:100 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED\n";
+        assert_eq!(&out[..header.len()], header);
+        let frames = &out[header.len()..];
+        // 6 luma samples, then 2x1 samples for each chroma plane.
+        let frame_len = b"FRAME\n".len() + 6 + 2 + 2;
+        assert_eq!(frames.len(), 2 * frame_len);
+        assert_eq!(
+            &frames[6..frame_len],
+            &[235, 235, 235, 235, 235, 235, 128, 128, 128, 128]
+        );
+        let red = &frames[frame_len + 6..];
+        assert_eq!(&red[..6], &[63, 63, 16, 63, 63, 63]);
+        // The right chroma block is half black, which dilutes its chroma but not its hue.
+        assert_eq!(&red[6..], &[102, 115, 240, 184]);
+    }
+
+    #[test]
+    fn test_rgba_stream() {
+        let canvas = Canvas::from_rgba8(2, 1, &[0x10, 0x20, 0x30, 255, 0x80, 0, 0, 0x80]).unwrap();
+        let mut stream = FrameStream::new(Vec::new(), StreamFormat::Rgba, 2, 1, 30.0).unwrap();
+        stream.write_frame(&canvas).unwrap();
+        let out = stream.finish().unwrap();
+        assert_eq!(
+            out,
+            [
+                &b"RGBA 2 1 30\n"[..],
+                &[0x10, 0x20, 0x30, 255, 0x80, 0, 0, 0x80]
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::{self, Write};
+
+use crate::art::{Layer, Recording, Stroke};
+
+/// Writes a recording as an SVG document, with one `<ellipse>` per stroke.
+///
+/// Coordinates are in output pixels for a virtual canvas of the given width, so the document has
+/// the same dimensions as the corresponding PNG. Strokes stay in paint order. Each run of
+/// consecutive strokes from the same flow line group and layer is wrapped in a `<g>` element with
+/// `class` set to the layer name and `data-group` set to the group index. Since shadows and
+/// normal points are interleaved, a single flow line group may span many such elements.
+pub fn write_svg<W: Write>(recording: &Recording, canvas_width: i32, mut out: W) -> io::Result<()> {
+    let (width, height) = recording.dimensions(canvas_width);
+    let origin = recording.origin();
+    let scale = recording.scale_ratio(canvas_width);
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height,
+    )?;
+    writeln!(
+        out,
+        r#"<rect width="{}" height="{}" fill="{}"/>"#,
+        width,
+        height,
+        hex_color(recording.background.to_rgb().to_solid_source()),
+    )?;
+
+    let mut current_run: Option<(usize, Layer)> = None;
+    for stroke in &recording.strokes {
+        let run = (stroke.group, stroke.layer);
+        if current_run != Some(run) {
+            if current_run.is_some() {
+                writeln!(out, "</g>")?;
+            }
+            writeln!(
+                out,
+                r#"<g class="{}" data-group="{}" fill="none">"#,
+                stroke.layer.name(),
+                stroke.group,
+            )?;
+            current_run = Some(run);
+        }
+        write_ellipse(&mut out, stroke, origin, scale)?;
+    }
+    if current_run.is_some() {
+        writeln!(out, "</g>")?;
+    }
+
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
+
+fn write_ellipse<W: Write>(
+    out: &mut W,
+    stroke: &Stroke,
+    (ox, oy): (f64, f64),
+    scale: f64,
+) -> io::Result<()> {
+    let (x, y) = stroke.center;
+    writeln!(
+        out,
+        r#"<ellipse cx="{:.3}" cy="{:.3}" rx="{:.3}" ry="{:.3}" stroke="{}" stroke-width="{:.3}"/>"#,
+        (x - ox) * scale,
+        (y - oy) * scale,
+        // A negative radius traces the same ellipse in the raster path, but is invalid in SVG.
+        stroke.rx.abs() * scale,
+        stroke.ry.abs() * scale,
+        hex_color(stroke.color.to_rgb().to_solid_source()),
+        stroke.width * scale,
+    )
+}
+
+fn hex_color(c: raqote::SolidSource) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::art::Hsb;
+    use crate::config::FractionalViewport;
+
+    #[test]
+    fn test_write_svg_groups_runs() {
+        let stroke = |group, layer| Stroke {
+            center: (1000.0, 1250.0),
+            rx: 10.0,
+            ry: -20.0,
+            width: 2.0,
+            steps: 8.0,
+            color: Hsb(0.0, 100.0, 100.0),
+            color_key: 0,
+            layer,
+            group,
+        };
+        let recording = Recording {
+            background: Hsb(0.0, 0.0, 100.0),
+            viewport: FractionalViewport::default(),
+            strokes: vec![
+                stroke(0, Layer::Normal),
+                stroke(0, Layer::Normal),
+                stroke(1, Layer::Shadow),
+                stroke(1, Layer::Normal),
+            ],
+        };
+        let mut buf = Vec::new();
+        write_svg(&recording, 200, &mut buf).unwrap();
+        let svg = String::from_utf8(buf).unwrap();
+
+        assert!(svg.contains(r#"width="200" height="250""#));
+        assert!(svg.contains(r##"<rect width="200" height="250" fill="#ffffff"/>"##));
+        assert_eq!(svg.matches("<g ").count(), 3);
+        assert_eq!(svg.matches("</g>").count(), 3);
+        assert_eq!(svg.matches("<ellipse ").count(), 4);
+        assert!(svg.contains(
+            r##"<ellipse cx="100.000" cy="125.000" rx="1.000" ry="2.000" stroke="#ff0000" stroke-width="0.200"/>"##
+        ));
+    }
+}
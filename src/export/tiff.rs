@@ -0,0 +1,445 @@
+//! Tiled TIFF output, for canvases too large to composite in memory.
+//!
+//! A [`TiffWriter`] accepts the chunks of a render one at a time, in any order, and encodes each
+//! as whole tiles, so the full canvas never needs to exist at once. Chunk boundaries must fall on
+//! tile boundaries; see [`crate::art::draw_chunks`]. Files that may exceed 4 GiB are written as
+//! BigTIFF.
+//!
+//! Tiles are RGB with 8 bits per sample, Deflate-compressed with a horizontal predictor.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::straight_rgba;
+use crate::canvas::Canvas;
+
+/// Options for [`TiffWriter`].
+#[derive(Debug, Clone)]
+pub struct TiffOptions {
+    /// Width and height of each tile in pixels. Must be a positive multiple of 16.
+    pub tile_size: u32,
+    /// Horizontal and vertical resolution, in pixels per inch.
+    pub dpi: (f64, f64),
+    /// Stored in the `ImageDescription` tag.
+    pub description: String,
+    /// Stored in the `Software` tag.
+    pub software: String,
+    /// Use BigTIFF even if the file would fit in a classic TIFF.
+    pub force_bigtiff: bool,
+}
+
+impl Default for TiffOptions {
+    fn default() -> Self {
+        TiffOptions {
+            tile_size: 512,
+            dpi: (72.0, 72.0),
+            description: String::new(),
+            software: String::new(),
+            force_bigtiff: false,
+        }
+    }
+}
+
+const SAMPLES_PER_PIXEL: usize = 3;
+
+// Field types.
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const LONG8: u16 = 16;
+
+/// An IFD entry whose value has already been encoded as little-endian bytes.
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u64,
+    data: Vec<u8>,
+}
+
+impl Entry {
+    fn shorts(tag: u16, values: &[u16]) -> Self {
+        Entry {
+            tag,
+            field_type: SHORT,
+            count: values.len() as u64,
+            data: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+    fn long(tag: u16, value: u32) -> Self {
+        Entry {
+            tag,
+            field_type: LONG,
+            count: 1,
+            data: value.to_le_bytes().to_vec(),
+        }
+    }
+    fn rational(tag: u16, value: f64) -> Self {
+        const DENOMINATOR: u32 = 1000;
+        let numerator = (value * f64::from(DENOMINATOR)).round() as u32;
+        Entry {
+            tag,
+            field_type: RATIONAL,
+            count: 1,
+            data: [numerator, DENOMINATOR]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        }
+    }
+    fn ascii(tag: u16, value: &str) -> Self {
+        let mut data: Vec<u8> = value.bytes().filter(|&b| b != 0).collect();
+        data.push(0);
+        Entry {
+            tag,
+            field_type: ASCII,
+            count: data.len() as u64,
+            data,
+        }
+    }
+    /// Offsets or byte counts: `LONG` in classic TIFF, `LONG8` in BigTIFF.
+    fn offsets(tag: u16, values: &[u64], big: bool) -> Self {
+        let data = if big {
+            values.iter().flat_map(|v| v.to_le_bytes()).collect()
+        } else {
+            values
+                .iter()
+                .flat_map(|&v| (v as u32).to_le_bytes())
+                .collect()
+        };
+        Entry {
+            tag,
+            field_type: if big { LONG8 } else { LONG },
+            count: values.len() as u64,
+            data,
+        }
+    }
+}
+
+/// Writes a tiled TIFF one chunk at a time. Call [`TiffWriter::finish`] once all chunks have been
+/// written.
+pub struct TiffWriter<W> {
+    out: W,
+    /// Current byte offset in `out`, relative to the start of the file.
+    offset: u64,
+    big: bool,
+    width: u32,
+    height: u32,
+    opts: TiffOptions,
+    tiles_across: u32,
+    /// Offset and byte count of each tile in row-major order, once written.
+    tiles: Vec<Option<(u64, u64)>>,
+}
+
+impl<W: Write + Seek> TiffWriter<W> {
+    /// Starts a TIFF file for an image of the given size. The header is written immediately and
+    /// patched by [`TiffWriter::finish`].
+    pub fn new(mut out: W, width: u32, height: u32, opts: TiffOptions) -> io::Result<Self> {
+        let tile_size = opts.tile_size;
+        if tile_size == 0 || !tile_size.is_multiple_of(16) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "tile size must be a positive multiple of 16; got {}",
+                    tile_size
+                ),
+            ));
+        }
+        let tiles_across = width.div_ceil(tile_size);
+        let tiles_down = height.div_ceil(tile_size);
+        let num_tiles = u64::from(tiles_across) * u64::from(tiles_down);
+        // Leave generous room for incompressible tiles plus the IFD before deciding that 32-bit
+        // offsets suffice.
+        let raw_size = num_tiles * u64::from(tile_size).pow(2) * SAMPLES_PER_PIXEL as u64;
+        let big = opts.force_bigtiff
+            || raw_size + raw_size / 100 + (1 << 20) + num_tiles * 16 > u64::from(u32::MAX);
+
+        let header: &[u8] = if big {
+            // Byte order, version 43, offset size 8, reserved, first IFD offset (patched later).
+            &[b'I', b'I', 43, 0, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        } else {
+            &[b'I', b'I', 42, 0, 0, 0, 0, 0]
+        };
+        out.write_all(header)?;
+        Ok(TiffWriter {
+            out,
+            offset: header.len() as u64,
+            big,
+            width,
+            height,
+            opts,
+            tiles_across,
+            tiles: vec![None; num_tiles as usize],
+        })
+    }
+
+    /// Whether this file uses the BigTIFF format, with 64-bit offsets.
+    pub fn is_bigtiff(&self) -> bool {
+        self.big
+    }
+
+    /// Encodes a chunk whose top-left corner is at `(left, top)` in the image. The corner must lie
+    /// on a tile boundary, and the chunk must cover whole tiles except at the right and bottom
+    /// edges of the image. The chunk is assumed to be opaque; alpha is discarded.
+    pub fn write_chunk(&mut self, (left, top): (u32, u32), chunk: &Canvas) -> io::Result<()> {
+        let t = self.opts.tile_size;
+        let (right, bottom) = (
+            (left + chunk.width() as u32).min(self.width),
+            (top + chunk.height() as u32).min(self.height),
+        );
+        let aligned = |start: u32, end: u32, limit: u32| {
+            start.is_multiple_of(t) && (end.is_multiple_of(t) || end == limit) && start < end
+        };
+        if !aligned(left, right, self.width) || !aligned(top, bottom, self.height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "chunk {}x{}+{}+{} does not align to {}px tiles",
+                    chunk.width(),
+                    chunk.height(),
+                    left,
+                    top,
+                    t
+                ),
+            ));
+        }
+
+        let tiles: Vec<(u32, u32)> = (top / t..bottom.div_ceil(t))
+            .flat_map(|ty| (left / t..right.div_ceil(t)).map(move |tx| (tx, ty)))
+            .collect();
+        // Canvases are `!Sync`, so share just their pixels with the encoder threads.
+        let (data, stride) = (chunk.data(), chunk.width());
+        let encode = |&(tx, ty): &(u32, u32)| {
+            encode_tile(
+                data,
+                stride,
+                ((tx * t - left) as i32, (ty * t - top) as i32),
+                (right - left) as i32,
+                (bottom - top) as i32,
+                t as usize,
+            )
+        };
+        let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let per_thread = tiles.len().div_ceil(num_threads).max(1);
+        let encoded: Vec<Vec<u8>> = std::thread::scope(|s| {
+            let handles: Vec<_> = tiles
+                .chunks(per_thread)
+                .map(|batch| s.spawn(move || batch.iter().map(encode).collect::<Vec<_>>()))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("tile encoder panicked"))
+                .collect()
+        });
+
+        for ((tx, ty), data) in tiles.into_iter().zip(encoded) {
+            let index = (ty * self.tiles_across + tx) as usize;
+            self.tiles[index] = Some((self.offset, data.len() as u64));
+            self.write_raw(&data)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the image directory and returns the underlying writer. Fails if any tile is missing.
+    pub fn finish(mut self) -> io::Result<W> {
+        let missing = self.tiles.iter().filter(|t| t.is_none()).count();
+        if missing > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} of {} tiles were never written",
+                    missing,
+                    self.tiles.len()
+                ),
+            ));
+        }
+        let (offsets, byte_counts): (Vec<u64>, Vec<u64>) =
+            self.tiles.iter().flatten().copied().unzip();
+        let t = self.opts.tile_size;
+        // Entries must be sorted by tag.
+        let mut entries = vec![
+            Entry::long(256, self.width),                    // ImageWidth
+            Entry::long(257, self.height),                   // ImageLength
+            Entry::shorts(258, &[8; SAMPLES_PER_PIXEL]),     // BitsPerSample
+            Entry::shorts(259, &[8]),                        // Compression: Adobe Deflate
+            Entry::shorts(262, &[2]),                        // PhotometricInterpretation: RGB
+            Entry::shorts(277, &[SAMPLES_PER_PIXEL as u16]), // SamplesPerPixel
+            Entry::rational(282, self.opts.dpi.0),           // XResolution
+            Entry::rational(283, self.opts.dpi.1),           // YResolution
+            Entry::shorts(284, &[1]),                        // PlanarConfiguration: chunky
+            Entry::shorts(296, &[2]),                        // ResolutionUnit: inch
+            Entry::shorts(317, &[2]),                        // Predictor: horizontal differencing
+            Entry::long(322, t),                             // TileWidth
+            Entry::long(323, t),                             // TileLength
+            Entry::offsets(324, &offsets, self.big),         // TileOffsets
+            Entry::offsets(325, &byte_counts, self.big),     // TileByteCounts
+        ];
+        if !self.opts.description.is_empty() {
+            entries.push(Entry::ascii(270, &self.opts.description)); // ImageDescription
+        }
+        if !self.opts.software.is_empty() {
+            entries.push(Entry::ascii(305, &self.opts.software)); // Software
+        }
+        entries.sort_by_key(|e| e.tag);
+
+        // Values that don't fit in an entry go before the directory.
+        let inline_size = if self.big { 8 } else { 4 };
+        let mut value_offsets = Vec::with_capacity(entries.len());
+        for entry in &entries {
+            if entry.data.len() <= inline_size {
+                value_offsets.push(None);
+                continue;
+            }
+            self.align()?;
+            value_offsets.push(Some(self.offset));
+            self.write_raw(&entry.data)?;
+        }
+
+        self.align()?;
+        let ifd_offset = self.offset;
+        let mut ifd = Vec::new();
+        if self.big {
+            ifd.extend((entries.len() as u64).to_le_bytes());
+        } else {
+            ifd.extend((entries.len() as u16).to_le_bytes());
+        }
+        for (entry, value_offset) in entries.iter().zip(value_offsets) {
+            ifd.extend(entry.tag.to_le_bytes());
+            ifd.extend(entry.field_type.to_le_bytes());
+            let mut value = match value_offset {
+                Some(offset) if self.big => offset.to_le_bytes().to_vec(),
+                Some(offset) => (offset as u32).to_le_bytes().to_vec(),
+                None => entry.data.clone(),
+            };
+            value.resize(inline_size, 0);
+            if self.big {
+                ifd.extend(entry.count.to_le_bytes());
+            } else {
+                ifd.extend((entry.count as u32).to_le_bytes());
+            }
+            ifd.extend(value);
+        }
+        ifd.extend(vec![0; inline_size]); // no next IFD
+        self.write_raw(&ifd)?;
+
+        if self.big {
+            self.out.seek(SeekFrom::Start(8))?;
+            self.out.write_all(&ifd_offset.to_le_bytes())?;
+        } else {
+            self.out.seek(SeekFrom::Start(4))?;
+            self.out.write_all(&(ifd_offset as u32).to_le_bytes())?;
+        }
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Pads to a word boundary, as TIFF requires for offsets.
+    fn align(&mut self) -> io::Result<()> {
+        if !self.offset.is_multiple_of(2) {
+            self.write_raw(&[0])?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes the `size`-by-`size` tile whose top-left corner is at `(x, y)` in a buffer of raqote
+/// pixels with the given row stride, treating pixels at or beyond `width`/`height` as padding.
+fn encode_tile(
+    data: &[u32],
+    stride: i32,
+    (x, y): (i32, i32),
+    width: i32,
+    height: i32,
+    size: usize,
+) -> Vec<u8> {
+    let row_len = size * SAMPLES_PER_PIXEL;
+    let mut raw = vec![0u8; row_len * size];
+    for (row, out) in raw.chunks_exact_mut(row_len).enumerate() {
+        let src_y = y + row as i32;
+        if src_y >= height {
+            break;
+        }
+        let start = (src_y * stride + x) as usize;
+        let n = (width - x).clamp(0, size as i32) as usize;
+        for (&px, out) in data[start..start + n]
+            .iter()
+            .zip(out.chunks_exact_mut(SAMPLES_PER_PIXEL))
+        {
+            let [r, g, b, _] = straight_rgba(px);
+            out.copy_from_slice(&[r, g, b]);
+        }
+        // Horizontal differencing predictor, applied back to front.
+        for i in (SAMPLES_PER_PIXEL..row_len).rev() {
+            out[i] = out[i].wrapping_sub(out[i - SAMPLES_PER_PIXEL]);
+        }
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&raw)
+        .expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use raqote::SolidSource;
+
+    fn write_test_image(force_bigtiff: bool) -> Vec<u8> {
+        let opts = TiffOptions {
+            tile_size: 16,
+            dpi: (300.0, 150.5),
+            description: "hello".to_string(),
+            force_bigtiff,
+            ..TiffOptions::default()
+        };
+        let mut writer = TiffWriter::new(io::Cursor::new(Vec::new()), 40, 20, opts).unwrap();
+        assert_eq!(writer.is_bigtiff(), force_bigtiff);
+        // Two chunks, written right one first: a red 32px-wide one and a blue 8px-wide one.
+        let mut right = Canvas::new(8, 20);
+        right
+            .draw_target_mut()
+            .clear(SolidSource::from_unpremultiplied_argb(255, 0, 0, 255));
+        writer.write_chunk((32, 0), &right).unwrap();
+        let mut left = Canvas::new(32, 20);
+        let dt = left.draw_target_mut();
+        dt.clear(SolidSource::from_unpremultiplied_argb(255, 255, 0, 0));
+        dt.get_data_mut()[20] = 0xff123456;
+        writer.write_chunk((0, 0), &left).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_write_tiff_roundtrip() {
+        for force_bigtiff in [false, true] {
+            let buf = write_test_image(force_bigtiff);
+            let img = image::load_from_memory_with_format(&buf, image::ImageFormat::Tiff)
+                .unwrap()
+                .into_rgb8();
+            assert_eq!(img.dimensions(), (40, 20));
+            assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0]);
+            assert_eq!(img.get_pixel(20, 0).0, [0x12, 0x34, 0x56]);
+            assert_eq!(img.get_pixel(31, 19).0, [255, 0, 0]);
+            assert_eq!(img.get_pixel(32, 0).0, [0, 0, 255]);
+            assert_eq!(img.get_pixel(39, 19).0, [0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn test_write_chunk_rejects_misaligned() {
+        let mut writer =
+            TiffWriter::new(io::Cursor::new(Vec::new()), 40, 20, TiffOptions::default()).unwrap();
+        assert!(writer.write_chunk((8, 0), &Canvas::new(8, 8)).is_err());
+        assert!(writer.finish().is_err(), "no tiles were written");
+    }
+}
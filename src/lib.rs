@@ -1,10 +1,22 @@
 pub mod art;
+pub mod canvas;
+pub mod canvas16;
+pub mod catalog;
 pub mod color;
 pub mod config;
+pub mod error;
+pub mod export;
 pub mod layouts;
 pub mod math;
+pub mod progress;
 pub mod rand;
+pub mod rarity;
+pub mod search;
 pub mod sectors;
+pub mod seed;
+pub mod token;
 pub mod traits;
 
+pub use error::{Error, Result};
+
 pub fn unit() {}
@@ -205,14 +205,15 @@ mod tests {
 
     #[test]
     fn test_sin_cos() {
-        const TEST_CASES: &'static [(f64, f64, f64)] = &[
+        use std::f64::consts::{FRAC_PI_2, PI, TAU};
+        const TEST_CASES: &[(f64, f64, f64)] = &[
             (0.0, 0.0, 1.0),
             (0.1, 0.09972839720069836, 0.9935973557943562),
             (1.0, 0.8403747950252756, 0.5395579585710483),
-            (1.5707963267948966, 0.9984625, 0.00003250000000000475),
+            (FRAC_PI_2, 0.9984625, 0.00003250000000000475),
             (2.0, 0.9074940439786922, -0.41534209884358286),
-            (3.141592653589793, 0.0, -0.99795),
-            (6.283185307179586, 0.0, 1.0),
+            (PI, 0.0, -0.99795),
+            (TAU, 0.0, 1.0),
             (10.0, -0.5439582041429563, -0.8389752174069942),
             (-0.1, -0.09972839720069898, 0.993597355794356),
             (-1.0, -0.8403747950252756, 0.5395579585710483),
@@ -240,7 +241,7 @@ mod tests {
 
     #[test]
     fn test_sqrt() {
-        const TEST_CASES: &'static [(f64, f64)] = &[
+        const TEST_CASES: &[(f64, f64)] = &[
             (0.0, 0.0),
             (0.1, 0.3162277665175675),
             (1.0, 1.0),
@@ -268,7 +269,7 @@ mod tests {
             d: f64,
             ub: f64,
         }
-        const TEST_CASES: &'static [TestCase] = &[
+        const TEST_CASES: &[TestCase] = &[
             TestCase {
                 points: ((0.0, 0.0), (3.0, 4.0)),
                 lb: 4.82072072072072,
@@ -299,6 +300,8 @@ mod tests {
                     p1, p2, actual_lb, actual_d, actual_ub, lb, d, ub
                 );
             }
+            // Negated comparisons so that NaNs also count as bad bounds.
+            #[allow(clippy::neg_cmp_op_on_partial_ord)]
             if !(actual_ub >= actual_d) || !(actual_lb <= actual_d) {
                 panic!(
                     "{:?} ~> {:?}: bad bounds: {} .. {} .. {}",
@@ -314,7 +317,7 @@ mod tests {
             points: ((f64, f64), (f64, f64)),
             angle: f64,
         }
-        const TEST_CASES: &'static [TestCase] = &[
+        const TEST_CASES: &[TestCase] = &[
             TestCase {
                 points: ((1.0, 2.0), (3.0, 5.0)),
                 angle: 0.9827989414909313,
@@ -0,0 +1,120 @@
+//! Progress reporting for long-running renders.
+//!
+//! The library doesn't print anything itself. Instead, the `try_` functions in [`crate::art`]
+//! report what they're doing to an [`Observer`], which can log, drive a progress bar, or ignore
+//! events with [`Silent`]. The functions that panic instead of returning errors report nothing.
+
+use std::time::{Duration, Instant};
+
+/// A phase of laying out or painting a piece, in the order that they run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Reading traits from the seed and picking the color scheme and other parameters.
+    Traits,
+    /// Building the flow field and tracing flow lines through it.
+    FlowField,
+    /// Placing points along the flow lines.
+    Points,
+    /// Painting (or recording) the points, including all frames of an animation.
+    Paint,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Traits => "traits",
+            Stage::FlowField => "flow field",
+            Stage::Points => "points",
+            Stage::Paint => "paint",
+        }
+    }
+}
+
+/// Something that happened during a render, as reported to an [`Observer`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// A stage started.
+    StageStarted(Stage),
+    /// A stage finished, after `elapsed`. Not reported for a stage that fails.
+    StageFinished { stage: Stage, elapsed: Duration },
+    /// Chunk `(column, row)` of the `config.chunks` grid started painting. It covers
+    /// `width`x`height` pixels with its top-left corner at `(left, top)` on the canvas.
+    ChunkStarted {
+        column: u32,
+        row: u32,
+        left: i32,
+        top: i32,
+        width: i32,
+        height: i32,
+    },
+    /// Chunk `(column, row)` finished painting, after `elapsed`.
+    ChunkFinished {
+        column: u32,
+        row: u32,
+        elapsed: Duration,
+    },
+    /// `painted` of the `total` points in the paint stage have been painted. Every chunk paints
+    /// every point, so with several chunks, `total` counts each point once per chunk. Reported
+    /// every so often rather than for each point, and possibly from several threads at once.
+    PointsPainted { painted: usize, total: usize },
+    /// A frame was handed to the caller, `elapsed` after the paint stage started. `number` is as
+    /// for [`crate::art::Frame::number`].
+    FrameEmitted {
+        number: Option<u32>,
+        elapsed: Duration,
+    },
+    /// Something noteworthy that isn't a failure, like an export retrying with other settings.
+    Note(String),
+}
+
+/// Receives [`Event`]s as a render progresses. Events may arrive from worker threads.
+///
+/// Closures taking an `&Event` are observers, too.
+pub trait Observer: Sync {
+    fn on_event(&self, event: &Event);
+}
+
+impl<F: Fn(&Event) + Sync> Observer for F {
+    fn on_event(&self, event: &Event) {
+        self(event)
+    }
+}
+
+/// An observer that ignores all events.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Silent;
+
+impl Observer for Silent {
+    fn on_event(&self, _event: &Event) {}
+}
+
+/// Reports the start of a stage, and then its end with timing when [`StageTimer::finish`] is
+/// called.
+pub(crate) struct StageTimer<'a> {
+    observer: &'a dyn Observer,
+    stage: Stage,
+    start: Instant,
+}
+
+impl<'a> StageTimer<'a> {
+    pub(crate) fn start(observer: &'a dyn Observer, stage: Stage) -> Self {
+        observer.on_event(&Event::StageStarted(stage));
+        StageTimer {
+            observer,
+            stage,
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub(crate) fn finish(self) {
+        self.observer.on_event(&Event::StageFinished {
+            stage: self.stage,
+            elapsed: self.elapsed(),
+        });
+    }
+}
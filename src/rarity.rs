@@ -0,0 +1,260 @@
+//! Exact trait probabilities, from the bit layout that [`Traits::from_seed`] reads.
+//!
+//! The weights listed in [`crate::traits`] describe the intended distribution, but decoding
+//! doesn't use them: each trait takes just enough bits to index its options and wraps around with
+//! `raw % num_options`. So when the number of options isn't a power of two, the first few options
+//! are more likely than the rest. For instance, three options read from two bits come out 50%,
+//! 25%, and 25%. Probabilities here assume uniformly random trait bits. Traits are read from
+//! disjoint bits, so they're independent, and a combination's probability is the product of its
+//! parts.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::seed::TRAIT_BYTES;
+use crate::traits::{TraitBits, Traits};
+
+/// The probability of each option of one trait.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitDistribution {
+    /// Name of the trait, as in [`TraitBits::name`].
+    pub name: &'static str,
+    pub bits: Range<u32>,
+    /// Options in index order.
+    pub options: Vec<OptionProbability>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionProbability {
+    pub value: String,
+    /// How many raw values of the trait's bits select this option.
+    pub raw_values: u32,
+    pub probability: f64,
+}
+
+/// The probability of one trait value of a seed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitRarity {
+    pub name: &'static str,
+    pub value: String,
+    pub probability: f64,
+}
+
+/// The rarity of a seed's full combination of traits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedRarity {
+    pub traits: Vec<TraitRarity>,
+    /// Probability that a seed with random trait bits has all of these traits.
+    pub probability: f64,
+    /// `-log2(probability)`: the number of random bits that this combination is worth. Higher is
+    /// rarer, and scores of different traits add up.
+    pub score: f64,
+}
+
+impl SeedRarity {
+    pub fn new(seed: &[u8; 32]) -> Self {
+        let (_, trait_bits) = Traits::decode(seed);
+        let traits: Vec<TraitRarity> = trait_bits
+            .into_iter()
+            .map(|bits| TraitRarity {
+                probability: probability(&bits.bits, bits.num_options, bits.index),
+                name: bits.name,
+                value: bits.value,
+            })
+            .collect();
+        let probability = traits.iter().map(|t| t.probability).product();
+        SeedRarity {
+            traits,
+            probability,
+            score: score(probability),
+        }
+    }
+}
+
+/// Rarity score of a probability. See [`SeedRarity::score`].
+pub fn score(probability: f64) -> f64 {
+    -probability.log2()
+}
+
+/// Probability that a trait read from `bits` selects option `index` of `num_options`.
+pub fn probability(bits: &Range<u32>, num_options: usize, index: usize) -> f64 {
+    let num_raw = 1u32 << (bits.end - bits.start);
+    f64::from(raw_values(num_raw, num_options, index)) / f64::from(num_raw)
+}
+
+/// How many of the raw values `0..num_raw` are congruent to `index` mod `num_options`.
+fn raw_values(num_raw: u32, num_options: usize, index: usize) -> u32 {
+    let (num_options, index) = (num_options as u32, index as u32);
+    if index >= num_raw {
+        return 0;
+    }
+    (num_raw - 1 - index) / num_options + 1
+}
+
+/// The distribution of every trait read from the trait bits, in the order they're read.
+pub fn distributions() -> Vec<TraitDistribution> {
+    // The layout doesn't depend on the seed. To name each option, decode a seed with just that
+    // option's bits set.
+    let (_, layout) = Traits::decode(&[0; 32]);
+    layout.into_iter().map(distribution).collect()
+}
+
+fn distribution(layout: TraitBits) -> TraitDistribution {
+    let TraitBits {
+        name,
+        bits,
+        num_options,
+        ..
+    } = layout;
+    let num_raw = 1 << (bits.end - bits.start);
+    let options = (0..num_options)
+        .map(|index| {
+            let mut seed = [0; 32];
+            let field = (index as u32) << bits.start;
+            seed[TRAIT_BYTES].copy_from_slice(&field.to_be_bytes());
+            let (_, decoded) = Traits::decode(&seed);
+            let value = decoded
+                .into_iter()
+                .find(|b| b.name == name)
+                .expect("same layout")
+                .value;
+            OptionProbability {
+                value,
+                raw_values: raw_values(num_raw, num_options, index),
+                probability: probability(&bits, num_options, index),
+            }
+        })
+        .collect();
+    TraitDistribution {
+        name,
+        bits,
+        options,
+    }
+}
+
+/// Probability that a seed with random trait bits has all of the given traits, as
+/// `(name, value)` pairs accepted by [`Traits::set`]. Traits not mentioned can be anything. A
+/// trait given more than once must match every value given, so conflicting values have
+/// probability 0. The version isn't read from the trait bits, so it can't be constrained.
+pub fn combination_probability(traits: &[(&str, &str)]) -> anyhow::Result<f64> {
+    let (base, layout) = Traits::decode(&[0; 32]);
+    // The option index that each constrained field of the trait bits must have.
+    let mut required: HashMap<&str, usize> = HashMap::new();
+    let mut disjoint = false;
+    for &(name, value) in traits {
+        let name = name.replace('-', "_");
+        if name == "version" {
+            anyhow::bail!("The version isn't part of the trait bits, so it has no probability");
+        }
+        let mut target = base.clone();
+        target.set(&name, value)?;
+        for (bits, (_, index)) in layout.iter().zip(target.option_indices()) {
+            let constrained = if name == "bullseye_rings" {
+                bits.name.starts_with("bullseye_rings.")
+            } else {
+                bits.name == name
+            };
+            if constrained && *required.entry(bits.name).or_insert(index) != index {
+                disjoint = true;
+            }
+        }
+    }
+    if disjoint {
+        return Ok(0.0);
+    }
+    Ok(layout
+        .iter()
+        .filter_map(|bits| {
+            let &index = required.get(bits.name)?;
+            Some(probability(&bits.bits, bits.num_options, index))
+        })
+        .product())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn test_distributions() {
+        let dists = distributions();
+        for dist in &dists {
+            let total: f64 = dist.options.iter().map(|o| o.probability).sum();
+            assert_eq!(total, 1.0, "{}", dist.name);
+        }
+        let probs = |name: &str| -> Vec<(String, f64)> {
+            let dist = dists.iter().find(|d| d.name == name).unwrap();
+            dist.options
+                .iter()
+                .map(|o| (o.value.clone(), o.probability))
+                .collect()
+        };
+        // Listed with weight 0, but it takes two of the four raw values.
+        assert_eq!(
+            probs("turbulence"),
+            vec![
+                ("None".to_string(), 0.5),
+                ("Low".to_string(), 0.25),
+                ("High".to_string(), 0.25)
+            ]
+        );
+        let palettes = probs("color_palette");
+        assert_eq!(palettes[0], ("Austin".to_string(), 0.25));
+        assert_eq!(palettes[6], ("Seoul".to_string(), 0.125));
+        assert!(probs("flow_field").iter().all(|(_, p)| *p == 0.125));
+    }
+
+    #[test]
+    fn test_seed_rarity() {
+        let seed = hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let rarity = SeedRarity::new(&seed);
+        let product: f64 = rarity.traits.iter().map(|t| t.probability).product();
+        assert_eq!(rarity.probability, product);
+        let fidenza = rarity
+            .traits
+            .iter()
+            .find(|t| t.name == "color_palette")
+            .unwrap();
+        assert_eq!(
+            (fidenza.value.as_str(), fidenza.probability),
+            ("Fidenza", 0.125)
+        );
+        // Circular (1/8), None (1/2), Wide (1/4), High (1/4), Stacked (1/4), Formation (1/4),
+        // three rings (1/2 each), Thick (1/4), Medium (1/4), Constant (1/2), Fidenza (1/8), and
+        // Sparse (1/4).
+        assert_eq!(rarity.score, 27.0 - 2.0);
+    }
+
+    #[test]
+    fn test_combination_probability() {
+        let p = combination_probability(&[
+            ("flow_field", "spiral"),
+            ("color_palette", "seoul"),
+            ("turbulence", "none"),
+        ])
+        .unwrap();
+        assert_eq!(p, 0.125 * 0.125 * 0.5);
+        assert_eq!(
+            combination_probability(&[("bullseye_rings", "1,7")]).unwrap(),
+            0.125
+        );
+        assert_eq!(combination_probability(&[]).unwrap(), 1.0);
+        // Repeated traits intersect, rather than the last one winning.
+        assert_eq!(
+            combination_probability(&[("flow_field", "spiral"), ("flow_field", "spiral")]).unwrap(),
+            0.125
+        );
+        assert_eq!(
+            combination_probability(&[("flow_field", "spiral"), ("flow_field", "horizontal")])
+                .unwrap(),
+            0.0
+        );
+        assert_eq!(
+            combination_probability(&[("bullseye_rings", "1,7"), ("bullseye_rings", "1,3")])
+                .unwrap(),
+            0.0
+        );
+        assert!(combination_probability(&[("version", "v1")]).is_err());
+    }
+}
@@ -0,0 +1,577 @@
+//! Searching the seeds that an address can mint for ones with given traits.
+//!
+//! Any seed that starts with the minter's address is valid, so an address can mint seeds with any
+//! nonce and any trait bits. [`search`] enumerates V1 seeds for an address, trying every value of
+//! the trait bits for one nonce before moving to the next, and tests each candidate in parallel.
+//! Seeds whose traits match can then be laid out and filtered by how they turn out, with an
+//! [`OutcomeFilter`].
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::art::RenderData;
+use crate::color::{ColorDb, ColorKey};
+use crate::seed::{NONCE_BYTES, SENTINEL, SENTINEL_BYTES, TRAIT_BYTES, VERSION_BITS};
+use crate::traits::{TraitBits, Traits};
+
+/// Number of distinct trait bit patterns for each nonce: every trait bit, plus the unused bit
+/// below the version nibble.
+const TRAIT_SPACE: u64 = 1 << VERSION_BITS.start;
+
+/// Number of candidates that a worker claims at a time.
+const BLOCK_SIZE: u64 = 1 << 14;
+
+/// Constraints on traits, as a set of allowed options for each trait.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraitFilter {
+    /// For each trait in decoding order, a bitmask of allowed option indices.
+    allowed: Vec<(&'static str, u64)>,
+}
+
+impl Default for TraitFilter {
+    /// A filter that allows all traits.
+    fn default() -> Self {
+        let (_, layout) = Traits::decode(&[0; 32]);
+        TraitFilter {
+            allowed: layout.iter().map(|b| (b.name, u64::MAX)).collect(),
+        }
+    }
+}
+
+impl TraitFilter {
+    /// Restricts a trait to any of `values`. Names and values are as accepted by [`Traits::set`],
+    /// and `bullseye_rings` restricts all three rings. Restricting a trait again keeps only the
+    /// values allowed by both.
+    pub fn restrict(&mut self, name: &str, values: &[&str]) -> anyhow::Result<()> {
+        let name = name.replace('-', "_");
+        if name == "version" {
+            anyhow::bail!("Searched seeds are always V1, so the version can't be constrained");
+        }
+        let (mut scratch, layout) = Traits::decode(&[0; 32]);
+        let in_trait = |field: &str| {
+            field == name || (name == "bullseye_rings" && field.starts_with("bullseye_rings."))
+        };
+        let fields: Vec<usize> = layout
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| in_trait(b.name))
+            .map(|(i, _)| i)
+            .collect();
+        if fields.is_empty() {
+            anyhow::bail!("Unknown trait {:?}", name);
+        }
+        if name == "bullseye_rings" && values.len() != 1 {
+            anyhow::bail!(
+                "bullseye_rings takes a single list of ring sizes; constrain bullseye_rings.one, \
+                 bullseye_rings.three, and bullseye_rings.seven separately instead"
+            );
+        }
+        let mut masks = vec![0u64; layout.len()];
+        for value in values {
+            scratch.set(&name, value)?;
+            let indices = scratch.option_indices();
+            for &i in &fields {
+                masks[i] |= 1 << indices[i].1;
+            }
+        }
+        for &i in &fields {
+            self.allowed[i].1 &= masks[i];
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, traits: &Traits) -> bool {
+        traits
+            .option_indices()
+            .iter()
+            .enumerate()
+            .all(|(field, &(_, index))| self.allows(field, index))
+    }
+
+    /// Whether the `field`th trait in decoding order (see [`Traits::decode`]) may take its
+    /// `index`th option.
+    pub fn allows(&self, field: usize, index: usize) -> bool {
+        self.allowed[field].1 & (1 << index) != 0
+    }
+
+    /// Whether no traits can match, because some trait has no allowed options.
+    pub fn is_empty(&self) -> bool {
+        let (_, layout) = Traits::decode(&[0; 32]);
+        layout
+            .iter()
+            .zip(&self.allowed)
+            .any(|(TraitBits { num_options, .. }, &(_, mask))| {
+                mask & (u64::MAX >> (64 - num_options)) == 0
+            })
+    }
+}
+
+/// Parses whitespace-separated constraints, like
+/// `flow_field=spiral spacing=dense palette in [seoul,fidenza]`. Each constraint is either
+/// `TRAIT=VALUE`, `TRAIT=VALUE,VALUE,...`, or `TRAIT in [VALUE,VALUE,...]`. `palette` is short
+/// for `color_palette`.
+impl FromStr for TraitFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = TraitFilter::default();
+        let mut rest = s.trim_start();
+        while !rest.is_empty() {
+            let name_end = rest
+                .find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let (name, after_name) = rest.split_at(name_end);
+            let name = match name {
+                "palette" => "color_palette",
+                name => name,
+            };
+            let after_name = after_name.trim_start();
+            let (values, after_values) = if let Some(after) = after_name.strip_prefix('=') {
+                let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            } else if let Some(after) = after_name.strip_prefix("in") {
+                let after = after.trim_start();
+                let after = after
+                    .strip_prefix('[')
+                    .ok_or_else(|| anyhow::anyhow!("Expected [ after {} in", name))?;
+                let end = after
+                    .find(']')
+                    .ok_or_else(|| anyhow::anyhow!("Missing ] after {} in [", name))?;
+                (&after[..end], &after[end + 1..])
+            } else {
+                anyhow::bail!(
+                    "Invalid constraint on {:?}; expected TRAIT=VALUE or TRAIT in [VALUE,...]",
+                    name
+                );
+            };
+            let values: Vec<&str> = if name == "bullseye_rings" {
+                vec![values]
+            } else {
+                values.split(',').map(str::trim).collect()
+            };
+            filter.restrict(name, &values)?;
+            rest = after_values.trim_start();
+        }
+        Ok(filter)
+    }
+}
+
+/// Where a [`search`] starts and how far it goes.
+#[derive(Debug, Clone)]
+pub struct SearchSpace {
+    pub address: [u8; 20],
+    /// The first nonce to try, as a big-endian integer. Later nonces count up from here.
+    pub start_nonce: u64,
+    /// How many nonces to try before giving up.
+    pub num_nonces: u64,
+}
+
+impl SearchSpace {
+    /// Number of candidate seeds in this space.
+    pub fn len(&self) -> u64 {
+        self.num_nonces.saturating_mul(TRAIT_SPACE)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `index`th candidate: a V1 seed with the `index / 2^28`th nonce and the trait bits
+    /// `index % 2^28`.
+    pub fn candidate(&self, index: u64) -> [u8; 32] {
+        let nonce = self.start_nonce.wrapping_add(index / TRAIT_SPACE) & ((1 << 48) - 1);
+        let trait_field = (1 << VERSION_BITS.start) | (index % TRAIT_SPACE) as u32;
+        let mut seed = [0; 32];
+        seed[..20].copy_from_slice(&self.address);
+        seed[NONCE_BYTES].copy_from_slice(&nonce.to_be_bytes()[2..]);
+        seed[SENTINEL_BYTES].copy_from_slice(&SENTINEL);
+        seed[TRAIT_BYTES].copy_from_slice(&trait_field.to_be_bytes());
+        seed
+    }
+}
+
+/// The result of a [`search`].
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Matching seeds, in search order.
+    pub seeds: Vec<[u8; 32]>,
+    /// How many candidates were checked.
+    pub num_candidates: u64,
+    /// How many candidates matched the trait filter and were passed to `accept`.
+    pub num_accept_calls: u64,
+}
+
+/// Finds the first `limit` seeds in `space` whose traits match `filter` and that `accept`
+/// approves. `accept` is only called for seeds whose traits match, so it can do more expensive
+/// checks, like [`OutcomeFilter::matches`] on the result of [`crate::art::analyze`]. Candidates
+/// are checked on all cores, but the result is the same as in a sequential search.
+pub fn search<F>(space: &SearchSpace, filter: &TraitFilter, limit: usize, accept: F) -> SearchResult
+where
+    F: Fn(&[u8; 32]) -> bool + Sync,
+{
+    let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut result = SearchResult {
+        seeds: Vec::new(),
+        num_candidates: 0,
+        num_accept_calls: 0,
+    };
+    if filter.is_empty() {
+        return result;
+    }
+    // Work in rounds: find the next few seeds whose traits match, then run `accept` on them. Each
+    // round asks for at least as many seeds as are still needed, and enough to keep every core
+    // busy when `accept` is slow.
+    while result.seeds.len() < limit && result.num_candidates < space.len() {
+        let needed = limit - result.seeds.len();
+        let (candidates, next) = scan(
+            space,
+            result.num_candidates,
+            filter,
+            needed.max(4 * num_threads),
+        );
+        let (accepted, num_calls) = accept_in_order(&candidates, needed, num_threads, &accept);
+        result.seeds.extend(accepted);
+        result.num_candidates = next;
+        result.num_accept_calls += num_calls;
+    }
+    result
+}
+
+/// Finds the first `limit` candidates at or after index `start` whose traits match `filter`.
+/// Returns them with the index to resume scanning from.
+fn scan(
+    space: &SearchSpace,
+    start: u64,
+    filter: &TraitFilter,
+    limit: usize,
+) -> (Vec<[u8; 32]>, u64) {
+    let num_blocks = (space.len() - start).div_ceil(BLOCK_SIZE);
+    let next_block = AtomicU64::new(0);
+    let done = AtomicBool::new(limit == 0);
+    let matches: Mutex<Vec<(u64, [u8; 32])>> = Mutex::new(Vec::new());
+
+    let scan_blocks = || {
+        while !done.load(Ordering::Relaxed) {
+            let block = next_block.fetch_add(1, Ordering::Relaxed);
+            if block >= num_blocks {
+                break;
+            }
+            let block_start = start + block * BLOCK_SIZE;
+            let block_end = (block_start + BLOCK_SIZE).min(space.len());
+            let mut found = Vec::new();
+            for index in block_start..block_end {
+                let seed = space.candidate(index);
+                if filter.matches(&Traits::from_seed(&seed)) {
+                    found.push((index, seed));
+                }
+            }
+            if !found.is_empty() {
+                let mut matches = matches.lock().unwrap();
+                matches.extend(found);
+                if matches.len() >= limit {
+                    // Blocks are claimed in order, so once the blocks in progress finish, every
+                    // block before them is done, and the first matches are all known.
+                    done.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    };
+    let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    std::thread::scope(|s| {
+        for _ in 0..num_threads {
+            s.spawn(scan_blocks);
+        }
+    });
+
+    let mut matches = matches.into_inner().unwrap();
+    matches.sort_by_key(|&(index, _)| index);
+    matches.truncate(limit);
+    let next = match matches.last() {
+        Some(&(index, _)) if matches.len() == limit => index + 1,
+        _ => (start + next_block.into_inner().min(num_blocks) * BLOCK_SIZE).min(space.len()),
+    };
+    (matches.into_iter().map(|(_, seed)| seed).collect(), next)
+}
+
+/// Runs `accept` on `candidates` on all cores, until the first `limit` accepted candidates are
+/// known. Returns those, in order, and how many times `accept` was called.
+fn accept_in_order<F>(
+    candidates: &[[u8; 32]],
+    limit: usize,
+    num_threads: usize,
+    accept: &F,
+) -> (Vec<[u8; 32]>, u64)
+where
+    F: Fn(&[u8; 32]) -> bool + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let num_accepted = AtomicUsize::new(0);
+    let results: Mutex<Vec<(usize, bool)>> = Mutex::new(Vec::new());
+    let accept_next = || {
+        // Candidates are claimed in order, so once `limit` are accepted, every unclaimed
+        // candidate comes after them.
+        while num_accepted.load(Ordering::Relaxed) < limit {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(seed) = candidates.get(i) else {
+                break;
+            };
+            let accepted = accept(seed);
+            if accepted {
+                num_accepted.fetch_add(1, Ordering::Relaxed);
+            }
+            results.lock().unwrap().push((i, accepted));
+        }
+    };
+    std::thread::scope(|s| {
+        for _ in 0..num_threads.min(candidates.len()) {
+            s.spawn(accept_next);
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    let num_calls = results.len() as u64;
+    results.sort_unstable();
+    let accepted = results
+        .into_iter()
+        .filter(|&(_, accepted)| accepted)
+        .take(limit)
+        .map(|(i, _)| candidates[i])
+        .collect();
+    (accepted, num_calls)
+}
+
+/// A range of counts, like `10..20`, `10..=19`, `10..`, `..20`, or just `15`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CountRange {
+    pub min: usize,
+    /// Inclusive upper bound, if any.
+    pub max: Option<usize>,
+}
+
+impl CountRange {
+    pub fn contains(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl FromStr for CountRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| -> anyhow::Result<usize> {
+            n.trim()
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid count {:?}: {}", n, e))
+        };
+        let Some((min, max)) = s.split_once("..") else {
+            let n = parse(s)?;
+            return Ok(CountRange {
+                min: n,
+                max: Some(n),
+            });
+        };
+        let min = if min.trim().is_empty() {
+            0
+        } else {
+            parse(min)?
+        };
+        let max = if let Some(max) = max.strip_prefix('=') {
+            Some(parse(max)?)
+        } else if max.trim().is_empty() {
+            None
+        } else {
+            match parse(max)?.checked_sub(1) {
+                Some(max) => Some(max),
+                None => anyhow::bail!("Empty range {:?}", s),
+            }
+        };
+        Ok(CountRange { min, max })
+    }
+}
+
+/// Constraints on how a piece lays out, as reported in [`RenderData`] by
+/// [`crate::art::analyze`].
+#[derive(Debug, Clone, Default)]
+pub struct OutcomeFilter {
+    pub num_points: Option<CountRange>,
+    /// Colors that must be used.
+    pub required_colors: Vec<ColorKey>,
+    /// Colors that must not be used.
+    pub forbidden_colors: Vec<ColorKey>,
+    /// For each ring count, the allowed number of points drawn with that many rings.
+    pub ring_counts: Vec<(u32, CountRange)>,
+}
+
+impl OutcomeFilter {
+    /// Whether no outcomes are constrained, so that every piece matches.
+    pub fn is_empty(&self) -> bool {
+        self.num_points.is_none()
+            && self.required_colors.is_empty()
+            && self.forbidden_colors.is_empty()
+            && self.ring_counts.is_empty()
+    }
+
+    pub fn matches<C>(&self, data: &RenderData<C>) -> bool {
+        let used = data.colors_used.as_slice();
+        self.num_points
+            .is_none_or(|range| range.contains(data.num_points))
+            && self.required_colors.iter().all(|c| used.contains(c))
+            && !self.forbidden_colors.iter().any(|c| used.contains(c))
+            && self.ring_counts.iter().all(|(rings, range)| {
+                range.contains(data.ring_counts_used.get(rings).copied().unwrap_or(0))
+            })
+    }
+
+    /// Looks up a color by its name in `color_db`, like `fPink`, for
+    /// [`OutcomeFilter::required_colors`] and [`OutcomeFilter::forbidden_colors`].
+    pub fn color_key(color_db: &ColorDb, name: &str) -> anyhow::Result<ColorKey> {
+        color_db
+            .color_key(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown color {:?}", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::traits::{ColorPalette, FlowField, Spacing, Version};
+
+    #[test]
+    fn test_trait_filter_parse() {
+        let filter: TraitFilter = "flow_field=spiral spacing=dense palette in [seoul, fidenza]"
+            .parse()
+            .unwrap();
+        let mut traits = Traits::from_seed(&[0; 32]);
+        traits.flow_field = FlowField::Spiral;
+        traits.spacing = Spacing::Dense;
+        traits.color_palette = ColorPalette::Fidenza;
+        assert!(filter.matches(&traits));
+        traits.color_palette = ColorPalette::Austin;
+        assert!(!filter.matches(&traits));
+
+        let conflicting: TraitFilter = "spacing=dense spacing in [sparse,medium]".parse().unwrap();
+        assert!(conflicting.is_empty());
+        assert!(!filter.is_empty());
+        assert!("spacing=roomy".parse::<TraitFilter>().is_err());
+        assert!("palette in seoul".parse::<TraitFilter>().is_err());
+        assert!("version=v0".parse::<TraitFilter>().is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let space = SearchSpace {
+            address: [0x12; 20],
+            start_nonce: 7,
+            num_nonces: 1,
+        };
+        // Traits read from the low bits, so that matches come early.
+        let filter: TraitFilter = "flow_field=spiral turbulence in [low,high] bullseye_rings=1,7"
+            .parse()
+            .unwrap();
+        let result = search(&space, &filter, 5, |_| true);
+        assert_eq!(result.seeds.len(), 5);
+        let mut prev = None;
+        for seed in &result.seeds {
+            assert_eq!(seed[..20], [0x12; 20]);
+            assert_eq!(seed[20..26], [0, 0, 0, 0, 0, 7]);
+            let traits = Traits::from_seed(seed);
+            assert!(filter.matches(&traits));
+            assert_eq!(traits.version, Version::V1);
+            assert!(prev < Some(*seed), "results should be in search order");
+            prev = Some(*seed);
+        }
+
+        // A sequential scan finds the same seeds.
+        let expected: Vec<[u8; 32]> = (0..space.len())
+            .map(|i| space.candidate(i))
+            .filter(|seed| filter.matches(&Traits::from_seed(seed)))
+            .take(5)
+            .collect();
+        assert_eq!(result.seeds, expected);
+
+        let rejected = search(&space, &filter, 5, |seed| seed[30] % 2 == 0);
+        assert!(rejected.seeds.iter().all(|seed| seed[30] % 2 == 0));
+        assert_eq!(rejected.seeds.len(), 5);
+    }
+
+    #[test]
+    fn test_count_range_parse() {
+        let range = |s: &str| s.parse::<CountRange>().unwrap();
+        assert_eq!(
+            range("10..20"),
+            CountRange {
+                min: 10,
+                max: Some(19)
+            }
+        );
+        assert_eq!(
+            range("10..=20"),
+            CountRange {
+                min: 10,
+                max: Some(20)
+            }
+        );
+        assert_eq!(range("10.."), CountRange { min: 10, max: None });
+        assert_eq!(
+            range("..20"),
+            CountRange {
+                min: 0,
+                max: Some(19)
+            }
+        );
+        assert_eq!(
+            range("15"),
+            CountRange {
+                min: 15,
+                max: Some(15)
+            }
+        );
+        assert!("..0".parse::<CountRange>().is_err());
+        assert!("ten..".parse::<CountRange>().is_err());
+    }
+
+    #[test]
+    fn test_outcome_filter() {
+        let color_db = ColorDb::from_bundle();
+        let seed =
+            hex_literal::hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let data = crate::art::analyze(&seed, &color_db, &Default::default());
+        let used = data.colors_used.as_slice();
+        let unused = (0..)
+            .find(|key| color_db.color(*key).is_some() && !used.contains(key))
+            .unwrap();
+        let (&rings, &count) = data.ring_counts_used.iter().next().unwrap();
+
+        let mut filter = OutcomeFilter {
+            num_points: Some(CountRange {
+                min: data.num_points,
+                max: Some(data.num_points),
+            }),
+            required_colors: vec![used[0]],
+            forbidden_colors: vec![unused],
+            ring_counts: vec![(
+                rings,
+                CountRange {
+                    min: count,
+                    max: None,
+                },
+            )],
+        };
+        assert!(filter.matches(&data));
+        filter.ring_counts.push((
+            rings,
+            CountRange {
+                min: count + 1,
+                max: None,
+            },
+        ));
+        assert!(!filter.matches(&data));
+        filter.ring_counts.pop();
+        filter.forbidden_colors.push(used[0]);
+        assert!(!filter.matches(&data));
+        assert!(OutcomeFilter::default().matches(&data));
+        assert!(OutcomeFilter::color_key(&color_db, "fPink").is_ok());
+        assert!(OutcomeFilter::color_key(&color_db, "Plaid").is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use std::ops::RangeInclusive;
 
 use crate::{
-    config::Config,
+    config::LayoutOptions,
     math::{dist, dist_lower_bound, dist_upper_bound},
 };
 
@@ -38,7 +38,7 @@ pub struct Collider {
 }
 
 impl Sectors {
-    pub fn new(config: &Config, left: f64, right: f64, top: f64, bottom: f64) -> Self {
+    pub fn new(options: &LayoutOptions, left: f64, right: f64, top: f64, bottom: f64) -> Self {
         let ix = Indexer::new(f64::min(left, right), f64::max(left, right));
         let iy = Indexer::new(f64::min(top, bottom), f64::max(top, bottom));
         let sectors = {
@@ -48,7 +48,7 @@ impl Sectors {
             slice_of_arrays.try_into().unwrap()
         };
         Sectors {
-            fast_collisions: config.fast_collisions,
+            fast_collisions: options.fast_collisions,
             ix,
             iy,
             sectors,
@@ -0,0 +1,444 @@
+//! The anatomy of a QQL seed.
+//!
+//! A seed is 32 bytes: the 20-byte address of the only wallet allowed to mint it, 6 nonce bytes,
+//! a 2-byte sentinel, and a 32-bit trait field. Seeds from the official minting UI have an `ffff`
+//! sentinel, and then the top nibble of the trait field is a version number. The low bits of the
+//! trait field select each trait; see [`TraitBits`].
+
+use std::ops::Range;
+
+use crate::rand::Rng;
+use crate::search::TraitFilter;
+use crate::traits::{TraitBits, Traits, Version};
+
+pub const ADDRESS_BYTES: Range<usize> = 0..20;
+pub const NONCE_BYTES: Range<usize> = 20..26;
+pub const SENTINEL_BYTES: Range<usize> = 26..28;
+pub const TRAIT_BYTES: Range<usize> = 28..32;
+
+/// Sentinel marking a seed as versioned.
+pub const SENTINEL: [u8; 2] = [0xff, 0xff];
+
+/// Bits of the trait field that hold the version, for seeds with a [`SENTINEL`].
+pub const VERSION_BITS: Range<u32> = 28..32;
+
+/// A seed broken down into its parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedInfo {
+    pub seed: [u8; 32],
+    /// Address of the wallet that may mint this seed.
+    pub address: [u8; 20],
+    pub nonce: [u8; 6],
+    pub sentinel: [u8; 2],
+    /// The top nibble of the trait field, which is the version only if [`SeedInfo::sentinel`] is
+    /// [`SENTINEL`].
+    pub version_nibble: u8,
+    pub version: Version,
+    /// Bytes `28..32` of the seed, as a big-endian integer.
+    pub trait_field: u32,
+    pub traits: Traits,
+    /// Where each trait was read from in the trait field, from the least significant bits up.
+    pub trait_bits: Vec<TraitBits>,
+    /// Bits of the trait field above all traits, which don't affect them. These include the
+    /// [`VERSION_BITS`].
+    pub unused_bits: Range<u32>,
+}
+
+impl SeedInfo {
+    pub fn new(seed: &[u8; 32]) -> Self {
+        let (traits, trait_bits) = Traits::decode(seed);
+        let trait_field = u32::from_be_bytes(seed[TRAIT_BYTES].try_into().unwrap());
+        let traits_end = trait_bits.last().map_or(0, |b| b.bits.end);
+        SeedInfo {
+            seed: *seed,
+            address: seed[ADDRESS_BYTES].try_into().unwrap(),
+            nonce: seed[NONCE_BYTES].try_into().unwrap(),
+            sentinel: seed[SENTINEL_BYTES].try_into().unwrap(),
+            version_nibble: (trait_field >> VERSION_BITS.start) as u8,
+            version: traits.version,
+            trait_field,
+            traits,
+            trait_bits,
+            unused_bits: traits_end..u32::BITS,
+        }
+    }
+
+    /// Whether this seed has the [`SENTINEL`] of seeds from the official minting UI.
+    pub fn has_sentinel(&self) -> bool {
+        self.sentinel == SENTINEL
+    }
+
+    /// The value of the [`SeedInfo::unused_bits`].
+    pub fn unused(&self) -> u32 {
+        bit_range(self.trait_field, &self.unused_bits)
+    }
+}
+
+/// Version nibble for [`compose`]d unversioned seeds that have a [`SENTINEL`] anyway. No
+/// version uses it.
+const UNVERSIONED_NIBBLE: u8 = 0xf;
+
+/// Why [`compose`] can't build a seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComposeError {
+    /// Versioned seeds must have the [`SENTINEL`], but the given bytes have another sentinel.
+    MissingSentinel { version: Version, sentinel: [u8; 2] },
+}
+
+impl std::fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComposeError::MissingSentinel { version, sentinel } => write!(
+                f,
+                "{:?} seeds must have sentinel ffff at bytes 26..28, but these bytes have {}",
+                version,
+                hex::encode(sentinel)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ComposeError {}
+
+/// Builds the seed that starts with `prefix` (the address, nonce, and sentinel: bytes `0..28`)
+/// and decodes to exactly `traits`. The inverse of [`Traits::from_seed`].
+///
+/// Each trait is stored as its option index, which is the smallest raw value that decodes to it.
+/// The version nibble is set from `traits.version`, and other unused bits are zero. Unversioned
+/// seeds whose prefix has the [`SENTINEL`] anyway get a version nibble that no version uses.
+pub fn compose(prefix: &[u8; 28], traits: &Traits) -> Result<[u8; 32], ComposeError> {
+    let sentinel: [u8; 2] = prefix[SENTINEL_BYTES].try_into().unwrap();
+    let version_nibble = match (traits.version, sentinel == SENTINEL) {
+        (Version::V0, true) => 0,
+        (Version::V1, true) => 1,
+        (Version::Unversioned, true) => UNVERSIONED_NIBBLE,
+        (Version::Unversioned, false) => 0,
+        (version @ (Version::V0 | Version::V1), false) => {
+            return Err(ComposeError::MissingSentinel { version, sentinel })
+        }
+    };
+
+    // The bit layout doesn't depend on the seed, so read it off of any seed.
+    let (_, layout) = Traits::decode(&[0; 32]);
+    let mut trait_field = u32::from(version_nibble) << VERSION_BITS.start;
+    for (bits, (name, index)) in layout.iter().zip(traits.option_indices()) {
+        debug_assert_eq!(bits.name, name, "trait order mismatch");
+        trait_field |= (index as u32) << bits.bits.start;
+    }
+
+    let mut seed = [0; 32];
+    seed[..28].copy_from_slice(prefix);
+    seed[TRAIT_BYTES].copy_from_slice(&trait_field.to_be_bytes());
+    Ok(seed)
+}
+
+/// How [`mutations`] vary a seed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MutationKind {
+    /// One mutation for each other option of each trait.
+    Options,
+    /// One mutation for each bit of each trait, flipped.
+    Bits,
+}
+
+/// A seed that differs from another only in the bits of one trait.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutation {
+    pub seed: [u8; 32],
+    /// The trait whose bits changed, like `"flow_field"` or `"bullseye_rings.one"`.
+    pub trait_name: &'static str,
+    /// The bits of the trait field that changed.
+    pub bits: Range<u32>,
+    /// The official name of the trait's option before and after the change.
+    pub from: String,
+    pub to: String,
+}
+
+impl Mutation {
+    /// Whether the change of bits changes the trait at all. Flipping a bit can wrap around to the
+    /// same option when the number of options isn't a power of two.
+    pub fn changes_trait(&self) -> bool {
+        self.from != self.to
+    }
+}
+
+/// Variants of `seed` that each change one trait, by setting its bits to another option or by
+/// flipping one of its bits. All other bits, including the version nibble, are kept. Mutations
+/// are in decoding order, as in [`SeedInfo::trait_bits`].
+pub fn mutations(seed: &[u8; 32], kind: MutationKind) -> Vec<Mutation> {
+    let info = SeedInfo::new(seed);
+    let mut result = Vec::new();
+    for bits in &info.trait_bits {
+        let raws: Vec<u32> = match kind {
+            MutationKind::Options => (0..bits.num_options as u32)
+                .filter(|&index| index as usize != bits.index)
+                .collect(),
+            MutationKind::Bits => (0..bits.bits.end - bits.bits.start)
+                .map(|bit| bits.raw ^ (1 << bit))
+                .collect(),
+        };
+        for raw in raws {
+            let mask = bit_range(u32::MAX, &bits.bits) << bits.bits.start;
+            let trait_field = (info.trait_field & !mask) | (raw << bits.bits.start);
+            let mut mutated = *seed;
+            mutated[TRAIT_BYTES].copy_from_slice(&trait_field.to_be_bytes());
+            let (_, mutated_bits) = Traits::decode(&mutated);
+            let changed = mutated_bits
+                .into_iter()
+                .find(|b| b.name == bits.name)
+                .expect("trait layout doesn't depend on the seed");
+            result.push(Mutation {
+                seed: mutated,
+                trait_name: bits.name,
+                bits: match kind {
+                    MutationKind::Options => bits.bits.clone(),
+                    MutationKind::Bits => {
+                        let bit = bits.bits.start + (raw ^ bits.raw).trailing_zeros();
+                        bit..bit + 1
+                    }
+                },
+                from: bits.value.clone(),
+                to: changed.value,
+            });
+        }
+    }
+    result
+}
+
+/// An [`Rng`] seeded from fresh randomness, for [`random`] seeds that differ on every run.
+pub fn fresh_rng() -> Rng {
+    use std::hash::{BuildHasher, Hasher};
+    // The standard library seeds each `RandomState` from the operating system.
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+    hasher.write_u128(now.map_or(0, |d| d.as_nanos()));
+    Rng::from_seed(&hasher.finish().to_le_bytes())
+}
+
+/// An [`Rng`] determined by `passphrase`, for [`random`] seeds that can be named and generated
+/// again later.
+pub fn passphrase_rng(passphrase: &str) -> Rng {
+    Rng::from_seed(passphrase.as_bytes())
+}
+
+/// Generates a random V1 seed whose traits match `filter`, or `None` if no traits match.
+///
+/// The seed has the [`SENTINEL`] and version nibble `1`, like seeds from the official minting UI.
+/// The address (unless given), nonce, and trait bits are drawn from `rng`, with each trait's bits
+/// uniform over the raw values that `filter` allows. So traits are as likely as when minting,
+/// given the constraints. The same `rng` state always gives the same seed.
+pub fn random(rng: &mut Rng, address: Option<&[u8; 20]>, filter: &TraitFilter) -> Option<[u8; 32]> {
+    if filter.is_empty() {
+        return None;
+    }
+    let mut next_u32 = || (rng.rnd() * 2f64.powi(32)) as u32;
+
+    let mut seed = [0; 32];
+    // Always draw the address, so that fixing it doesn't change the rest of the seed.
+    for byte in &mut seed[..NONCE_BYTES.end] {
+        *byte = next_u32() as u8;
+    }
+    if let Some(address) = address {
+        seed[ADDRESS_BYTES].copy_from_slice(address);
+    }
+    seed[SENTINEL_BYTES].copy_from_slice(&SENTINEL);
+
+    let (_, layout) = Traits::decode(&[0; 32]);
+    let traits_end = layout.last().map_or(0, |b| b.bits.end);
+    let unused_below_version = (1 << VERSION_BITS.start) - (1 << traits_end);
+    let mut trait_field = (1 << VERSION_BITS.start) | (next_u32() & unused_below_version);
+    for (field, bits) in layout.iter().enumerate() {
+        let raws: Vec<u32> = (0..1 << (bits.bits.end - bits.bits.start))
+            .filter(|&raw| filter.allows(field, raw as usize % bits.num_options))
+            .collect();
+        let raw = raws[next_u32() as usize % raws.len()];
+        trait_field |= raw << bits.bits.start;
+    }
+    seed[TRAIT_BYTES].copy_from_slice(&trait_field.to_be_bytes());
+    Some(seed)
+}
+
+/// Extracts bits `range` of `field`, shifted down to the least significant bits.
+pub fn bit_range(field: u32, range: &Range<u32>) -> u32 {
+    let width = range.end - range.start;
+    if width == 0 {
+        return 0;
+    }
+    (field >> range.start) & (u32::MAX >> (u32::BITS - width))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn test_seed_info() {
+        let seed = hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let info = SeedInfo::new(&seed);
+        assert_eq!(
+            info.address,
+            hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a17")
+        );
+        assert_eq!(info.nonce, hex!("8c012cd49a85"));
+        assert!(info.has_sentinel());
+        assert_eq!((info.version_nibble, info.version), (1, Version::V1));
+        assert_eq!(info.trait_field, 0x14c54b46);
+        assert_eq!(info.unused_bits, 27..32);
+        assert_eq!(info.unused(), 0b00010);
+
+        // The trait bits tile the low bits of the field, and each one decodes to its trait.
+        let mut end = 0;
+        for bits in &info.trait_bits {
+            assert_eq!(bits.bits.start, end);
+            assert_eq!(bits.raw, bit_range(info.trait_field, &bits.bits));
+            assert_eq!(bits.index, bits.raw as usize % bits.num_options);
+            end = bits.bits.end;
+        }
+        let flow_field = &info.trait_bits[0];
+        assert_eq!(
+            (flow_field.name, flow_field.bits.clone(), flow_field.raw),
+            ("flow_field", 0..3, 6)
+        );
+        assert_eq!(flow_field.value, "Circular");
+        let palette = info
+            .trait_bits
+            .iter()
+            .find(|b| b.name == "color_palette")
+            .unwrap();
+        assert_eq!((palette.bits.clone(), palette.num_options), (22..25, 7));
+        assert_eq!(palette.value, "Fidenza");
+    }
+
+    #[test]
+    fn test_compose_roundtrip() {
+        let mut seeds = vec![
+            hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46"),
+            hex!("e03a5189dac8182085e4adf66281f679fff2291d52a252d295b02feda9118a49"),
+        ];
+        // Plus every value of the low 16 trait bits, for some versioned seeds.
+        for low in (0..=u16::MAX).step_by(7) {
+            let mut seed = seeds[0];
+            seed[30..32].copy_from_slice(&low.to_be_bytes());
+            seeds.push(seed);
+        }
+        for seed in seeds {
+            let traits = Traits::from_seed(&seed);
+            let prefix: [u8; 28] = seed[..28].try_into().unwrap();
+            let composed = compose(&prefix, &traits).unwrap();
+            assert_eq!(composed[..28], seed[..28]);
+            assert_eq!(Traits::from_seed(&composed), traits);
+        }
+    }
+
+    #[test]
+    fn test_compose_version() {
+        let seed = hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let prefix: [u8; 28] = seed[..28].try_into().unwrap();
+        let mut traits = Traits::from_seed(&seed);
+        traits.flow_field = crate::traits::FlowField::Spiral;
+        traits.version = Version::V0;
+        let info = SeedInfo::new(&compose(&prefix, &traits).unwrap());
+        assert_eq!((info.version_nibble, info.traits), (0, traits.clone()));
+
+        traits.version = Version::Unversioned;
+        let info = SeedInfo::new(&compose(&prefix, &traits).unwrap());
+        assert_eq!(info.version, Version::Unversioned);
+        assert_eq!(info.traits, traits);
+
+        let mut unsentineled = prefix;
+        unsentineled[26] = 0x2f;
+        traits.version = Version::V1;
+        assert_eq!(
+            compose(&unsentineled, &traits).unwrap_err().to_string(),
+            "V1 seeds must have sentinel ffff at bytes 26..28, but these bytes have 2fff"
+        );
+    }
+
+    #[test]
+    fn test_random() {
+        let filter: TraitFilter = "flow_field=spiral palette in [seoul,fidenza]"
+            .parse()
+            .unwrap();
+        let address = hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a17");
+        let mut rng = passphrase_rng("hello");
+        let mut seeds = Vec::new();
+        for _ in 0..100 {
+            let seed = random(&mut rng, Some(&address), &filter).unwrap();
+            let info = SeedInfo::new(&seed);
+            assert_eq!(info.address, address);
+            assert!(info.has_sentinel());
+            assert_eq!((info.version_nibble, info.version), (1, Version::V1));
+            assert!(filter.matches(&info.traits));
+            seeds.push(seed);
+        }
+        seeds.dedup();
+        assert_eq!(seeds.len(), 100);
+
+        // The same passphrase gives the same seeds, and fixing the address changes nothing else.
+        let again = random(&mut passphrase_rng("hello"), None, &filter).unwrap();
+        assert_ne!(again[ADDRESS_BYTES], address);
+        assert_eq!(again[20..], seeds[0][20..]);
+
+        let mut impossible = TraitFilter::default();
+        impossible.restrict("flow_field", &["spiral"]).unwrap();
+        impossible.restrict("flow_field", &["horizontal"]).unwrap();
+        assert_eq!(random(&mut fresh_rng(), None, &impossible), None);
+    }
+
+    #[test]
+    fn test_mutations() {
+        let seed = hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let info = SeedInfo::new(&seed);
+
+        let options = mutations(&seed, MutationKind::Options);
+        let num_options: usize = info.trait_bits.iter().map(|b| b.num_options - 1).sum();
+        assert_eq!(options.len(), num_options);
+        for mutation in &options {
+            assert!(mutation.changes_trait());
+            let mutated = SeedInfo::new(&mutation.seed);
+            assert_eq!(mutated.seed[..28], seed[..28]);
+            assert_eq!(mutated.version, Version::V1);
+            assert_eq!(mutated.unused(), info.unused());
+            // Only the mutated trait differs.
+            for (before, after) in info.trait_bits.iter().zip(&mutated.trait_bits) {
+                assert_eq!(
+                    before.value == after.value,
+                    before.name != mutation.trait_name
+                );
+            }
+        }
+        let spiral = options
+            .iter()
+            .find(|m| m.trait_name == "flow_field" && m.to == "Spiral")
+            .unwrap();
+        assert_eq!(
+            (spiral.from.as_str(), spiral.bits.clone()),
+            ("Circular", 0..3)
+        );
+
+        let bits = mutations(&seed, MutationKind::Bits);
+        assert_eq!(bits.len(), info.unused_bits.start as usize);
+        for (i, mutation) in bits.iter().enumerate() {
+            assert_eq!(mutation.bits, i as u32..i as u32 + 1);
+            let trait_field = u32::from_be_bytes(mutation.seed[TRAIT_BYTES].try_into().unwrap());
+            assert_eq!(trait_field ^ info.trait_field, 1 << i);
+        }
+        // Turbulence has three options in two bits, so raw values 0 and 2 are both "None".
+        let turbulence: Vec<_> = bits
+            .iter()
+            .filter(|m| m.trait_name == "turbulence")
+            .collect();
+        assert_eq!(turbulence.len(), 2);
+    }
+
+    #[test]
+    fn test_unversioned_seed_info() {
+        let seed = hex!("e03a5189dac8182085e4adf66281f679fff2291d52a252d295b02feda9118a49");
+        let info = SeedInfo::new(&seed);
+        assert!(!info.has_sentinel());
+        assert_eq!(info.sentinel, hex!("2fed"));
+        assert_eq!(info.version_nibble, 0xa);
+        assert_eq!(info.version, Version::Unversioned);
+    }
+}
@@ -0,0 +1,74 @@
+//! ERC-721 token metadata, in the JSON format that marketplaces like OpenSea read.
+
+use serde::{Deserialize, Serialize};
+
+use crate::traits::Traits;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub name: String,
+    /// Path or URL of an image of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    pub attributes: Vec<Attribute>,
+}
+
+/// One trait of a token, with official names like `"Flow Field"` and `"Random Radial"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attribute {
+    pub trait_type: String,
+    pub value: String,
+}
+
+impl TokenMetadata {
+    /// Metadata for a seed, named by its hex and with one attribute per trait. `thumbnail` is
+    /// stored as the `image`.
+    pub fn new(seed: &[u8; 32], thumbnail: Option<String>) -> Self {
+        let attributes = Traits::from_seed(seed)
+            .attributes()
+            .into_iter()
+            .map(|(trait_type, value)| Attribute {
+                trait_type: trait_type.to_string(),
+                value,
+            })
+            .collect();
+        TokenMetadata {
+            name: format!("QQL 0x{}", hex::encode(seed)),
+            image: thumbnail,
+            attributes,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("metadata is always serializable")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn test_token_metadata() {
+        let seed = hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let metadata = TokenMetadata::new(&seed, Some("thumbs/qql.png".to_string()));
+        let json: serde_json::Value = serde_json::from_str(&metadata.to_json()).unwrap();
+        assert_eq!(json["image"], "thumbs/qql.png");
+        let attributes = json["attributes"].as_array().unwrap();
+        assert_eq!(attributes.len(), 12);
+        assert_eq!(
+            attributes[0],
+            serde_json::json!({"trait_type": "Flow Field", "value": "Circular"})
+        );
+        assert_eq!(
+            attributes[6],
+            serde_json::json!({"trait_type": "Bullseye Rings", "value": "1, 7"})
+        );
+
+        let no_image = TokenMetadata::new(&seed, None).to_json();
+        assert!(!no_image.contains("image"));
+        let parsed: TokenMetadata = serde_json::from_str(&no_image).unwrap();
+        assert_eq!(parsed, TokenMetadata::new(&seed, None));
+    }
+}
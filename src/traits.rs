@@ -1,4 +1,11 @@
-#[derive(Debug, PartialEq, Eq, Clone)]
+use std::fmt::Display;
+use std::ops::Range;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serializes with the official name of each value, like `"Random Radial"`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Traits {
     pub flow_field: FlowField,
     pub turbulence: Turbulence,
@@ -17,45 +24,48 @@ pub struct Traits {
 
 impl Traits {
     pub fn from_seed(raw_seed: &[u8; 32]) -> Self {
-        let mut remaining = u32::from_be_bytes(raw_seed[28..32].try_into().unwrap());
-        fn pluck<T: Copy>(seed: &mut u32, options: &'static [(T, u32)]) -> T {
-            if options.is_empty() {
-                panic!("no options");
-            }
-            let num_bits: u32 = options.len().next_power_of_two().ilog2();
-            let mask: u32 = (1 << num_bits) - 1;
-            let index = *seed & mask;
-            *seed >>= num_bits;
-            let (option, _weight) = options[(index % options.len() as u32) as usize];
-            option
-        }
+        Traits::read(raw_seed, None)
+    }
+
+    /// Decodes traits like [`Traits::from_seed`], and also reports the bits of the trait field
+    /// that each trait was read from, in the order they're read.
+    pub fn decode(raw_seed: &[u8; 32]) -> (Self, Vec<TraitBits>) {
+        let mut bits = Vec::new();
+        let traits = Traits::read(raw_seed, Some(&mut bits));
+        (traits, bits)
+    }
+
+    fn read(raw_seed: &[u8; 32], record: Option<&mut Vec<TraitBits>>) -> Self {
+        let mut reader = TraitReader {
+            remaining: u32::from_be_bytes(raw_seed[crate::seed::TRAIT_BYTES].try_into().unwrap()),
+            offset: 0,
+            record,
+        };
+        let ring_options = &[(true, 1), (false, 1)];
         Traits {
-            flow_field: pluck(&mut remaining, FlowField::options()),
-            turbulence: pluck(&mut remaining, Turbulence::options()),
-            margin: pluck(&mut remaining, Margin::options()),
-            color_variety: pluck(&mut remaining, ColorVariety::options()),
-            color_mode: pluck(&mut remaining, ColorMode::options()),
-            structure: pluck(&mut remaining, Structure::options()),
-            bullseye_rings: {
-                let ring_options = &[(true, 1), (false, 1)];
-                BullseyeRings {
-                    one: pluck(&mut remaining, ring_options),
-                    three: pluck(&mut remaining, ring_options),
-                    seven: pluck(&mut remaining, ring_options),
-                }
+            flow_field: reader.pluck("flow_field", FlowField::options()),
+            turbulence: reader.pluck("turbulence", Turbulence::options()),
+            margin: reader.pluck("margin", Margin::options()),
+            color_variety: reader.pluck("color_variety", ColorVariety::options()),
+            color_mode: reader.pluck("color_mode", ColorMode::options()),
+            structure: reader.pluck("structure", Structure::options()),
+            bullseye_rings: BullseyeRings {
+                one: reader.pluck("bullseye_rings.one", ring_options),
+                three: reader.pluck("bullseye_rings.three", ring_options),
+                seven: reader.pluck("bullseye_rings.seven", ring_options),
             },
-            ring_thickness: pluck(&mut remaining, RingThickness::options()),
-            ring_size: pluck(&mut remaining, RingSize::options()),
-            size_variety: pluck(&mut remaining, SizeVariety::options()),
-            color_palette: pluck(&mut remaining, ColorPalette::options()),
-            spacing: pluck(&mut remaining, Spacing::options()),
+            ring_thickness: reader.pluck("ring_thickness", RingThickness::options()),
+            ring_size: reader.pluck("ring_size", RingSize::options()),
+            size_variety: reader.pluck("size_variety", SizeVariety::options()),
+            color_palette: reader.pluck("color_palette", ColorPalette::options()),
+            spacing: reader.pluck("spacing", Spacing::options()),
             version: Traits::get_version(raw_seed),
         }
     }
 
     fn get_version(raw_seed: &[u8; 32]) -> Version {
-        let sentinel = &raw_seed[26..28];
-        if sentinel != [0xff, 0xff] {
+        let sentinel = &raw_seed[crate::seed::SENTINEL_BYTES];
+        if sentinel != crate::seed::SENTINEL {
             return Version::Unversioned;
         }
         match raw_seed[28] >> 4 {
@@ -64,8 +74,266 @@ impl Traits {
             _ => Version::Unversioned,
         }
     }
+
+    /// The option index of each trait, named and ordered as in [`Traits::decode`]. Doesn't
+    /// include the version, which isn't read from the trait bits.
+    pub fn option_indices(&self) -> [(&'static str, usize); 14] {
+        fn index<T: PartialEq>(options: &[(T, u32)], value: T) -> usize {
+            options
+                .iter()
+                .position(|(option, _weight)| *option == value)
+                .expect("value is one of its options")
+        }
+        let ring_options = &[(true, 1), (false, 1)];
+        [
+            ("flow_field", index(FlowField::options(), self.flow_field)),
+            ("turbulence", index(Turbulence::options(), self.turbulence)),
+            ("margin", index(Margin::options(), self.margin)),
+            (
+                "color_variety",
+                index(ColorVariety::options(), self.color_variety),
+            ),
+            ("color_mode", index(ColorMode::options(), self.color_mode)),
+            ("structure", index(Structure::options(), self.structure)),
+            (
+                "bullseye_rings.one",
+                index(ring_options, self.bullseye_rings.one),
+            ),
+            (
+                "bullseye_rings.three",
+                index(ring_options, self.bullseye_rings.three),
+            ),
+            (
+                "bullseye_rings.seven",
+                index(ring_options, self.bullseye_rings.seven),
+            ),
+            (
+                "ring_thickness",
+                index(RingThickness::options(), self.ring_thickness),
+            ),
+            ("ring_size", index(RingSize::options(), self.ring_size)),
+            (
+                "size_variety",
+                index(SizeVariety::options(), self.size_variety),
+            ),
+            (
+                "color_palette",
+                index(ColorPalette::options(), self.color_palette),
+            ),
+            ("spacing", index(Spacing::options(), self.spacing)),
+        ]
+    }
+
+    /// Official `(trait, value)` names of each trait, as in token metadata, like
+    /// `("Flow Field", "Random Radial")`. Doesn't include the version.
+    pub fn attributes(&self) -> [(&'static str, String); 12] {
+        [
+            (FlowField::TRAIT_NAME, self.flow_field.to_string()),
+            (Turbulence::TRAIT_NAME, self.turbulence.to_string()),
+            (Margin::TRAIT_NAME, self.margin.to_string()),
+            (ColorVariety::TRAIT_NAME, self.color_variety.to_string()),
+            (ColorMode::TRAIT_NAME, self.color_mode.to_string()),
+            (Structure::TRAIT_NAME, self.structure.to_string()),
+            (BullseyeRings::TRAIT_NAME, self.bullseye_rings.to_string()),
+            (RingThickness::TRAIT_NAME, self.ring_thickness.to_string()),
+            (RingSize::TRAIT_NAME, self.ring_size.to_string()),
+            (SizeVariety::TRAIT_NAME, self.size_variety.to_string()),
+            (ColorPalette::TRAIT_NAME, self.color_palette.to_string()),
+            (Spacing::TRAIT_NAME, self.spacing.to_string()),
+        ]
+    }
+
+    /// Sets one trait by name, as in [`Traits::decode`], from a string like `"random_radial"`.
+    /// Also accepts `version` (`v0`, `v1`, or `unversioned`) and `bullseye_rings`, as a list of
+    /// ring sizes like `"1,7"` or `"none"`.
+    pub fn set(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name.replace('-', "_").as_str() {
+            "flow_field" => self.flow_field = value.parse()?,
+            "turbulence" => self.turbulence = value.parse()?,
+            "margin" => self.margin = value.parse()?,
+            "color_variety" => self.color_variety = value.parse()?,
+            "color_mode" => self.color_mode = value.parse()?,
+            "structure" => self.structure = value.parse()?,
+            "bullseye_rings" => self.bullseye_rings = value.parse()?,
+            "bullseye_rings.one" => self.bullseye_rings.one = parse_bool(value)?,
+            "bullseye_rings.three" => self.bullseye_rings.three = parse_bool(value)?,
+            "bullseye_rings.seven" => self.bullseye_rings.seven = parse_bool(value)?,
+            "ring_thickness" => self.ring_thickness = value.parse()?,
+            "ring_size" => self.ring_size = value.parse()?,
+            "size_variety" => self.size_variety = value.parse()?,
+            "color_palette" => self.color_palette = value.parse()?,
+            "spacing" => self.spacing = value.parse()?,
+            "version" => self.version = value.parse()?,
+            _ => anyhow::bail!("Unknown trait {:?}", name),
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(s: &str) -> anyhow::Result<bool> {
+    match s {
+        "true" | "yes" => Ok(true),
+        "false" | "no" => Ok(false),
+        _ => anyhow::bail!("Invalid boolean {:?}; expected true or false", s),
+    }
+}
+
+/// Lowercases a name and drops everything but letters and digits, so that `RandomRadial`,
+/// `random_radial`, and `Random Radial` all compare equal.
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Converts a `CamelCase` variant name to `snake_case`.
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+/// Where one trait was read from in the 32-bit trait field at the end of a seed (bytes `28..32`,
+/// big-endian). Traits are read from the least significant bits up, each taking just enough bits
+/// to index its options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraitBits {
+    /// Name of the trait, like `"flow_field"` or `"bullseye_rings.one"`.
+    pub name: &'static str,
+    /// Bit positions in the trait field, counting from the least significant bit.
+    pub bits: Range<u32>,
+    /// The value of those bits.
+    pub raw: u32,
+    /// Index of the chosen option. When the number of options isn't a power of two, some raw
+    /// values wrap around: the index is `raw % num_options`.
+    pub index: usize,
+    pub num_options: usize,
+    /// The official name of the chosen option, like `"Random Radial"`.
+    pub value: String,
+}
+
+struct TraitReader<'a> {
+    remaining: u32,
+    offset: u32,
+    record: Option<&'a mut Vec<TraitBits>>,
+}
+
+impl TraitReader<'_> {
+    fn pluck<T: Copy + Display>(&mut self, name: &'static str, options: &'static [(T, u32)]) -> T {
+        if options.is_empty() {
+            panic!("no options");
+        }
+        let num_bits: u32 = options.len().next_power_of_two().ilog2();
+        let mask: u32 = (1 << num_bits) - 1;
+        let raw = self.remaining & mask;
+        self.remaining >>= num_bits;
+        let index = (raw % options.len() as u32) as usize;
+        let (option, _weight) = options[index];
+        if let Some(record) = &mut self.record {
+            record.push(TraitBits {
+                name,
+                bits: self.offset..self.offset + num_bits,
+                raw,
+                index,
+                num_options: options.len(),
+                value: option.to_string(),
+            });
+        }
+        self.offset += num_bits;
+        option
+    }
+}
+
+macro_rules! trait_enum {
+    (
+        $trait:ident $trait_name:literal {
+            $($value:ident($weight:expr) $($display:literal)?),* $(,)?
+        }
+    ) => {
+        #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+        pub enum $trait {
+            $($value),*
+        }
+        impl $trait {
+            /// The official name of this trait, as in token metadata.
+            pub const TRAIT_NAME: &'static str = $trait_name;
+
+            pub fn options() -> &'static [(Self, u32)] {
+                &[$((Self::$value, $weight)),*]
+            }
+
+            /// The official name of this value, like `"Random Radial"`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$value => display_name!(stringify!($value) $(, $display)?)),*
+                }
+            }
+        }
+        impl Display for $trait {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+        /// Expects a variant name in any case, with or without separators, like `random_radial`
+        /// or `Random Radial`.
+        impl FromStr for $trait {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let name = normalize_name(s);
+                $(
+                    if name == normalize_name(stringify!($value)) {
+                        return Ok(Self::$value);
+                    }
+                )*
+                let expected: Vec<String> = vec![$(snake_case(stringify!($value))),*];
+                anyhow::bail!(
+                    "Invalid {} {:?}; expected one of: {}",
+                    $trait_name.to_lowercase(),
+                    s,
+                    expected.join(", ")
+                )
+            }
+        }
+        serde_via_display!($trait);
+    };
+}
+
+/// The official display name of a trait value, if it differs from the variant name.
+macro_rules! display_name {
+    ($default:expr) => {
+        $default
+    };
+    ($default:expr, $display:literal) => {
+        $display
+    };
 }
 
+/// Implements serde traits with the `Display` and `FromStr` impls, so that values are written as
+/// their official names.
+macro_rules! serde_via_display {
+    ($type:ty) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+pub(crate) use serde_via_display;
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Version {
     Unversioned,
@@ -73,6 +341,31 @@ pub enum Version {
     V1,
 }
 
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Version::Unversioned => "Unversioned",
+            Version::V0 => "V0",
+            Version::V1 => "V1",
+        })
+    }
+}
+
+serde_via_display!(Version);
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize_name(s).as_str() {
+            "unversioned" => Ok(Version::Unversioned),
+            "v0" | "0" => Ok(Version::V0),
+            "v1" | "1" => Ok(Version::V1),
+            _ => anyhow::bail!("Invalid version {:?}; expected v0, v1, or unversioned", s),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub struct BullseyeRings {
     pub one: bool,
@@ -80,80 +373,116 @@ pub struct BullseyeRings {
     pub seven: bool,
 }
 
-macro_rules! trait_enum {
-    ($trait:ident { $($value:ident($weight:expr)),* $(,)? }) => {
-        #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-        pub enum $trait {
-            $($value),*
+impl BullseyeRings {
+    /// The official trait name.
+    pub const TRAIT_NAME: &'static str = "Bullseye Rings";
+}
+
+/// Formats as the official trait value: the ring sizes, like `1, 7`, or `None`.
+impl Display for BullseyeRings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sizes: Vec<&str> = [(self.one, "1"), (self.three, "3"), (self.seven, "7")]
+            .into_iter()
+            .filter_map(|(present, size)| present.then_some(size))
+            .collect();
+        if sizes.is_empty() {
+            f.write_str("None")
+        } else {
+            f.write_str(&sizes.join(", "))
         }
-        impl $trait {
-            pub fn options() -> &'static [(Self, u32)] {
-                &[$((Self::$value, $weight)),*]
+    }
+}
+
+serde_via_display!(BullseyeRings);
+
+/// Expects a comma-separated list of ring sizes, like `1,7`, or `none`.
+impl FromStr for BullseyeRings {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rings = BullseyeRings {
+            one: false,
+            three: false,
+            seven: false,
+        };
+        if s.trim().eq_ignore_ascii_case("none") {
+            return Ok(rings);
+        }
+        for size in s.split(',') {
+            match size.trim() {
+                "1" => rings.one = true,
+                "3" => rings.three = true,
+                "7" => rings.seven = true,
+                _ => anyhow::bail!(
+                    "Invalid bullseye ring size {:?}; expected a list of 1, 3, and 7, or none",
+                    size
+                ),
             }
         }
-    };
+        Ok(rings)
+    }
 }
 
-trait_enum!(FlowField {
+trait_enum!(FlowField "Flow Field" {
     Horizontal(3),
     Diagonal(1),
     Vertical(3),
-    RandomLinear(1),
+    RandomLinear(1) "Random Linear",
     Explosive(1),
     Spiral(4),
     Circular(2),
-    RandomRadial(1),
+    RandomRadial(1) "Random Radial",
 });
 
-trait_enum!(Turbulence {
+trait_enum!(Turbulence "Turbulence" {
     None(0),
     Low(3),
     High(1),
 });
 
-trait_enum!(Margin {
+trait_enum!(Margin "Margin" {
     None(1),
     Crisp(1),
     Wide(2),
 });
 
-trait_enum!(ColorVariety {
+trait_enum!(ColorVariety "Color Variety" {
     Low(2),
     Medium(4),
     High(3),
 });
 
-trait_enum!(ColorMode {
+trait_enum!(ColorMode "Color Mode" {
     Simple(2),
     Stacked(3),
     Zebra(1),
 });
 
-trait_enum!(Structure {
+trait_enum!(Structure "Structure" {
     Orbital(1),
     Formation(1),
     Shadows(1),
 });
 
-trait_enum!(RingThickness {
+trait_enum!(RingThickness "Ring Thickness" {
     Thin(1),
     Thick(2),
     Mixed(2),
 });
 
-trait_enum!(SizeVariety {
+trait_enum!(SizeVariety "Size Variety" {
     Constant(1),
     Variable(3),
     Wild(1),
 });
 
-trait_enum!(RingSize {
+trait_enum!(RingSize "Ring Size" {
     Small(4),
     Medium(3),
     Large(1),
 });
 
-trait_enum!(ColorPalette {
+trait_enum!(ColorPalette "Color Palette" {
     Austin(1),
     Berlin(1),
     Edinburgh(2),
@@ -163,7 +492,7 @@ trait_enum!(ColorPalette {
     Seoul(2),
 });
 
-trait_enum!(Spacing {
+trait_enum!(Spacing "Spacing" {
     Dense(2),
     Medium(1),
     Sparse(1),
@@ -202,6 +531,65 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_option_indices_match_decode() {
+        let raw_seed = &hex!("e03a5189dac8182085e4adf66281f679fff2291d52a252d295b02feda9118a49");
+        let (traits, bits) = Traits::decode(raw_seed);
+        let decoded: Vec<(&str, usize)> = bits.iter().map(|b| (b.name, b.index)).collect();
+        assert_eq!(decoded, traits.option_indices());
+    }
+
+    #[test]
+    fn test_set_by_name() {
+        let raw_seed = &hex!("33c9371d25ce44a408f8a6473fbad86bf81e1a178c012cd49a85ffff14c54b46");
+        let mut traits = Traits::from_seed(raw_seed);
+        traits.set("flow_field", "random_radial").unwrap();
+        traits.set("color-palette", "Seoul").unwrap();
+        traits.set("bullseye_rings", "3,7").unwrap();
+        traits.set("version", "v0").unwrap();
+        assert_eq!(traits.flow_field, FlowField::RandomRadial);
+        assert_eq!(traits.color_palette, ColorPalette::Seoul);
+        assert_eq!(
+            traits.bullseye_rings,
+            BullseyeRings {
+                one: false,
+                three: true,
+                seven: true
+            }
+        );
+        assert_eq!(traits.version, Version::V0);
+        assert_eq!(
+            traits.set("spacing", "roomy").unwrap_err().to_string(),
+            "Invalid spacing \"roomy\"; expected one of: dense, medium, sparse"
+        );
+        assert!(traits.set("sparkle", "high").is_err());
+    }
+
+    #[test]
+    fn test_official_names_and_serde() {
+        assert_eq!(FlowField::RandomRadial.to_string(), "Random Radial");
+        assert_eq!(
+            "Random Radial".parse::<FlowField>().unwrap(),
+            FlowField::RandomRadial
+        );
+        assert_eq!(ColorPalette::Seoul.to_string(), "Seoul");
+        let rings = |one, three, seven| BullseyeRings { one, three, seven };
+        assert_eq!(rings(true, false, true).to_string(), "1, 7");
+        assert_eq!(rings(false, false, false).to_string(), "None");
+        assert_eq!(
+            "None".parse::<BullseyeRings>().unwrap(),
+            rings(false, false, false)
+        );
+
+        let raw_seed = &hex!("e03a5189dac8182085e4adf66281f679fff2291d52a252d295b02feda9118a49");
+        let traits = Traits::from_seed(raw_seed);
+        let json = serde_json::to_value(&traits).unwrap();
+        assert_eq!(json["flow_field"], "Diagonal");
+        assert_eq!(json["bullseye_rings"], "1, 3");
+        assert_eq!(json["version"], "Unversioned");
+        assert_eq!(serde_json::from_value::<Traits>(json).unwrap(), traits);
+    }
+
     /// This seed has asymmetrical bullseye rings (1 and 3 but not 7), and is also unversioned
     /// (generated before the spirals patch).
     #[test]
@@ -5,7 +5,7 @@ use std::path::{Path, PathBuf};
 use anyhow::Context;
 use hex_literal::hex;
 use image::ImageFormat;
-use raqote::DrawTarget;
+use qql::canvas::Canvas;
 
 // Set this environment variable to any non-empty string to write golden files (trivially passing
 // the test) instead of checking them.
@@ -21,8 +21,10 @@ fn test_golden(seed: [u8; 32]) -> anyhow::Result<()> {
     ]);
 
     let color_db = qql::color::ColorDb::from_bundle();
-    let mut config = qql::config::Config::default();
-    config.chunks = "2x2".parse().unwrap();
+    let config = qql::config::RenderConfig {
+        chunks: "2x2".parse().unwrap(),
+        ..Default::default()
+    };
 
     let canvas = qql::art::draw(&seed, &color_db, &config, GOLDEN_WIDTH, |_| {}).canvas;
     if std::env::var_os(ENV_UPDATE_GOLDENS).is_some_and(|v| !v.is_empty()) {
@@ -32,12 +34,13 @@ fn test_golden(seed: [u8; 32]) -> anyhow::Result<()> {
     }
 }
 
-fn write_golden(dt: &DrawTarget, golden_filepath: &Path) -> anyhow::Result<()> {
-    dt.write_png(&golden_filepath)
+fn write_golden(canvas: &Canvas, golden_filepath: &Path) -> anyhow::Result<()> {
+    canvas
+        .write_png(golden_filepath, &[])
         .context("Failed to write golden PNG")
 }
 
-fn check_golden(dt: &DrawTarget, golden_filepath: &Path) -> anyhow::Result<()> {
+fn check_golden(canvas: &Canvas, golden_filepath: &Path) -> anyhow::Result<()> {
     let reader = BufReader::new(
         File::open(golden_filepath)
             .with_context(|| format!("Failed to read golden at {}", golden_filepath.display()))?,
@@ -49,14 +52,14 @@ fn check_golden(dt: &DrawTarget, golden_filepath: &Path) -> anyhow::Result<()> {
         .into_rgba8();
 
     assert_eq!(
-        (dt.width() as u32, dt.height() as u32),
+        (canvas.width() as u32, canvas.height() as u32),
         (golden.width(), golden.height())
     );
 
-    let actual_pixels = dt.get_data().iter();
+    let actual_pixels = canvas.straight_pixels();
     let golden_pixels = golden.enumerate_pixels();
     for (actual_px, (x, y, golden_px)) in actual_pixels.zip(golden_pixels) {
-        let [ab, ag, ar, _aa] = actual_px.to_le_bytes();
+        let [ar, ag, ab, _aa] = actual_px;
         let [gr, gg, gb, _ga] = golden_px.0;
         // Use a simple L-infinity norm for now. Can refine if we need to.
         assert_px_close((x, y), (ar, ag, ab), (gr, gg, gb));
@@ -79,7 +82,7 @@ fn assert_px_close((x, y): (u32, u32), actual: (u8, u8, u8), golden: (u8, u8, u8
 }
 
 fn channel_delta(u: u8, v: u8) -> u32 {
-    ((u as i32) - (v as i32)).abs() as u32
+    ((u as i32) - (v as i32)).unsigned_abs()
 }
 
 // Pick some QQLs to cover all the `Structure::*` and `ColorMode::*` options, as well as the linear